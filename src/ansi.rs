@@ -0,0 +1,259 @@
+use std::borrow::Cow;
+use ratatui::{
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+};
+
+// SGR 30-37 / 40-47, then the bright 90-97 / 100-107 variants
+const PALETTE: [Color; 16] = [
+    Color::Black, Color::Red, Color::Green, Color::Yellow, Color::Blue, Color::Magenta, Color::Cyan, Color::Gray,
+    Color::DarkGray, Color::LightRed, Color::LightGreen, Color::LightYellow, Color::LightBlue, Color::LightMagenta, Color::LightCyan, Color::White,
+];
+
+const TAB_WIDTH: usize = 8;
+
+// Cursor movements can't go further right than this, like a terminal clamps them to its width, so
+// a huge or repeated `ESC [ n G` / `ESC [ n C` can't pad a line with millions of blanks. Text
+// itself can still run past it.
+const MAX_COLUMNS: usize = 4096;
+
+// Renders one line of output the way a terminal would show it: colours and attributes become
+// styles, `\r` and backspace move the cursor back so later text overwrites earlier text,
+// erase-in-line clears, and every other control sequence is dropped.
+pub fn parse(line: &str) -> Line<'static> {
+    let mut cells: Vec<(char, Style)> = Vec::new();
+    let mut cursor = 0;
+    let mut style = Style::default();
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\x1b' => match chars.next() {
+                Some('[') => {
+                    let mut params = String::new();
+                    let mut command = None;
+                    for c in chars.by_ref() {
+                        if ('\x40'..='\x7e').contains(&c) {
+                            command = Some(c);
+                            break;
+                        }
+                        params.push(c);
+                    }
+                    let n = params.parse::<usize>().unwrap_or(0).min(MAX_COLUMNS);
+                    match command {
+                        Some('m') => apply_sgr(&mut style, &params),
+                        Some('K') => match n {
+                            0 => cells.truncate(cursor),
+                            1 => cells.iter_mut().take(cursor.saturating_add(1)).for_each(|cell| *cell = (' ', Style::default())),
+                            _ => cells.clear(),
+                        },
+                        Some('G') => cursor = n.saturating_sub(1),
+                        Some('C') => cursor = cursor.saturating_add(n.max(1)).min(MAX_COLUMNS.max(cursor)),
+                        Some('D') => cursor = cursor.saturating_sub(n.max(1)),
+                        _ => {}
+                    }
+                }
+                // Operating system commands (window titles, hyperlinks) end with BEL or ESC \
+                Some(']') => {
+                    while let Some(c) = chars.next() {
+                        if c == '\x07' || (c == '\x1b' && chars.next_if_eq(&'\\').is_some()) {
+                            break;
+                        }
+                    }
+                }
+                // Two-byte escapes, possibly with intermediates like the charset selection ESC ( B
+                Some(c) if ('\x20'..='\x2f').contains(&c) => {
+                    chars.next();
+                }
+                _ => {}
+            },
+            '\r' => cursor = 0,
+            '\x08' => cursor = cursor.saturating_sub(1),
+            '\t' => {
+                let stop = (cursor / TAB_WIDTH + 1) * TAB_WIDTH;
+                while cursor < stop {
+                    put(&mut cells, &mut cursor, ' ', style);
+                }
+            }
+            c if c.is_control() => {}
+            c => put(&mut cells, &mut cursor, c, style),
+        }
+    }
+
+    // Merge runs of equally styled cells into spans
+    let mut spans: Vec<Span<'static>> = Vec::new();
+    let mut text = String::new();
+    let mut current = Style::default();
+    for (c, cell_style) in cells {
+        if cell_style != current && !text.is_empty() {
+            spans.push(Span::styled(std::mem::take(&mut text), current));
+        }
+        current = cell_style;
+        text.push(c);
+    }
+    if !text.is_empty() {
+        spans.push(Span::styled(text, current));
+    }
+    Line::from(spans)
+}
+
+fn put(cells: &mut Vec<(char, Style)>, cursor: &mut usize, c: char, style: Style) {
+    if *cursor < cells.len() {
+        cells[*cursor] = (c, style);
+    } else {
+        cells.resize(*cursor, (' ', Style::default()));
+        cells.push((c, style));
+    }
+    *cursor += 1;
+}
+
+fn apply_sgr(style: &mut Style, params: &str) {
+    // Both `38;5;n` and the `38:5:n` sub-parameter form are in use
+    let codes: Vec<u16> = params.split([';', ':']).map(|p| p.parse().unwrap_or(0)).collect();
+    let mut i = 0;
+    while i < codes.len() {
+        match codes[i] {
+            0 => *style = Style::default(),
+            1 => *style = style.add_modifier(Modifier::BOLD),
+            2 => *style = style.add_modifier(Modifier::DIM),
+            3 => *style = style.add_modifier(Modifier::ITALIC),
+            4 | 21 => *style = style.add_modifier(Modifier::UNDERLINED),
+            5 | 6 => *style = style.add_modifier(Modifier::SLOW_BLINK),
+            7 => *style = style.add_modifier(Modifier::REVERSED),
+            8 => *style = style.add_modifier(Modifier::HIDDEN),
+            9 => *style = style.add_modifier(Modifier::CROSSED_OUT),
+            22 => *style = style.remove_modifier(Modifier::BOLD | Modifier::DIM),
+            23 => *style = style.remove_modifier(Modifier::ITALIC),
+            24 => *style = style.remove_modifier(Modifier::UNDERLINED),
+            25 => *style = style.remove_modifier(Modifier::SLOW_BLINK),
+            27 => *style = style.remove_modifier(Modifier::REVERSED),
+            28 => *style = style.remove_modifier(Modifier::HIDDEN),
+            29 => *style = style.remove_modifier(Modifier::CROSSED_OUT),
+            n @ 30..=37 => style.fg = Some(PALETTE[n as usize - 30]),
+            n @ 40..=47 => style.bg = Some(PALETTE[n as usize - 40]),
+            n @ 90..=97 => style.fg = Some(PALETTE[n as usize - 90 + 8]),
+            n @ 100..=107 => style.bg = Some(PALETTE[n as usize - 100 + 8]),
+            39 => style.fg = None,
+            49 => style.bg = None,
+            n @ (38 | 48) => {
+                let color = match codes.get(i + 1) {
+                    Some(5) => {
+                        let index = codes.get(i + 2).copied();
+                        i += 2;
+                        index.map(|n| Color::Indexed(n as u8))
+                    }
+                    Some(2) => {
+                        let rgb = codes.get(i + 2..i + 5).map(|c| Color::Rgb(c[0] as u8, c[1] as u8, c[2] as u8));
+                        i += 4;
+                        rgb
+                    }
+                    _ => None,
+                };
+                if n == 38 {
+                    style.fg = color.or(style.fg);
+                } else {
+                    style.bg = color.or(style.bg);
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+}
+
+// The text `parse` would display, without styles; used for searching
+pub fn plain(line: &str) -> Cow<'_, str> {
+    if !line.contains(|c: char| c.is_control()) {
+        return Cow::Borrowed(line);
+    }
+    Cow::Owned(parse(line).spans.iter().map(|s| s.content.as_ref()).collect())
+}
+
+// The raw line with control characters in caret notation (ESC as ^[), so escape codes can be
+// inspected without the terminal acting on them
+pub fn escape(line: &str) -> Cow<'_, str> {
+    if !line.contains(|c: char| c.is_control()) {
+        return Cow::Borrowed(line);
+    }
+    Cow::Owned(
+        line.chars()
+            .map(|c| match c {
+                '\x7f' => "^?".to_string(),
+                c if (c as u32) < 0x20 => format!("^{}", (c as u8 + 0x40) as char),
+                c if c.is_control() => format!("<U+{:04X}>", c as u32),
+                c => c.to_string(),
+            })
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(line: &str) -> String {
+        plain(line).into_owned()
+    }
+
+    #[test]
+    fn sgr_becomes_styles() {
+        let line = parse("\x1b[1;31merror\x1b[0m: done");
+        assert_eq!(line.spans.len(), 2);
+        assert_eq!(line.spans[0].content, "error");
+        assert_eq!(line.spans[0].style, Style::default().fg(Color::Red).add_modifier(Modifier::BOLD));
+        assert_eq!(line.spans[1].content, ": done");
+        assert_eq!(line.spans[1].style, Style::default());
+
+        let line = parse("\x1b[38;5;208ma\x1b[48:2:1:2:3mb");
+        assert_eq!(line.spans[0].style.fg, Some(Color::Indexed(208)));
+        assert_eq!(line.spans[1].style.bg, Some(Color::Rgb(1, 2, 3)));
+    }
+
+    #[test]
+    fn carriage_return_overwrites() {
+        assert_eq!(text("10%\r50%\r100%"), "100%");
+        assert_eq!(text("downloading\rdone"), "doneloading");
+        assert_eq!(text("ab\x08c"), "ac");
+    }
+
+    #[test]
+    fn erase_in_line() {
+        assert_eq!(text("downloading\rdone\x1b[K"), "done");
+        assert_eq!(text("abcdef\x1b[3D\x1b[1K"), "    ef");
+        assert_eq!(text("abcdef\x1b[2Kxy"), "      xy");
+    }
+
+    #[test]
+    fn cursor_moves_and_tabs() {
+        assert_eq!(text("abc\x1b[5Gx"), "abc x");
+        assert_eq!(text("a\x1b[2Cb"), "a  b");
+        assert_eq!(text("a\tb"), "a       b");
+    }
+
+    #[test]
+    fn oversized_parameters_are_clamped() {
+        assert_eq!(text("\x1b[999999999999Gx").len(), MAX_COLUMNS);
+        assert_eq!(text("a\x1b[18446744073709551615Cb").len(), MAX_COLUMNS + 1);
+        assert_eq!(text(&"\x1b[4096Cx".repeat(1000)).len(), MAX_COLUMNS + 1000);
+        // Past the limit a forward move stays put rather than pulling the cursor back
+        let long = "x".repeat(MAX_COLUMNS + 10);
+        assert_eq!(text(&format!("{}\x1b[5Cy", long)).len(), MAX_COLUMNS + 11);
+    }
+
+    #[test]
+    fn other_sequences_are_dropped() {
+        assert_eq!(text("\x1b]0;title\x07a\x1b]8;;http://x\x1b\\b\x1b(Bc\x1b[?25l"), "abc");
+    }
+
+    #[test]
+    fn plain_borrows_clean_lines() {
+        assert!(matches!(plain("no escapes"), Cow::Borrowed("no escapes")));
+    }
+
+    #[test]
+    fn escape_uses_caret_notation() {
+        assert_eq!(escape("\x1b[31mred\r\x7f"), "^[[31mred^M^?");
+        assert_eq!(escape("\u{85}"), "<U+0085>");
+        assert!(matches!(escape("plain"), Cow::Borrowed("plain")));
+    }
+}
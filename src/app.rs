@@ -1,39 +1,91 @@
-use std::{collections::{HashMap, VecDeque}, fs::File, io::BufReader, time::SystemTime};
-use crate::types::{ProcessInfo, PendingLaunch, Mode};
+use std::{collections::{HashMap, HashSet}, time::SystemTime};
+use crate::types::{ProcessInfo, Mode, Tab, ScheduledJob, JobForm, LogLayout};
 use crate::monitor::Monitor;
+use crate::stats::StatSample;
+use crate::events::EventRecord;
+use crate::logview::{LogTail, LogView, MergedLog};
+use crate::proclist::ListOptions;
+use crate::launch::{LaunchForm, LaunchSpec};
+use crate::config::Action;
 
 pub struct App {
+    // The processes shown, after filtering, sorting and grouping
     pub processes: Vec<(String, ProcessInfo)>,
+    // Tracked processes before filtering
+    pub total_processes: usize,
+    pub list: ListOptions,
+    // PIDs that bulk actions apply to instead of the selected process
+    pub marked: HashSet<String>,
+    // A bulk action waiting for a yes in the confirmation prompt
+    pub confirm: Option<(Action, Vec<String>)>,
     pub selected_index: usize,
     pub mode: Mode,
     pub input_buffer: String,
     pub error_message: String,
-    pub log_cache: HashMap<String, VecDeque<String>>,
-    pub log_readers: HashMap<String, BufReader<File>>,
+    pub log_tails: HashMap<String, LogTail>,
+    // Scroll and search state per log file
+    pub log_views: HashMap<String, LogView>,
+    pub log_layout: LogLayout,
+    // Log files pinned to the split and merged layouts, in pane order
+    pub panes: Vec<String>,
+    // Rebuilt whenever the merged logs change
+    pub merged: Option<MergedLog>,
+    pub merged_view: LogView,
     pub is_paused: bool,
     pub last_reap: SystemTime,
-    pub name_input_mode: bool,
-    pub pending_launch: Option<PendingLaunch>,
+    pub launch_form: Option<LaunchForm>,
+    // Saved launch templates by name, listed by the template picker
+    pub templates: Vec<(String, LaunchSpec)>,
+    pub template_index: usize,
     pub monitor: Monitor,
     pub show_resources: bool,
+    pub resource_tree_view: bool,
+    // Recorded samples within tui.resource_window, by display name
+    pub stats_history: HashMap<String, Vec<StatSample>>,
+    // When the history files were last read; the daemon only appends every few seconds
+    pub stats_read_at: u64,
+    pub show_events: bool,
+    pub recent_events: Vec<EventRecord>,
+    pub tab: Tab,
+    pub jobs: Vec<ScheduledJob>,
+    pub job_index: usize,
+    pub job_form: Option<JobForm>,
 }
 
 impl App {
     pub fn new(processes: Vec<(String, ProcessInfo)>) -> Self {
         Self {
+            total_processes: processes.len(),
             processes,
+            list: ListOptions::new(),
+            marked: HashSet::new(),
+            confirm: None,
             selected_index: 0,
             mode: Mode::Navigate,
             input_buffer: String::new(),
             error_message: String::new(),
-            log_cache: HashMap::new(),
-            log_readers: HashMap::new(),
+            log_tails: HashMap::new(),
+            log_views: HashMap::new(),
+            log_layout: LogLayout::Single,
+            panes: Vec::new(),
+            merged: None,
+            merged_view: LogView::new(),
             is_paused: false,
             last_reap: SystemTime::now(),
-            name_input_mode: false,
-            pending_launch: None,
+            launch_form: None,
+            templates: Vec::new(),
+            template_index: 0,
             monitor: Monitor::new(),
             show_resources: false,
+            resource_tree_view: false,
+            stats_history: HashMap::new(),
+            stats_read_at: 0,
+            show_events: false,
+            recent_events: Vec::new(),
+            tab: Tab::Processes,
+            jobs: Vec::new(),
+            job_index: 0,
+            job_form: None,
         }
     }
 }
@@ -0,0 +1,565 @@
+use std::{collections::HashMap, env, fs, path::{Path, PathBuf}, sync::RwLock};
+use ratatui::style::Color;
+use serde::{Deserialize, Serialize};
+use crate::{process, scheduler, types::RestartPolicy};
+
+pub const DEFAULT_PROFILE: &str = "default";
+
+// Per-profile overrides; anything unset falls back to the profile's XDG directory
+#[derive(Serialize, Deserialize, Default, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ProfileConfig {
+    pub state_dir: Option<PathBuf>,
+    pub log_dir: Option<PathBuf>,
+}
+
+// ~/.config/ksai_proc/config.toml
+#[derive(Serialize, Deserialize, Default, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    // Used when neither --profile nor KSAI_PROC_PROFILE is given
+    pub profile: Option<String>,
+    pub state_dir: Option<PathBuf>,
+    pub log_dir: Option<PathBuf>,
+    pub state_backend: Option<String>,
+    #[serde(default)]
+    pub tui: TuiConfig,
+    #[serde(default)]
+    pub scheduler: SchedulerConfig,
+    #[serde(default)]
+    pub process: ProcessConfig,
+    #[serde(default)]
+    pub logs: LogConfig,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub profiles: HashMap<String, ProfileConfig>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct TuiConfig {
+    // Lines of each log kept in memory for the log pane
+    pub log_cache_lines: usize,
+    // How often the process list is refreshed (and dead processes reaped)
+    pub reap_interval_ms: u64,
+    // Actions that ask before touching a single process; acting on several always asks
+    pub confirm: Vec<String>,
+    // How much recorded history the resource view's sparklines and charts cover
+    pub resource_window: String,
+    pub colors: Colors,
+    pub keys: Keys,
+}
+
+impl Default for TuiConfig {
+    fn default() -> Self {
+        Self {
+            log_cache_lines: 1000,
+            reap_interval_ms: 500,
+            confirm: vec!["kill".to_string(), "remove".to_string(), "clear_logs".to_string()],
+            resource_window: "10m".to_string(),
+            colors: Colors::default(),
+            keys: Keys::default(),
+        }
+    }
+}
+
+// Colour names ("light-red"), 256-colour indexes ("208") or hex ("#ff8800")
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct Colors {
+    pub running: String,
+    pub stopped: String,
+    pub failed: String,
+    pub selected_fg: String,
+    pub selected_bg: String,
+    pub footer_fg: String,
+    pub footer_bg: String,
+    pub error: String,
+    // Backgrounds of search matches in the log pane, and of the match last jumped to
+    pub search_match: String,
+    pub search_current: String,
+}
+
+impl Default for Colors {
+    fn default() -> Self {
+        Self {
+            running: "green".to_string(),
+            stopped: "yellow".to_string(),
+            failed: "red".to_string(),
+            selected_fg: "white".to_string(),
+            selected_bg: "blue".to_string(),
+            footer_fg: "black".to_string(),
+            footer_bg: "white".to_string(),
+            error: "red".to_string(),
+            search_match: "yellow".to_string(),
+            search_current: "lightred".to_string(),
+        }
+    }
+}
+
+impl Colors {
+    fn all(&self) -> [(&'static str, &String); 10] {
+        [
+            ("running", &self.running),
+            ("stopped", &self.stopped),
+            ("failed", &self.failed),
+            ("selected_fg", &self.selected_fg),
+            ("selected_bg", &self.selected_bg),
+            ("footer_fg", &self.footer_fg),
+            ("footer_bg", &self.footer_bg),
+            ("error", &self.error),
+            ("search_match", &self.search_match),
+            ("search_current", &self.search_current),
+        ]
+    }
+}
+
+// Validated at load time, so anything that reaches the TUI parses
+pub fn color(name: &str) -> Color {
+    name.parse().unwrap_or(Color::Reset)
+}
+
+// The destructive actions tui.confirm may list
+const CONFIRMABLE: [&str; 4] = ["kill", "restart", "remove", "clear_logs"];
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Action {
+    Quit,
+    Down,
+    Up,
+    Open,
+    Kill,
+    Remove,
+    Restart,
+    ClearLogs,
+    Pause,
+    Resources,
+    Tree,
+    Events,
+    Edit,
+    Toggle,
+    Top,
+    Bottom,
+    Search,
+    NextMatch,
+    PrevMatch,
+    Wrap,
+    Raw,
+    Pin,
+    Layout,
+    Filter,
+    Sort,
+    HideInactive,
+    Group,
+    Mark,
+    MarkAll,
+    Templates,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct Keys {
+    pub quit: char,
+    pub down: char,
+    pub up: char,
+    pub open: char,
+    pub kill: char,
+    pub remove: char,
+    pub restart: char,
+    pub clear_logs: char,
+    pub pause: char,
+    pub resources: char,
+    pub tree: char,
+    pub events: char,
+    // Scheduled jobs tab
+    pub edit: char,
+    pub toggle: char,
+    // Log pane
+    pub top: char,
+    pub bottom: char,
+    pub search: char,
+    pub next_match: char,
+    pub prev_match: char,
+    pub wrap: char,
+    pub raw: char,
+    pub pin: char,
+    pub layout: char,
+    // Process list
+    pub filter: char,
+    pub sort: char,
+    pub hide_inactive: char,
+    pub group: char,
+    pub mark: char,
+    pub mark_all: char,
+    // Launch templates
+    pub templates: char,
+}
+
+impl Default for Keys {
+    fn default() -> Self {
+        Self {
+            quit: 'q',
+            down: 'j',
+            up: 'k',
+            open: 'o',
+            kill: 'x',
+            remove: 'X',
+            restart: 'R',
+            clear_logs: 'c',
+            pause: 'p',
+            resources: 's',
+            tree: 't',
+            events: 'e',
+            edit: 'E',
+            toggle: 'T',
+            top: 'g',
+            bottom: 'G',
+            search: '/',
+            next_match: 'n',
+            prev_match: 'N',
+            wrap: 'w',
+            raw: 'r',
+            pin: 'P',
+            layout: 'v',
+            filter: 'f',
+            sort: 'S',
+            hide_inactive: 'h',
+            group: 'b',
+            mark: ' ',
+            mark_all: 'a',
+            templates: 'l',
+        }
+    }
+}
+
+impl Keys {
+    fn all(&self) -> [(&'static str, char, Action); 30] {
+        [
+            ("quit", self.quit, Action::Quit),
+            ("down", self.down, Action::Down),
+            ("up", self.up, Action::Up),
+            ("open", self.open, Action::Open),
+            ("kill", self.kill, Action::Kill),
+            ("remove", self.remove, Action::Remove),
+            ("restart", self.restart, Action::Restart),
+            ("clear_logs", self.clear_logs, Action::ClearLogs),
+            ("pause", self.pause, Action::Pause),
+            ("resources", self.resources, Action::Resources),
+            ("tree", self.tree, Action::Tree),
+            ("events", self.events, Action::Events),
+            ("edit", self.edit, Action::Edit),
+            ("toggle", self.toggle, Action::Toggle),
+            ("top", self.top, Action::Top),
+            ("bottom", self.bottom, Action::Bottom),
+            ("search", self.search, Action::Search),
+            ("next_match", self.next_match, Action::NextMatch),
+            ("prev_match", self.prev_match, Action::PrevMatch),
+            ("wrap", self.wrap, Action::Wrap),
+            ("raw", self.raw, Action::Raw),
+            ("pin", self.pin, Action::Pin),
+            ("layout", self.layout, Action::Layout),
+            ("filter", self.filter, Action::Filter),
+            ("sort", self.sort, Action::Sort),
+            ("hide_inactive", self.hide_inactive, Action::HideInactive),
+            ("group", self.group, Action::Group),
+            ("mark", self.mark, Action::Mark),
+            ("mark_all", self.mark_all, Action::MarkAll),
+            ("templates", self.templates, Action::Templates),
+        ]
+    }
+
+    pub fn action(&self, c: char) -> Option<Action> {
+        self.all().into_iter().find(|(_, key, _)| *key == c).map(|(_, _, action)| action)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct SchedulerConfig {
+    // Longest the daemon sleeps between samples, watchdog checks and log rotation.
+    // Signals, child exits, schedule deadlines and state changes wake it sooner.
+    pub tick_ms: u64,
+    // Used for jobs whose frequency can't be parsed
+    pub default_frequency: String,
+    // What happens to running processes when the daemon gets SIGTERM/SIGINT
+    pub on_shutdown: ShutdownAction,
+}
+
+impl Default for SchedulerConfig {
+    fn default() -> Self {
+        Self { tick_ms: 1000, default_frequency: "1m".to_string(), on_shutdown: ShutdownAction::Leave }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ShutdownAction {
+    Leave,
+    Stop,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProcessConfig {
+    // Tried in order when looking for scripts/<name>.<ext>; the bare name is tried last
+    pub script_extensions: Vec<String>,
+    // Applied to processes launched without --restart
+    pub restart_policy: RestartPolicy,
+    // Sent by stop, restart, remove, timeouts and the watchdog; SIGKILL follows if the process outlives stop_timeout
+    pub stop_signal: String,
+    pub stop_timeout: String,
+}
+
+impl Default for ProcessConfig {
+    fn default() -> Self {
+        Self {
+            script_extensions: vec!["py".to_string(), "sh".to_string()],
+            restart_policy: RestartPolicy::Never,
+            stop_signal: "SIGKILL".to_string(),
+            stop_timeout: "5s".to_string(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogConfig {
+    // Rotate a process log once it grows past this size (e.g. 10M); unset disables rotation
+    pub max_size: Option<String>,
+    // How many rotated files (<log>.1 .. <log>.N) to keep
+    pub keep: usize,
+    // How long deleted logs stay in <log_dir>/trash; "0s" deletes them outright
+    pub trash_retention: String,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self { max_size: None, keep: 3, trash_retention: "7d".to_string() }
+    }
+}
+
+impl Config {
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(backend) = &self.state_backend
+            && backend != "json" && backend != "sqlite"
+        {
+            return Err(format!("state_backend must be \"json\" or \"sqlite\", not \"{}\"", backend));
+        }
+        if let Some(name) = self.profile.iter().chain(self.profiles.keys()).find(|name| validate_profile(name).is_err()) {
+            return Err(format!("profiles: invalid profile name \"{}\": use only letters, digits, '_' and '-'", name));
+        }
+        if self.tui.log_cache_lines == 0 {
+            return Err("tui.log_cache_lines must be at least 1".to_string());
+        }
+        if self.tui.reap_interval_ms < 100 {
+            return Err("tui.reap_interval_ms must be at least 100".to_string());
+        }
+        for (key, value) in self.tui.colors.all() {
+            if value.parse::<Color>().is_err() {
+                return Err(format!("tui.colors.{}: unknown colour \"{}\"", key, value));
+            }
+        }
+        let keys = self.tui.keys.all();
+        for (i, (name, key, _)) in keys.iter().enumerate() {
+            if let Some((other, _, _)) = keys[..i].iter().find(|(_, k, _)| k == key) {
+                return Err(format!("tui.keys.{} and tui.keys.{} are both bound to '{}'", other, name, key));
+            }
+        }
+        if let Some(name) = self.tui.confirm.iter().find(|name| !CONFIRMABLE.contains(&name.as_str())) {
+            return Err(format!("tui.confirm: \"{}\" is not one of {}", name, CONFIRMABLE.join(", ")));
+        }
+        if scheduler::parse_frequency(&self.tui.resource_window).is_none_or(|secs| secs == 0) {
+            return Err(format!("tui.resource_window: invalid duration \"{}\"", self.tui.resource_window));
+        }
+        if self.scheduler.tick_ms < 100 {
+            return Err("scheduler.tick_ms must be at least 100".to_string());
+        }
+        if scheduler::parse_frequency(&self.scheduler.default_frequency).is_none() {
+            return Err(format!("scheduler.default_frequency: invalid duration \"{}\"", self.scheduler.default_frequency));
+        }
+        if process::parse_signal(&self.process.stop_signal).is_none() {
+            return Err(format!("process.stop_signal: unknown signal \"{}\"", self.process.stop_signal));
+        }
+        if scheduler::parse_frequency(&self.process.stop_timeout).is_none() {
+            return Err(format!("process.stop_timeout: invalid duration \"{}\"", self.process.stop_timeout));
+        }
+        if let Some(size) = &self.logs.max_size
+            && process::parse_size(size).is_none_or(|s| s == 0)
+        {
+            return Err(format!("logs.max_size: invalid size \"{}\"", size));
+        }
+        if self.logs.keep == 0 {
+            return Err("logs.keep must be at least 1".to_string());
+        }
+        if scheduler::parse_frequency(&self.logs.trash_retention).is_none() {
+            return Err(format!("logs.trash_retention: invalid duration \"{}\"", self.logs.trash_retention));
+        }
+        Ok(())
+    }
+
+    pub fn default_frequency_secs(&self) -> u64 {
+        scheduler::parse_frequency(&self.scheduler.default_frequency).unwrap_or(60)
+    }
+
+    pub fn stop_signal(&self) -> i32 {
+        process::parse_signal(&self.process.stop_signal).unwrap_or(libc::SIGKILL)
+    }
+
+    pub fn stop_timeout_secs(&self) -> u64 {
+        scheduler::parse_frequency(&self.process.stop_timeout).unwrap_or(5)
+    }
+
+    pub fn resource_window_secs(&self) -> u64 {
+        scheduler::parse_frequency(&self.tui.resource_window).unwrap_or(600)
+    }
+
+    pub fn trash_retention_secs(&self) -> u64 {
+        scheduler::parse_frequency(&self.logs.trash_retention).unwrap_or(0)
+    }
+
+    // Whether the TUI asks before applying the action to a single process
+    pub fn confirms(&self, action: Action) -> bool {
+        self.tui.keys.all().iter().any(|(name, _, a)| *a == action && self.tui.confirm.iter().any(|c| c == name))
+    }
+
+    pub fn log_max_size(&self) -> Option<u64> {
+        self.logs.max_size.as_deref().and_then(process::parse_size)
+    }
+}
+
+fn home_dir() -> PathBuf {
+    env::var("HOME").map(PathBuf::from).unwrap_or_else(|_| PathBuf::from("/tmp"))
+}
+
+fn xdg_dir(var: &str, fallback: &str) -> PathBuf {
+    env::var(var)
+        .ok()
+        .filter(|v| Path::new(v).is_absolute())
+        .map(PathBuf::from)
+        .unwrap_or_else(|| home_dir().join(fallback))
+}
+
+pub fn config_home() -> PathBuf {
+    xdg_dir("XDG_CONFIG_HOME", ".config")
+}
+
+pub fn config_file() -> PathBuf {
+    env::var("KSAI_PROC_CONFIG")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| config_home().join("ksai_proc/config.toml"))
+}
+
+// A missing file is an empty config
+pub fn read_file(path: &Path) -> Result<Config, String> {
+    let Ok(s) = fs::read_to_string(path) else {
+        return Ok(Config::default());
+    };
+    let config: Config = toml::from_str(&s).map_err(|e| e.to_string())?;
+    config.validate()?;
+    Ok(config)
+}
+
+// A broken or invalid file is reported and ignored
+pub fn load() -> Config {
+    let path = config_file();
+    read_file(&path).unwrap_or_else(|e| {
+        eprintln!("Error: Ignoring invalid config {}: {}", path.display(), e);
+        Config::default()
+    })
+}
+
+// Each loaded config is leaked so callers can keep a plain reference; that only happens again on reload
+static SETTINGS: RwLock<Option<&'static Config>> = RwLock::new(None);
+
+// The config is read once per process; the TUI picks up changes when restarted, the daemon on SIGHUP
+pub fn settings() -> &'static Config {
+    if let Some(config) = *SETTINGS.read().unwrap() {
+        return config;
+    }
+    let mut slot = SETTINGS.write().unwrap();
+    if slot.is_none() {
+        *slot = Some(Box::leak(Box::new(load())));
+    }
+    slot.unwrap()
+}
+
+// Re-reads the config file; an invalid file keeps the settings in use instead of falling back to defaults
+pub fn reload() -> Result<(), String> {
+    let config = read_file(&config_file())?;
+    *SETTINGS.write().unwrap() = Some(Box::leak(Box::new(config)));
+    Ok(())
+}
+
+// Expands a leading ~ so config paths can be written the usual way
+pub fn expand(path: &Path) -> PathBuf {
+    match path.strip_prefix("~") {
+        Ok(rest) => home_dir().join(rest),
+        Err(_) => path.to_path_buf(),
+    }
+}
+
+pub struct Paths {
+    pub profile: String,
+    pub state_file: PathBuf,
+    pub scheduled_file: PathBuf,
+    pub log_dir: PathBuf,
+}
+
+// Profile names become directory and systemd unit names, so nothing that could leave the
+// profiles directory or break a unit name is allowed
+pub fn validate_profile(name: &str) -> Result<(), String> {
+    if !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+        Ok(())
+    } else {
+        Err(format!("invalid profile name \"{}\": use only letters, digits, '_' and '-'", name))
+    }
+}
+
+// Where a profile lives when nothing overrides it: $XDG_STATE_HOME/ksai_proc[/profiles/<name>]
+pub fn default_state_dir(profile: &str) -> PathBuf {
+    let base = xdg_dir("XDG_STATE_HOME", ".local/state").join("ksai_proc");
+    if profile == DEFAULT_PROFILE { base } else { base.join("profiles").join(profile) }
+}
+
+// Precedence: KSAI_PROC_* env vars > profile section > top-level config > XDG defaults.
+// The default profile keeps using <exe_dir>/logs if state already exists there from older versions.
+pub fn resolve_paths(config: &Config, profile: Option<&str>, exe_dir: &Path) -> Result<Paths, String> {
+    let profile = profile
+        .map(str::to_string)
+        .or_else(|| env::var("KSAI_PROC_PROFILE").ok())
+        .or_else(|| config.profile.clone())
+        .unwrap_or_else(|| DEFAULT_PROFILE.to_string());
+    validate_profile(&profile)?;
+    let section = config.profiles.get(&profile).cloned().unwrap_or_default();
+    let is_default = profile == DEFAULT_PROFILE;
+
+    let legacy_dir = exe_dir.join("logs");
+    let use_legacy = is_default && legacy_dir.join("runningscripts.json").exists();
+
+    let state_dir = section.state_dir
+        .or_else(|| if is_default { config.state_dir.clone() } else { None })
+        .map(|p| expand(&p))
+        .unwrap_or_else(|| if use_legacy { legacy_dir.clone() } else { default_state_dir(&profile) });
+    let log_dir = section.log_dir
+        .or_else(|| if is_default { config.log_dir.clone() } else { None })
+        .map(|p| expand(&p))
+        .unwrap_or_else(|| if use_legacy { legacy_dir.clone() } else { state_dir.join("logs") });
+
+    Ok(Paths {
+        state_file: env::var("KSAI_PROC_LOG_JSON").map(PathBuf::from).unwrap_or_else(|_| state_dir.join("runningscripts.json")),
+        scheduled_file: env::var("KSAI_PROC_SCHEDULE_JSON").map(PathBuf::from).unwrap_or_else(|_| state_dir.join("scheduledscripts.json")),
+        log_dir: env::var("KSAI_PROC_LOG_DIR").map(PathBuf::from).unwrap_or(log_dir),
+        profile,
+    })
+}
+
+// Profiles named in the config plus any that already have state on disk
+pub fn known_profiles(config: &Config) -> Vec<String> {
+    let mut names: Vec<String> = config.profiles.keys().cloned().collect();
+    names.push(DEFAULT_PROFILE.to_string());
+    if let Ok(entries) = fs::read_dir(default_state_dir(DEFAULT_PROFILE).join("profiles")) {
+        names.extend(entries.flatten().filter(|e| e.path().is_dir()).map(|e| e.file_name().to_string_lossy().to_string()));
+    }
+    names.sort();
+    names.dedup();
+    names
+}
@@ -0,0 +1,226 @@
+use std::{ffi::OsString, fs::{self, File, OpenOptions}, io::{self, Write}, os::unix::{ffi::OsStringExt, fs::MetadataExt, process::CommandExt}, path::{Path, PathBuf}, process::{Command, Stdio}, sync::atomic::{AtomicBool, AtomicI32, Ordering}, thread, time::{Duration, Instant}};
+use fs2::FileExt;
+use crate::{config::{self, Paths, ShutdownAction}, service};
+
+// Held with an exclusive lock for the daemon's whole life, so a stale file can never look like a live daemon
+pub fn pid_file(state_file: &Path) -> PathBuf {
+    state_file.with_file_name("daemon.pid")
+}
+
+// Returns the locked pidfile, or None if another daemon already holds it
+pub fn acquire(state_file: &Path) -> io::Result<Option<File>> {
+    let mut file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(pid_file(state_file))?;
+    if FileExt::try_lock_exclusive(&file).is_err() {
+        return Ok(None);
+    }
+    file.set_len(0)?;
+    writeln!(file, "{}", std::process::id())?;
+    file.sync_all()?;
+    Ok(Some(file))
+}
+
+// False once the pidfile was deleted or replaced; a new daemon may then start, so the old one must go
+pub fn still_owns(file: &File, state_file: &Path) -> bool {
+    match (file.metadata(), fs::metadata(pid_file(state_file))) {
+        (Ok(held), Ok(on_disk)) => held.ino() == on_disk.ino() && held.dev() == on_disk.dev(),
+        _ => false,
+    }
+}
+
+pub fn running_pid(state_file: &Path) -> Option<u32> {
+    let file = File::open(pid_file(state_file)).ok()?;
+    if FileExt::try_lock_shared(&file).is_ok() {
+        FileExt::unlock(&file).ok();
+        return None;
+    }
+    // Locked but possibly not written yet if the daemon is just starting
+    for _ in 0..20 {
+        if let Some(pid) = fs::read_to_string(pid_file(state_file)).ok().and_then(|s| s.trim().parse().ok()) {
+            return Some(pid);
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+    None
+}
+
+// Starts a detached daemon; a second one racing with it just exits when it can't take the pidfile
+pub fn spawn(exe: &Path, paths: &Paths) -> io::Result<u32> {
+    let log_file = paths.log_dir.join("scheduler.log");
+    let log = OpenOptions::new().create(true).append(true).open(&log_file)?;
+
+    let mut cmd = Command::new(exe);
+    cmd.arg("internal-scheduler")
+        // Pin the daemon to exactly this profile's files
+        .envs(service::daemon_env(paths))
+        .current_dir("/")
+        .stdin(Stdio::null())
+        .stdout(Stdio::from(log.try_clone()?))
+        .stderr(Stdio::from(log));
+    unsafe {
+        cmd.pre_exec(|| {
+            libc::setsid();
+            libc::umask(0o022);
+            Ok(())
+        })
+    };
+    cmd.spawn().map(|child| child.id())
+}
+
+// Waits until a freshly spawned daemon holds the pidfile
+pub fn wait_until_running(state_file: &Path, timeout: Duration) -> Option<u32> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        if let Some(pid) = running_pid(state_file) {
+            return Some(pid);
+        }
+        if Instant::now() >= deadline {
+            return None;
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+}
+
+// How long a graceful shutdown may take, including stopping processes if the config asks for it
+pub fn stop_timeout() -> Duration {
+    let settings = config::settings();
+    let stopping = match settings.scheduler.on_shutdown {
+        ShutdownAction::Stop => settings.stop_timeout_secs(),
+        ShutdownAction::Leave => 0,
+    };
+    Duration::from_secs(stopping.saturating_add(10))
+}
+
+// SIGTERM, then SIGKILL if the daemon still holds the pidfile after the timeout
+pub fn stop(state_file: &Path, timeout: Duration) -> Option<u32> {
+    let pid = running_pid(state_file)?;
+    unsafe { libc::kill(pid as i32, libc::SIGTERM) };
+    let deadline = Instant::now() + timeout;
+    while running_pid(state_file).is_some() && Instant::now() < deadline {
+        thread::sleep(Duration::from_millis(50));
+    }
+    if running_pid(state_file).is_some() {
+        unsafe { libc::kill(pid as i32, libc::SIGKILL) };
+        thread::sleep(Duration::from_millis(200));
+    }
+    Some(pid)
+}
+
+static SHUTDOWN: AtomicBool = AtomicBool::new(false);
+static RELOAD: AtomicBool = AtomicBool::new(false);
+// Write end of the self-pipe; signal handlers may only do async-signal-safe work, so they just poke it
+static WAKE_FD: AtomicI32 = AtomicI32::new(-1);
+
+extern "C" fn on_signal(sig: libc::c_int) {
+    match sig {
+        libc::SIGTERM | libc::SIGINT => SHUTDOWN.store(true, Ordering::SeqCst),
+        libc::SIGHUP => RELOAD.store(true, Ordering::SeqCst),
+        _ => {}
+    }
+    let fd = WAKE_FD.load(Ordering::SeqCst);
+    if fd >= 0 {
+        unsafe { libc::write(fd, [0u8].as_ptr().cast(), 1) };
+    }
+}
+
+pub fn shutdown_requested() -> bool {
+    SHUTDOWN.load(Ordering::SeqCst)
+}
+
+pub fn take_reload() -> bool {
+    RELOAD.swap(false, Ordering::SeqCst)
+}
+
+// Sends SIGHUP so the daemon re-reads the config and the schedule
+pub fn reload(state_file: &Path) -> Option<u32> {
+    let pid = running_pid(state_file)?;
+    unsafe { libc::kill(pid as i32, libc::SIGHUP) };
+    Some(pid)
+}
+
+// What the daemon sleeps on: signals (through the self-pipe) and changes to the files it manages
+pub struct Wakeup {
+    pipe: i32,
+    inotify: i32,
+    names: Vec<OsString>,
+}
+
+impl Wakeup {
+    pub fn install(watched: &[PathBuf]) -> io::Result<Self> {
+        let mut fds = [0; 2];
+        if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_NONBLOCK | libc::O_CLOEXEC) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        WAKE_FD.store(fds[1], Ordering::SeqCst);
+        for sig in [libc::SIGTERM, libc::SIGINT, libc::SIGHUP, libc::SIGCHLD] {
+            unsafe {
+                let mut action: libc::sigaction = std::mem::zeroed();
+                action.sa_sigaction = on_signal as *const () as libc::sighandler_t;
+                action.sa_flags = libc::SA_RESTART | libc::SA_NOCLDSTOP;
+                libc::sigemptyset(&mut action.sa_mask);
+                libc::sigaction(sig, &action, std::ptr::null_mut());
+            }
+        }
+
+        // State files are replaced by rename, so watch their directories and filter by name.
+        // Without inotify the daemon still wakes on signals and every tick.
+        let inotify = unsafe { libc::inotify_init1(libc::IN_NONBLOCK | libc::IN_CLOEXEC) };
+        let mut names = Vec::new();
+        for path in watched {
+            let Some(dir) = path.parent().and_then(|d| std::ffi::CString::new(d.as_os_str().as_encoded_bytes()).ok()) else { continue };
+            if inotify >= 0 {
+                unsafe { libc::inotify_add_watch(inotify, dir.as_ptr(), libc::IN_CLOSE_WRITE | libc::IN_MOVED_TO | libc::IN_DELETE) };
+            }
+            names.extend(path.file_name().map(|n| n.to_os_string()));
+        }
+        Ok(Self { pipe: fds[0], inotify, names })
+    }
+
+    // Returns after a signal, a change to one of the watched files, or the timeout
+    pub fn wait(&self, timeout: Duration) {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return;
+            }
+            let mut fds = [
+                libc::pollfd { fd: self.pipe, events: libc::POLLIN, revents: 0 },
+                libc::pollfd { fd: self.inotify, events: libc::POLLIN, revents: 0 },
+            ];
+            let millis = remaining.as_millis().clamp(1, i32::MAX as u128) as i32;
+            if unsafe { libc::poll(fds.as_mut_ptr(), 2, millis) } <= 0 {
+                // Timeout, or EINTR from a signal whose byte is read on the next round
+                continue;
+            }
+            if fds[0].revents != 0 {
+                let mut buf = [0u8; 64];
+                while unsafe { libc::read(self.pipe, buf.as_mut_ptr().cast(), buf.len()) } > 0 {}
+                return;
+            }
+            if fds[1].revents != 0 && self.changed() {
+                return;
+            }
+        }
+    }
+
+    // Consumes pending inotify events; true if one was for a watched file
+    pub fn changed(&self) -> bool {
+        let mut relevant = false;
+        let mut buf = [0u8; 4096];
+        loop {
+            let n = unsafe { libc::read(self.inotify, buf.as_mut_ptr().cast(), buf.len()) };
+            if n <= 0 {
+                return relevant;
+            }
+            let mut offset = 0;
+            while offset + std::mem::size_of::<libc::inotify_event>() <= n as usize {
+                let event = unsafe { std::ptr::read_unaligned(buf.as_ptr().add(offset) as *const libc::inotify_event) };
+                let start = offset + std::mem::size_of::<libc::inotify_event>();
+                let raw = &buf[start..start + event.len as usize];
+                let name = OsString::from_vec(raw.iter().copied().take_while(|&b| b != 0).collect());
+                relevant |= self.names.contains(&name);
+                offset = start + event.len as usize;
+            }
+        }
+    }
+}
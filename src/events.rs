@@ -0,0 +1,201 @@
+use std::{fs::{self, File, OpenOptions}, io::{BufRead, BufReader, Read, Seek, SeekFrom, Write}, os::unix::fs::MetadataExt, path::{Path, PathBuf}, sync::atomic::{AtomicU8, Ordering}, time::{SystemTime, UNIX_EPOCH}};
+use fs2::FileExt;
+use serde::{Deserialize, Serialize};
+
+// Events older than this are dropped when the journal is compacted
+const RETENTION_SECS: f64 = 30.0 * 86400.0;
+// At most this many of the newest events are kept
+const MAX_EVENTS: usize = 10_000;
+// How often the scheduler daemon compacts the journal
+pub const COMPACT_INTERVAL_SECS: u64 = 600;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Actor {
+    Cli,
+    Tui,
+    Daemon,
+    // Records the exit status of the one process it watches
+    Supervisor,
+}
+
+static ACTOR: AtomicU8 = AtomicU8::new(Actor::Cli as u8);
+
+// Set once at startup depending on how ksai_proc was invoked
+pub fn set_actor(actor: Actor) {
+    ACTOR.store(actor as u8, Ordering::Relaxed);
+}
+
+fn actor_name() -> &'static str {
+    match ACTOR.load(Ordering::Relaxed) {
+        x if x == Actor::Tui as u8 => "tui",
+        x if x == Actor::Daemon as u8 => "daemon",
+        x if x == Actor::Supervisor as u8 => "supervisor",
+        _ => "cli",
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct EventRecord {
+    pub ts: f64,
+    pub kind: String, // spawned, exited, killed, removed, revived, scheduled-fired, health-changed
+    pub actor: String,
+    pub pid: String,
+    pub name: String,
+    #[serde(default)]
+    pub detail: Option<String>,
+}
+
+impl EventRecord {
+    pub fn format_line(&self) -> String {
+        let time = chrono::DateTime::from_timestamp(self.ts as i64, 0)
+            .map(|d| d.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M:%S").to_string())
+            .unwrap_or_else(|| "Unknown".to_string());
+        let mut line = format!("{} [{}] {} {} (PID {})", time, self.actor, self.kind, self.name, self.pid);
+        if let Some(detail) = &self.detail {
+            line.push_str(&format!(": {}", detail));
+        }
+        line
+    }
+}
+
+// The journal lives next to the state file
+pub fn journal_file(state_file: &Path) -> PathBuf {
+    state_file.with_file_name("events.jsonl")
+}
+
+// Appends take it shared and compaction exclusive, so no event is written to a journal that is
+// about to be replaced
+fn lock(state_file: &Path, exclusive: bool) -> Option<File> {
+    let file = OpenOptions::new().write(true).create(true).truncate(false).open(state_file.with_file_name("events.jsonl.lock")).ok()?;
+    let locked = if exclusive { file.lock_exclusive() } else { file.lock_shared() };
+    locked.ok().map(|_| file)
+}
+
+// Appends one line; O_APPEND keeps concurrent writers from interleaving short records
+pub fn record(state_file: &Path, kind: &str, pid: &str, name: &str, detail: Option<String>) {
+    let event = EventRecord {
+        ts: SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs_f64(),
+        kind: kind.to_string(),
+        actor: actor_name().to_string(),
+        pid: pid.to_string(),
+        name: name.to_string(),
+        detail,
+    };
+    let _guard = lock(state_file, false);
+    if let Ok(line) = serde_json::to_string(&event)
+        && let Ok(mut f) = OpenOptions::new().create(true).append(true).open(journal_file(state_file))
+    {
+        writeln!(f, "{}", line).ok();
+    }
+}
+
+// Drops events past the retention window and all but the newest MAX_EVENTS, like the
+// stats history compaction. Only rewrites the journal when something is dropped.
+pub fn compact(state_file: &Path, now: f64) {
+    let Some(_guard) = lock(state_file, true) else { return };
+    let events = read_events(state_file);
+    let recent: Vec<&EventRecord> = events.iter().filter(|e| now - e.ts < RETENTION_SECS).collect();
+    let kept = &recent[recent.len().saturating_sub(MAX_EVENTS)..];
+    if kept.len() == events.len() {
+        return;
+    }
+
+    let path = journal_file(state_file);
+    let tmp = path.with_extension("jsonl.tmp");
+    if let Ok(mut f) = File::create(&tmp) {
+        for e in kept {
+            if let Ok(line) = serde_json::to_string(e) {
+                writeln!(f, "{}", line).ok();
+            }
+        }
+        fs::rename(&tmp, &path).ok();
+    }
+}
+
+pub fn read_events(state_file: &Path) -> Vec<EventRecord> {
+    let Ok(file) = fs::File::open(journal_file(state_file)) else {
+        return Vec::new();
+    };
+    BufReader::new(file)
+        .lines()
+        .map_while(Result::ok)
+        .filter_map(|l| serde_json::from_str(&l).ok())
+        .collect()
+}
+
+// Reads only the end of the journal, for the TUI pane which refreshes constantly
+pub fn read_recent_events(state_file: &Path, max_bytes: u64) -> Vec<EventRecord> {
+    let Ok(mut file) = fs::File::open(journal_file(state_file)) else {
+        return Vec::new();
+    };
+    let len = file.metadata().map(|m| m.len()).unwrap_or(0);
+    let start = len.saturating_sub(max_bytes);
+    if file.seek(SeekFrom::Start(start)).is_err() {
+        return Vec::new();
+    }
+    let mut buf = String::new();
+    if file.read_to_string(&mut buf).is_err() {
+        return Vec::new();
+    }
+    // When starting mid-file the first line is partial
+    buf.lines()
+        .skip(if start > 0 { 1 } else { 0 })
+        .filter_map(|l| serde_json::from_str(l).ok())
+        .collect()
+}
+
+// Prints matching events, then keeps printing new ones as they are appended
+pub fn follow(state_file: &Path, name: Option<&str>, lines: usize, follow: bool) {
+    let matches = |e: &EventRecord| name.is_none_or(|n| e.name == n);
+
+    let all = read_events(state_file);
+    let events: Vec<&EventRecord> = all.iter().filter(|e| matches(e)).collect();
+    for e in events.iter().skip(events.len().saturating_sub(lines)) {
+        println!("{}", e.format_line());
+    }
+    if !follow {
+        return;
+    }
+
+    let path = journal_file(state_file);
+    let mut offset = fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+    let mut inode = fs::metadata(&path).map(|m| m.ino()).unwrap_or(0);
+    let mut last_line = all.last().and_then(|e| serde_json::to_string(e).ok());
+    let mut pending = String::new();
+    loop {
+        std::thread::sleep(std::time::Duration::from_millis(500));
+        let Ok(mut file) = fs::File::open(&path) else { continue };
+        let (len, ino) = file.metadata().map(|m| (m.len(), m.ino())).unwrap_or((0, 0));
+        if ino != inode || len < offset {
+            // Compacted or replaced: carry on after the last line already seen, if it was kept
+            let mut content = String::new();
+            file.read_to_string(&mut content).ok();
+            offset = last_line.as_ref()
+                .and_then(|l| content.find(&format!("{}\n", l)).map(|i| (i + l.len() + 1) as u64))
+                .unwrap_or(0);
+            inode = ino;
+            pending.clear();
+        }
+        if len == offset || file.seek(SeekFrom::Start(offset)).is_err() {
+            continue;
+        }
+        let mut chunk = String::new();
+        if file.read_to_string(&mut chunk).is_err() {
+            continue;
+        }
+        offset += chunk.len() as u64;
+        pending.push_str(&chunk);
+
+        // Keep an incomplete trailing line for the next round
+        while let Some(idx) = pending.find('\n') {
+            let line: String = pending.drain(..=idx).collect();
+            let line = line.trim_end();
+            if let Ok(e) = serde_json::from_str::<EventRecord>(line)
+                && matches(&e)
+            {
+                println!("{}", e.format_line());
+            }
+            last_line = Some(line.to_string());
+        }
+    }
+}
@@ -0,0 +1,235 @@
+use std::{fs::{self, OpenOptions}, io::{self, Read, Write}, net::TcpStream, path::{Path, PathBuf}, process::{Command, Stdio}, sync::{mpsc, Condvar, Mutex, OnceLock}, time::{Duration, Instant, SystemTime, UNIX_EPOCH}};
+use serde::{Deserialize, Serialize};
+use crate::types::ProcessInfo;
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct Hooks {
+    pub on_exit: Option<String>,
+    pub on_crash: Option<String>,
+    pub on_restart: Option<String>,
+    pub on_timeout: Option<String>,
+    pub on_unhealthy: Option<String>,
+    pub webhook: Option<String>, // http:// URL that receives every event as a JSON POST
+}
+
+impl Hooks {
+    pub fn is_empty(&self) -> bool {
+        self.on_exit.is_none() && self.on_crash.is_none() && self.on_restart.is_none()
+            && self.on_timeout.is_none() && self.on_unhealthy.is_none() && self.webhook.is_none()
+    }
+
+    fn command_for(&self, event: &str) -> Option<&String> {
+        match event {
+            "exit" => self.on_exit.as_ref(),
+            "crash" => self.on_crash.as_ref(),
+            "restart" => self.on_restart.as_ref(),
+            "timeout" => self.on_timeout.as_ref(),
+            "unhealthy" => self.on_unhealthy.as_ref(),
+            _ => None,
+        }
+    }
+
+    // Keys accepted by `ksai_proc hooks set/unset`
+    pub fn slot(&mut self, key: &str) -> Option<&mut Option<String>> {
+        match key {
+            "on_exit" => Some(&mut self.on_exit),
+            "on_crash" => Some(&mut self.on_crash),
+            "on_restart" => Some(&mut self.on_restart),
+            "on_timeout" => Some(&mut self.on_timeout),
+            "on_unhealthy" => Some(&mut self.on_unhealthy),
+            "webhook" => Some(&mut self.webhook),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct HookEvent {
+    pub event: String, // exit, crash, restart, timeout, unhealthy
+    pub pid: String,
+    pub name: String,
+    pub status: String,
+    pub exit_code: Option<i32>,
+    pub reason: Option<String>,
+    pub cmd: String,
+    pub working_dir: String,
+    pub log_file: String,
+    pub timestamp: u64,
+    #[serde(skip)]
+    hooks: Hooks,
+}
+
+impl HookEvent {
+    pub fn new(event: &str, pid: &str, proc: &ProcessInfo, reason: Option<String>) -> Self {
+        Self {
+            event: event.to_string(),
+            pid: pid.to_string(),
+            name: proc.display_name.clone(),
+            status: proc.status.clone(),
+            exit_code: proc.exit_code,
+            reason,
+            cmd: proc.cmd_str.clone(),
+            working_dir: proc.working_dir.clone(),
+            log_file: proc.log_file.clone(),
+            timestamp: SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs(),
+            hooks: proc.options.hooks.clone(),
+        }
+    }
+}
+
+// A process that ended on its own; a known non-zero exit code also counts as a crash
+pub fn exit_events(pid: &str, proc: &ProcessInfo) -> Vec<HookEvent> {
+    let mut events = vec![HookEvent::new("exit", pid, proc, None)];
+    if proc.exit_code.is_some_and(|c| c != 0) {
+        events.push(HookEvent::new("crash", pid, proc, None));
+    }
+    events
+}
+
+// Global hooks live next to the state file
+pub fn global_hooks_file(state_file: &Path) -> PathBuf {
+    state_file.with_file_name("hooks.json")
+}
+
+pub fn read_global_hooks(state_file: &Path) -> Hooks {
+    fs::read_to_string(global_hooks_file(state_file))
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+pub fn write_global_hooks(state_file: &Path, hooks: &Hooks) -> io::Result<()> {
+    fs::write(global_hooks_file(state_file), serde_json::to_string_pretty(hooks)?)
+}
+
+// Hooks are delivered by one worker thread so a slow command or an unreachable webhook never
+// holds up the TUI or the daemon tick. Deliveries queued but not finished yet are counted so
+// short-lived CLI invocations can wait for them before exiting.
+static QUEUE: OnceLock<mpsc::Sender<(PathBuf, Vec<HookEvent>)>> = OnceLock::new();
+static PENDING: (Mutex<usize>, Condvar) = (Mutex::new(0), Condvar::new());
+// How long `FlushOnExit` waits for outstanding hooks
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Queues the events; global hooks run first, then the process's own hooks
+pub fn fire(state_file: &Path, events: Vec<HookEvent>) {
+    if events.is_empty() {
+        return;
+    }
+    let queue = QUEUE.get_or_init(|| {
+        let (tx, rx) = mpsc::channel::<(PathBuf, Vec<HookEvent>)>();
+        std::thread::spawn(move || {
+            for (state_file, events) in rx {
+                deliver(&state_file, events);
+                *PENDING.0.lock().unwrap() -= 1;
+                PENDING.1.notify_all();
+            }
+        });
+        tx
+    });
+    *PENDING.0.lock().unwrap() += 1;
+    if queue.send((state_file.to_path_buf(), events)).is_err() {
+        *PENDING.0.lock().unwrap() -= 1;
+    }
+}
+
+// Held by main so queued hooks get delivered before the process exits
+pub struct FlushOnExit;
+
+impl Drop for FlushOnExit {
+    fn drop(&mut self) {
+        let deadline = Instant::now() + FLUSH_TIMEOUT;
+        let mut pending = PENDING.0.lock().unwrap();
+        while *pending > 0 {
+            let Some(left) = deadline.checked_duration_since(Instant::now()) else {
+                eprintln!("Gave up waiting for {} hook deliveries", *pending);
+                return;
+            };
+            pending = PENDING.1.wait_timeout(pending, left).unwrap().0;
+        }
+    }
+}
+
+// Failures are logged, never fatal
+fn deliver(state_file: &Path, events: Vec<HookEvent>) {
+    let global = read_global_hooks(state_file);
+    let hook_log = state_file.with_file_name("hooks.log");
+
+    for event in events {
+        for hooks in [&global, &event.hooks] {
+            if let Some(cmd) = hooks.command_for(&event.event)
+                && let Err(e) = run_command(cmd, &event, &hook_log)
+            {
+                eprintln!("Hook '{}' for {} failed: {}", cmd, event.event, e);
+            }
+            if let Some(url) = &hooks.webhook
+                && let Err(e) = post_webhook(url, &event)
+            {
+                eprintln!("Webhook {} for {} failed: {}", url, event.event, e);
+            }
+        }
+    }
+}
+
+// Event details are passed both as KSAI_* environment variables and as JSON on stdin
+fn run_command(cmd: &str, event: &HookEvent, hook_log: &Path) -> io::Result<()> {
+    let log = OpenOptions::new().create(true).append(true).open(hook_log)?;
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(cmd)
+        .env("KSAI_EVENT", &event.event)
+        .env("KSAI_PID", &event.pid)
+        .env("KSAI_NAME", &event.name)
+        .env("KSAI_STATUS", &event.status)
+        .env("KSAI_EXIT_CODE", event.exit_code.map(|c| c.to_string()).unwrap_or_default())
+        .env("KSAI_REASON", event.reason.clone().unwrap_or_default())
+        .env("KSAI_CMD", &event.cmd)
+        .env("KSAI_CWD", &event.working_dir)
+        .env("KSAI_LOG_FILE", &event.log_file)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::from(log.try_clone()?))
+        .stderr(Stdio::from(log))
+        .spawn()?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        // The hook may not read stdin at all, so a broken pipe is fine
+        let _ = stdin.write_all(serde_json::to_string(event)?.as_bytes());
+    }
+    // Don't block the caller on the hook; the thread just reaps it
+    std::thread::spawn(move || child.wait());
+    Ok(())
+}
+
+// Minimal HTTP/1.1 POST, enough for a local webhook receiver
+fn post_webhook(url: &str, event: &HookEvent) -> io::Result<()> {
+    let rest = url
+        .strip_prefix("http://")
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "only http:// webhooks are supported"))?;
+    let (host, path) = match rest.find('/') {
+        Some(i) => (&rest[..i], &rest[i..]),
+        None => (rest, "/"),
+    };
+    let addr = if host.contains(':') { host.to_string() } else { format!("{}:80", host) };
+
+    let timeout = Duration::from_secs(2);
+    let sock_addr = std::net::ToSocketAddrs::to_socket_addrs(&addr)?
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "could not resolve webhook host"))?;
+    let mut stream = TcpStream::connect_timeout(&sock_addr, timeout)?;
+    stream.set_read_timeout(Some(timeout))?;
+    stream.set_write_timeout(Some(timeout))?;
+
+    let body = serde_json::to_string(event)?;
+    write!(
+        stream,
+        "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        path, host, body.len(), body
+    )?;
+
+    let mut status_line = [0u8; 12];
+    stream.read_exact(&mut status_line)?;
+    let status = String::from_utf8_lossy(&status_line[9..12]).to_string();
+    if !status.starts_with('2') {
+        return Err(io::Error::other(format!("webhook responded with HTTP {}", status)));
+    }
+    Ok(())
+}
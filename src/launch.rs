@@ -0,0 +1,415 @@
+use std::{collections::BTreeMap, env, fs, io, path::{Path, PathBuf}};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use crate::{config, process, state::{read_json, update_json}, types::{ProcessOptions, RestartPolicy}};
+
+// Launches kept in the history, newest first
+const HISTORY_LIMIT: usize = 200;
+
+// Launch form for a new process in the TUI
+pub struct LaunchForm {
+    pub fields: [String; 6], // command, name, directory, timeout, restart, env
+    pub focus: usize,
+    // The field a validation or launch error is about, and the message shown under the form
+    pub error: Option<(usize, String)>,
+    // Directories offered by the last Tab in the directory field
+    pub completions: Vec<String>,
+    // Past launches, newest first; Up/Down in the command field step through them
+    pub history: Vec<LaunchSpec>,
+    pub history_index: Option<usize>,
+    // What was typed before stepping into the history, brought back by stepping out of it
+    draft: Option<[String; 6]>,
+    // Ctrl-R search through the history
+    pub search: Option<HistorySearch>,
+}
+
+pub struct HistorySearch {
+    pub query: String,
+    // Index into LaunchForm::search_matches()
+    pub selected: usize,
+}
+
+// A launch as the form describes it; kept in the launch history and as named templates
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct LaunchSpec {
+    pub command: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    // Empty means the directory ksai_proc is started from
+    #[serde(default)]
+    pub working_dir: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_sec: Option<f64>,
+    // None means process.restart_policy from the config
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub restart_policy: Option<RestartPolicy>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+}
+
+impl LaunchSpec {
+    pub fn options(&self) -> ProcessOptions {
+        ProcessOptions {
+            restart_policy: self.restart_policy.unwrap_or(config::settings().process.restart_policy),
+            env: self.env.clone(),
+            ..Default::default()
+        }
+    }
+
+    pub fn start(&self, state_file: &Path, log_dir: &Path) -> io::Result<()> {
+        let exe_dir = env::current_exe()?.parent().map(Path::to_path_buf).unwrap_or_default();
+        let working_dir = Some(self.working_dir.clone()).filter(|d| !d.is_empty());
+        process::launch_process_with_name(&exe_dir, state_file, log_dir, &self.command, self.timeout_sec, self.name.clone(), working_dir, self.options())
+    }
+
+    // One line for lists and searching
+    pub fn summary(&self) -> String {
+        let mut summary = join_args(&self.command);
+        if let Some(name) = &self.name {
+            summary = format!("{}: {}", name, summary);
+        }
+        if !self.working_dir.is_empty() {
+            summary.push_str(&format!("  (in {})", self.working_dir));
+        }
+        summary
+    }
+}
+
+impl LaunchForm {
+    pub const LABELS: [&'static str; 6] = ["Command", "Name", "Directory", "Timeout", "Restart", "Env"];
+    pub const HINTS: [&'static str; 6] = [
+        "program and arguments; quote arguments that contain spaces",
+        "shown in the list; defaults to the program name",
+        "[Tab] completes",
+        "seconds (e.g. 30 or 30s); empty for none",
+        "never, on-failure or always",
+        "KEY=VALUE pairs separated by spaces",
+    ];
+    pub const DIRECTORY: usize = 2;
+
+    pub fn new(history: Vec<LaunchSpec>) -> Self {
+        let cwd = env::current_dir().map(|d| d.to_string_lossy().to_string()).unwrap_or_default();
+        Self {
+            fields: [String::new(), String::new(), cwd, String::new(), policy_name(config::settings().process.restart_policy), String::new()],
+            focus: 0,
+            error: None,
+            completions: Vec::new(),
+            history,
+            history_index: None,
+            draft: None,
+            search: None,
+        }
+    }
+
+    // Fills every field from a saved launch
+    pub fn fill(&mut self, spec: &LaunchSpec) {
+        let env: Vec<String> = spec.env.iter().map(|(k, v)| format!("{}={}", k, quote_arg(v))).collect();
+        self.fields = [
+            join_args(&spec.command),
+            spec.name.clone().unwrap_or_default(),
+            if spec.working_dir.is_empty() { self.fields[Self::DIRECTORY].clone() } else { spec.working_dir.clone() },
+            spec.timeout_sec.map(|t| t.to_string()).unwrap_or_default(),
+            policy_name(spec.restart_policy.unwrap_or(config::settings().process.restart_policy)),
+            env.join(" "),
+        ];
+        self.error = None;
+    }
+
+    // Up (older) and Down (newer) in the command field
+    pub fn step_history(&mut self, older: bool) {
+        let next = match (self.history_index, older) {
+            (None, true) if !self.history.is_empty() => Some(0),
+            (None, _) => return,
+            (Some(i), true) => Some((i + 1).min(self.history.len() - 1)),
+            (Some(0), false) => None,
+            (Some(i), false) => Some(i - 1),
+        };
+        if self.history_index.is_none() {
+            self.draft = Some(self.fields.clone());
+        }
+        self.history_index = next;
+        match next {
+            Some(i) => {
+                let spec = self.history[i].clone();
+                self.fill(&spec);
+            }
+            None => {
+                if let Some(draft) = self.draft.take() {
+                    self.fields = draft;
+                }
+            }
+        }
+    }
+
+    // History entries matching the Ctrl-R query, best first
+    pub fn search_matches(&self) -> Vec<usize> {
+        let query = self.search.as_ref().map(|s| s.query.as_str()).unwrap_or("");
+        let mut scored: Vec<(usize, usize)> = self.history
+            .iter()
+            .enumerate()
+            .filter_map(|(i, spec)| fuzzy_score(query, &spec.summary()).map(|score| (score, i)))
+            .collect();
+        // Equal scores keep the history order, so the most recent wins
+        scored.sort_by_key(|(score, _)| *score);
+        scored.into_iter().map(|(_, i)| i).collect()
+    }
+
+    pub fn validate(&self) -> Result<LaunchSpec, (usize, String)> {
+        let [command, name, directory, timeout, restart, env_vars] = &self.fields;
+        let args = split_args(command).map_err(|e| (0, e))?;
+        if args.is_empty() {
+            return Err((0, "A command is needed".to_string()));
+        }
+
+        let dir = config::expand(Path::new(directory.trim()));
+        let dir = if dir.is_absolute() { dir } else { env::current_dir().unwrap_or_default().join(dir) };
+        if !dir.is_dir() {
+            return Err((2, format!("{} is not a directory", dir.display())));
+        }
+
+        let timeout_sec = match timeout.trim() {
+            "" => None,
+            t => Some(process::parse_timeout(t).filter(|t| *t > 0.0).ok_or_else(|| (3, format!("Invalid timeout \"{}\"", t)))?),
+        };
+
+        let restart_policy = match restart.trim() {
+            "" => config::settings().process.restart_policy,
+            r => RestartPolicy::from_str(r, true).map_err(|_| (4, format!("Unknown restart policy \"{}\"", r)))?,
+        };
+
+        let mut env = BTreeMap::new();
+        for var in split_args(env_vars).map_err(|e| (5, e))? {
+            let (key, value) = process::parse_env(&var).ok_or_else(|| (5, format!("\"{}\" is not KEY=VALUE", var)))?;
+            env.insert(key, value);
+        }
+
+        Ok(LaunchSpec {
+            command: args,
+            name: Some(name.trim().to_string()).filter(|n| !n.is_empty()),
+            working_dir: fs::canonicalize(&dir).unwrap_or(dir).to_string_lossy().to_string(),
+            timeout_sec,
+            restart_policy: Some(restart_policy),
+            env,
+        })
+    }
+
+    // Tab in the directory field: completes it, lists the choices when it can't, and moves on
+    // when pressed again with nothing left to complete. Returns whether the focus should stay.
+    pub fn complete(&mut self) -> bool {
+        let (completed, choices) = complete_dir(&self.fields[Self::DIRECTORY]);
+        if let Some(text) = completed {
+            self.fields[Self::DIRECTORY] = text;
+            self.completions = if choices.len() > 1 { choices } else { Vec::new() };
+            return true;
+        }
+        if choices.len() > 1 && self.completions != choices {
+            self.completions = choices;
+            return true;
+        }
+        self.completions.clear();
+        false
+    }
+
+    // A launch that failed on a duplicate name is the name field's problem; anything else the command's
+    pub fn launch_failed(&mut self, e: &io::Error) {
+        let field = if e.kind() == io::ErrorKind::AlreadyExists { 1 } else { 0 };
+        self.focus = field;
+        self.error = Some((field, format!("Launch error: {}", e)));
+    }
+}
+
+pub fn policy_name(policy: RestartPolicy) -> String {
+    policy.to_possible_value().map(|v| v.get_name().to_string()).unwrap_or_default()
+}
+
+// Splits a command line the way a POSIX shell would, without expansions: whitespace separates
+// arguments, single quotes keep everything literally, double quotes allow \" and \\, and a
+// backslash outside quotes escapes the next character.
+pub fn split_args(line: &str) -> Result<Vec<String>, String> {
+    let mut args = Vec::new();
+    let mut current: Option<String> = None;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {
+                if let Some(arg) = current.take() {
+                    args.push(arg);
+                }
+            }
+            '\'' => {
+                let arg = current.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => arg.push(c),
+                        None => return Err("Unterminated ' quote".to_string()),
+                    }
+                }
+            }
+            '"' => {
+                let arg = current.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\')) => arg.push(c),
+                            Some(c) => {
+                                arg.push('\\');
+                                arg.push(c);
+                            }
+                            None => return Err("Unterminated \" quote".to_string()),
+                        },
+                        Some(c) => arg.push(c),
+                        None => return Err("Unterminated \" quote".to_string()),
+                    }
+                }
+            }
+            '\\' => {
+                let arg = current.get_or_insert_with(String::new);
+                arg.push(chars.next().unwrap_or('\\'));
+            }
+            c => current.get_or_insert_with(String::new).push(c),
+        }
+    }
+    args.extend(current);
+    Ok(args)
+}
+
+// The inverse of split_args: quotes only the arguments that need it
+pub fn join_args(args: &[String]) -> String {
+    args.iter().map(|a| quote_arg(a)).collect::<Vec<_>>().join(" ")
+}
+
+fn quote_arg(arg: &str) -> String {
+    if !arg.is_empty() && !arg.contains(|c: char| c.is_whitespace() || "'\"\\".contains(c)) {
+        return arg.to_string();
+    }
+    if !arg.contains('\'') {
+        return format!("'{}'", arg);
+    }
+    format!("\"{}\"", arg.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+// Case-insensitive subsequence match; lower scores are better. Characters matched in a run and
+// matches near the start score best, so "srv" ranks "server.py" above "some/river.py".
+pub fn fuzzy_score(query: &str, text: &str) -> Option<usize> {
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    let mut score = 0;
+    let mut pos = 0;
+    for (i, q) in query.to_lowercase().chars().filter(|c| !c.is_whitespace()).enumerate() {
+        let found = pos + text[pos..].iter().position(|c| *c == q)?;
+        score += if i == 0 { found } else { (found - pos) * 2 };
+        pos = found + 1;
+    }
+    Some(score)
+}
+
+pub fn history_file(state_file: &Path) -> PathBuf {
+    state_file.with_file_name("launch_history.json")
+}
+
+pub fn templates_file(state_file: &Path) -> PathBuf {
+    state_file.with_file_name("templates.json")
+}
+
+pub fn read_history(state_file: &Path) -> Vec<LaunchSpec> {
+    read_json(&history_file(state_file))
+}
+
+// Moves a repeated launch to the front instead of keeping duplicates
+pub fn record_history(state_file: &Path, spec: &LaunchSpec) {
+    update_json(&history_file(state_file), |history: &mut Vec<LaunchSpec>| {
+        history.retain(|h| h != spec);
+        history.insert(0, spec.clone());
+        history.truncate(HISTORY_LIMIT);
+    });
+}
+
+pub fn read_templates(state_file: &Path) -> BTreeMap<String, LaunchSpec> {
+    read_json(&templates_file(state_file))
+}
+
+pub fn save_template(state_file: &Path, name: &str, spec: &LaunchSpec) {
+    update_json(&templates_file(state_file), |templates: &mut BTreeMap<String, LaunchSpec>| {
+        templates.insert(name.to_string(), spec.clone());
+    });
+}
+
+pub fn remove_template(state_file: &Path, name: &str) -> bool {
+    update_json(&templates_file(state_file), |templates: &mut BTreeMap<String, LaunchSpec>| templates.remove(name).is_some()).unwrap_or(false)
+}
+
+// Completes the last component of a directory path as far as the matching directories agree.
+// Returns the completed text (None when it can't be taken further) and the matching names.
+fn complete_dir(text: &str) -> (Option<String>, Vec<String>) {
+    let (parent, prefix) = match text.rfind('/') {
+        Some(i) => (&text[..=i], &text[i + 1..]),
+        None => ("", text),
+    };
+    let lookup = if parent.is_empty() { PathBuf::from(".") } else { config::expand(Path::new(parent)) };
+    let Ok(entries) = fs::read_dir(lookup) else {
+        return (None, Vec::new());
+    };
+    // Hidden directories only when asked for
+    let mut names: Vec<String> = entries
+        .flatten()
+        .filter(|e| e.path().is_dir())
+        .map(|e| e.file_name().to_string_lossy().to_string())
+        .filter(|n| n.starts_with(prefix) && (prefix.starts_with('.') || !n.starts_with('.')))
+        .collect();
+    names.sort();
+
+    let Some(first) = names.first() else {
+        return (None, names);
+    };
+    let common: String = names.iter().skip(1).fold(first.clone(), |common, name| {
+        common.chars().zip(name.chars()).take_while(|(a, b)| a == b).map(|(a, _)| a).collect()
+    });
+    let completed = if names.len() == 1 { format!("{}{}/", parent, common) } else { format!("{}{}", parent, common) };
+    let names = names.into_iter().map(|n| n + "/").collect();
+    ((completed != text).then_some(completed), names)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn split_args_handles_quotes_and_escapes() {
+        assert_eq!(split_args("  python  -m http.server ").unwrap(), strings(&["python", "-m", "http.server"]));
+        assert_eq!(split_args(r#"echo 'a b' "c \"d\" \n" e\ f"#).unwrap(), strings(&["echo", "a b", r#"c "d" \n"#, "e f"]));
+        assert_eq!(split_args("x'' \"\"").unwrap(), strings(&["x", ""]));
+        assert_eq!(split_args("").unwrap(), Vec::<String>::new());
+    }
+
+    #[test]
+    fn split_args_rejects_unterminated_quotes() {
+        assert!(split_args("echo 'oops").is_err());
+        assert!(split_args("echo \"oops").is_err());
+        assert!(split_args("echo \"oops\\").is_err());
+    }
+
+    #[test]
+    fn quote_arg_round_trips() {
+        assert_eq!(quote_arg("plain"), "plain");
+        assert_eq!(quote_arg("a b"), "'a b'");
+        assert_eq!(quote_arg(""), "''");
+        assert_eq!(quote_arg("it's"), r#""it's""#);
+        let args = strings(&["sh", "-c", "echo \"it's\" \\ done", "", "a\tb"]);
+        assert_eq!(split_args(&join_args(&args)).unwrap(), args);
+    }
+
+    #[test]
+    fn fuzzy_score_prefers_runs_near_the_start() {
+        assert_eq!(fuzzy_score("", "anything"), Some(0));
+        assert_eq!(fuzzy_score("xyz", "server.py"), None);
+        assert_eq!(fuzzy_score("SRV", "server.py"), fuzzy_score("srv", "server.py"));
+        assert!(fuzzy_score("srv", "server.py") < fuzzy_score("srv", "some/river.py"));
+        assert!(fuzzy_score("ser", "server.py") < fuzzy_score("ser", "my_server.py"));
+        assert_eq!(fuzzy_score("s p", "server.py"), fuzzy_score("sp", "server.py"));
+    }
+}
@@ -0,0 +1,508 @@
+use std::{borrow::Cow, collections::{HashMap, VecDeque}, fs::{self, File}, io::{self, BufRead, BufReader, Read, Seek, SeekFrom}, path::Path};
+use regex::Regex;
+use crate::ansi;
+
+// Bytes read per step when scanning a log backwards for older lines
+const CHUNK: u64 = 64 * 1024;
+
+// The last lines of one log file, kept up to date as the process writes
+pub struct LogTail {
+    pub lines: VecDeque<String>,
+    reader: BufReader<File>,
+    // Lines kept in memory; raised when scrolling or searching past the oldest one
+    limit: usize,
+    // True once `lines` starts at the beginning of the file
+    pub complete: bool,
+    // Output written since the last newline, e.g. a progress bar redrawn with `\r`.
+    // It is shown as the last line and replaced as it grows.
+    partial: String,
+    // Lines completed by the last poll
+    finished: usize,
+}
+
+impl LogTail {
+    pub fn open(path: &Path, limit: usize) -> io::Result<Self> {
+        let mut file = File::open(path)?;
+        let end = file.metadata()?.len();
+        // An unfinished last line is left to the first poll, which then tracks it as partial
+        let end = line_start(&mut file, end)?;
+        let (lines, complete) = read_last_lines(&mut file, end, limit)?;
+        file.seek(SeekFrom::Start(end))?;
+        Ok(Self { lines, reader: BufReader::new(file), limit, complete, partial: String::new(), finished: 0 })
+    }
+
+    // Reads what was written since the last call; returns the number of new lines
+    pub fn poll(&mut self, path: &Path) -> usize {
+        // The daemon's log rotation truncates the file in place; start reading it again from the top
+        let truncated = self.reader.stream_position().is_ok_and(|pos| fs::metadata(path).is_ok_and(|m| m.len() < pos));
+        if truncated {
+            self.reader.rewind().ok();
+            self.lines.clear();
+            self.complete = true;
+            self.partial.clear();
+        }
+
+        let mut added = 0;
+        self.finished = 0;
+        let mut line = String::new();
+        while let Ok(n) = self.reader.read_line(&mut line) {
+            if n == 0 {
+                break;
+            }
+            // Continue the unfinished line instead of starting a new one
+            let continued = !self.partial.is_empty();
+            if continued {
+                self.lines.pop_back();
+                line.insert_str(0, &std::mem::take(&mut self.partial));
+            }
+            if line.ends_with('\n') {
+                self.finished += 1;
+            } else {
+                self.partial = line.clone();
+            }
+            self.lines.push_back(line.trim_end().to_string());
+            if !continued {
+                added += 1;
+            }
+            if self.lines.len() > self.limit {
+                self.lines.pop_front();
+                self.complete = false;
+            }
+            line.clear();
+        }
+        added
+    }
+
+    // Loads older lines from disk, up to `limit` in total; false if there is nothing older
+    pub fn grow(&mut self, limit: usize) -> bool {
+        if self.complete || limit <= self.limit {
+            return false;
+        }
+        // Read up to where the reader is, so the newest lines stay exactly the same
+        let Ok(end) = self.reader.stream_position() else { return false };
+        match read_last_lines(self.reader.get_mut(), end, limit) {
+            Ok((lines, complete)) => {
+                self.lines = lines;
+                self.complete = complete;
+                self.limit = limit;
+            }
+            Err(_) => return false,
+        }
+        self.reader.seek(SeekFrom::Start(end)).is_ok()
+    }
+
+    // The loaded lines that are complete, leaving out an unfinished last one
+    pub fn finished_lines(&self) -> impl Iterator<Item = &String> {
+        self.lines.range(..self.finished_end())
+    }
+
+    // The lines the last poll completed, oldest first
+    pub fn just_finished(&self) -> impl Iterator<Item = &String> {
+        let end = self.finished_end();
+        self.lines.range(end.saturating_sub(self.finished)..end)
+    }
+
+    fn finished_end(&self) -> usize {
+        self.lines.len() - usize::from(!self.partial.is_empty())
+    }
+}
+
+// What a LogView scrolls through: one process's log or several merged
+pub trait LogLines {
+    fn lines(&self) -> &VecDeque<String>;
+    // Loads older lines; false if there are none
+    fn load_more(&mut self) -> bool;
+    fn load_all(&mut self) -> bool;
+}
+
+impl LogLines for LogTail {
+    fn lines(&self) -> &VecDeque<String> {
+        &self.lines
+    }
+
+    fn load_more(&mut self) -> bool {
+        self.grow(self.limit.saturating_mul(2))
+    }
+
+    fn load_all(&mut self) -> bool {
+        self.grow(usize::MAX)
+    }
+}
+
+// Lines of several logs interleaved in the order they are read, like `docker compose logs`
+pub struct MergedLog {
+    // The merged log files; `sources` holds an index into this for every line
+    pub files: Vec<String>,
+    pub lines: VecDeque<String>,
+    pub sources: VecDeque<usize>,
+    limit: usize,
+}
+
+impl MergedLog {
+    // Starts from what is already loaded of each log. There is no telling how older lines
+    // interleave, so each log's history comes as one block, the most recently written last.
+    pub fn new(files: Vec<String>, tails: &HashMap<String, LogTail>, limit: usize) -> Self {
+        let mut merged = Self { files, lines: VecDeque::new(), sources: VecDeque::new(), limit };
+        let mut order: Vec<usize> = (0..merged.files.len()).collect();
+        order.sort_by_key(|&i| fs::metadata(&merged.files[i]).and_then(|m| m.modified()).ok());
+        for i in order {
+            if let Some(tail) = tails.get(&merged.files[i]) {
+                for line in tail.finished_lines() {
+                    merged.push(i, line.clone());
+                }
+            }
+        }
+        merged
+    }
+
+    pub fn push(&mut self, source: usize, line: String) {
+        self.lines.push_back(line);
+        self.sources.push_back(source);
+        if self.lines.len() > self.limit {
+            self.lines.pop_front();
+            self.sources.pop_front();
+        }
+    }
+}
+
+impl LogLines for MergedLog {
+    fn lines(&self) -> &VecDeque<String> {
+        &self.lines
+    }
+
+    // Older lines of the merged stream were never read in order, so there is nothing to load
+    fn load_more(&mut self) -> bool {
+        false
+    }
+
+    fn load_all(&mut self) -> bool {
+        false
+    }
+}
+
+// Where the line containing byte `end - 1` starts; `end` itself if that byte is a newline
+fn line_start(file: &mut File, end: u64) -> io::Result<u64> {
+    let mut pos = end;
+    while pos > 0 {
+        let step = CHUNK.min(pos);
+        let mut chunk = vec![0; step as usize];
+        file.seek(SeekFrom::Start(pos - step))?;
+        file.read_exact(&mut chunk)?;
+        if let Some(i) = chunk.iter().rposition(|&b| b == b'\n') {
+            return Ok(pos - step + i as u64 + 1);
+        }
+        pos -= step;
+    }
+    Ok(0)
+}
+
+// The last `limit` lines before byte `end`, and whether they reach back to the start of the file
+fn read_last_lines(file: &mut File, end: u64, limit: usize) -> io::Result<(VecDeque<String>, bool)> {
+    let mut pos = end;
+    let mut buf = Vec::new();
+    while pos > 0 && buf.iter().filter(|&&b| b == b'\n').count() <= limit {
+        let step = CHUNK.min(pos);
+        pos -= step;
+        let mut chunk = vec![0; step as usize];
+        file.seek(SeekFrom::Start(pos))?;
+        file.read_exact(&mut chunk)?;
+        chunk.extend_from_slice(&buf);
+        buf = chunk;
+    }
+
+    let text = String::from_utf8_lossy(&buf);
+    let mut lines: VecDeque<String> = text.lines().map(|l| l.trim_end().to_string()).collect();
+    // Unless we reached the start, the first line was cut in half by the chunk boundary
+    if pos > 0 {
+        lines.pop_front();
+    }
+    let complete = pos == 0 && lines.len() <= limit;
+    while lines.len() > limit {
+        lines.pop_front();
+    }
+    Ok((lines, complete))
+}
+
+// Scroll and search state of the log pane. Positions are counted in lines from the bottom,
+// so they stay put while new output is appended.
+pub struct LogView {
+    // 0 follows the log
+    pub scroll: usize,
+    pub wrap: bool,
+    // Show escape codes instead of rendering them
+    pub raw: bool,
+    pub search: Option<Regex>,
+    pub current_match: Option<usize>,
+    // Rows available at the last draw, for paging
+    pub height: usize,
+}
+
+impl LogView {
+    pub fn new() -> Self {
+        Self { scroll: 0, wrap: false, raw: false, search: None, current_match: None, height: 20 }
+    }
+
+    // Keeps a frozen view and the current match on the same lines as output comes in
+    pub fn appended(&mut self, added: usize, total: usize) {
+        if self.scroll > 0 {
+            self.scroll += added;
+        }
+        self.current_match = self.current_match.map(|m| m + added).filter(|&m| m < total);
+        self.scroll = self.scroll.min(total.saturating_sub(1));
+    }
+
+    pub fn page_up(&mut self, log: &mut dyn LogLines) {
+        let wanted = self.scroll + self.height.max(1);
+        if wanted + self.height > log.lines().len() {
+            log.load_more();
+        }
+        self.scroll = wanted.min(log.lines().len().saturating_sub(self.height));
+    }
+
+    pub fn page_down(&mut self) {
+        self.scroll = self.scroll.saturating_sub(self.height.max(1));
+    }
+
+    // Loads the whole file so the very first line can be shown
+    pub fn top(&mut self, log: &mut dyn LogLines) {
+        log.load_all();
+        self.scroll = log.lines().len().saturating_sub(self.height);
+    }
+
+    pub fn follow(&mut self) {
+        self.scroll = 0;
+    }
+
+    // Jumps to the most recent match
+    pub fn start_search(&mut self, pattern: &str, log: &mut dyn LogLines) -> Result<(), String> {
+        let re = Regex::new(pattern).map_err(|e| e.to_string())?;
+        self.search = Some(re);
+        self.current_match = None;
+        if self.find(log, true, 0) {
+            Ok(())
+        } else {
+            Err(format!("Pattern not found: {}", pattern))
+        }
+    }
+
+    pub fn clear_search(&mut self) {
+        self.search = None;
+        self.current_match = None;
+    }
+
+    // Moves to the next older or newer match; older matches may pull more of the file from disk
+    pub fn next_match(&mut self, log: &mut dyn LogLines, older: bool) -> bool {
+        let from = match (self.current_match, older) {
+            (Some(m), true) => m + 1,
+            (Some(0), false) => return false,
+            (Some(m), false) => m - 1,
+            (None, _) => 0,
+        };
+        if self.find(log, older, from) {
+            return true;
+        }
+        older && log.load_all() && self.find(log, older, from)
+    }
+
+    fn find(&mut self, log: &dyn LogLines, older: bool, from: usize) -> bool {
+        let Some(re) = &self.search else { return false };
+        let lines = log.lines();
+        let len = lines.len();
+        if len == 0 {
+            return false;
+        }
+        let matches = |offset: &usize| re.is_match(&self.text(&lines[len - 1 - offset]));
+        let found = if older {
+            (from..len).find(matches)
+        } else {
+            (0..=from.min(len - 1)).rev().find(matches)
+        };
+        let Some(offset) = found else { return false };
+        self.current_match = Some(offset);
+        // Center the match in the pane
+        self.scroll = offset.saturating_sub(self.height / 2);
+        true
+    }
+
+    // How many of the loaded lines match, for the pane title
+    pub fn match_count(&self, log: &dyn LogLines) -> usize {
+        self.search.as_ref().map_or(0, |re| log.lines().iter().filter(|l| re.is_match(&self.text(l))).count())
+    }
+
+    // What the pane shows for a line, which is also what searches run against
+    pub fn text<'a>(&self, line: &'a str) -> Cow<'a, str> {
+        if self.raw { ansi::escape(line) } else { ansi::plain(line) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A log file unique to the test, removed again when dropped
+    struct TempLog(std::path::PathBuf);
+
+    impl TempLog {
+        fn new(tag: &str, contents: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("ksai_proc_logview_{}_{}.log", std::process::id(), tag));
+            fs::write(&path, contents).unwrap();
+            Self(path)
+        }
+
+        fn append(&self, text: &str) {
+            use std::io::Write;
+            fs::OpenOptions::new().append(true).open(&self.0).unwrap().write_all(text.as_bytes()).unwrap();
+        }
+    }
+
+    impl Drop for TempLog {
+        fn drop(&mut self) {
+            fs::remove_file(&self.0).ok();
+        }
+    }
+
+    fn last_lines(contents: &str, limit: usize) -> (Vec<String>, bool) {
+        let log = TempLog::new(&format!("last_{}_{}", contents.len(), limit), contents);
+        let mut file = File::open(&log.0).unwrap();
+        let (lines, complete) = read_last_lines(&mut file, contents.len() as u64, limit).unwrap();
+        (lines.into(), complete)
+    }
+
+    #[test]
+    fn reads_whole_short_files() {
+        assert_eq!(last_lines("a\nb  \nc\n", 10), (vec!["a".to_string(), "b".to_string(), "c".to_string()], true));
+        assert_eq!(last_lines("", 10), (Vec::new(), true));
+    }
+
+    #[test]
+    fn keeps_only_the_last_lines() {
+        assert_eq!(last_lines("a\nb\nc\nd", 2), (vec!["c".to_string(), "d".to_string()], false));
+    }
+
+    #[test]
+    fn drops_the_line_cut_by_a_chunk_boundary() {
+        // More than one chunk, so the scan stops partway through a line
+        let contents: String = (0..20_000).map(|i| format!("line {}\n", i)).collect();
+        let (lines, complete) = last_lines(&contents, 3);
+        assert_eq!(lines, vec!["line 19997", "line 19998", "line 19999"]);
+        assert!(!complete);
+        let (lines, _) = last_lines(&contents, 10_000);
+        assert_eq!(lines.len(), 10_000);
+        assert_eq!(lines[0], "line 10000");
+    }
+
+    #[test]
+    fn tail_follows_partial_lines() {
+        let log = TempLog::new("partial", "one\ntwo");
+        let mut tail = LogTail::open(&log.0, 10).unwrap();
+        assert_eq!(tail.lines, ["one"]);
+        assert_eq!(tail.poll(&log.0), 1);
+        assert_eq!(tail.lines, ["one", "two"]);
+        assert_eq!(tail.finished_lines().count(), 1);
+
+        // The unfinished line is replaced as it grows, not added again
+        log.append(" more\nthree\n");
+        assert_eq!(tail.poll(&log.0), 1);
+        assert_eq!(tail.lines, ["one", "two more", "three"]);
+        assert_eq!(tail.just_finished().collect::<Vec<_>>(), ["two more", "three"]);
+
+        // Truncation by log rotation starts over from the top
+        fs::write(&log.0, "new\n").unwrap();
+        tail.poll(&log.0);
+        assert_eq!(tail.lines, ["new"]);
+    }
+
+    #[test]
+    fn tail_grows_into_older_lines() {
+        let contents: String = (0..100).map(|i| format!("{}\n", i)).collect();
+        let log = TempLog::new("grow", &contents);
+        let mut tail = LogTail::open(&log.0, 10).unwrap();
+        assert_eq!(tail.lines.len(), 10);
+        assert!(!tail.complete);
+        assert!(tail.load_more());
+        assert_eq!(tail.lines.len(), 20);
+        assert_eq!(tail.lines[0], "80");
+        assert!(tail.load_all());
+        assert!(tail.complete);
+        assert_eq!(tail.lines.len(), 100);
+        assert!(!tail.load_more());
+    }
+
+    #[test]
+    fn search_moves_between_matches() {
+        let log = TempLog::new("search", "error 1\nok\n\x1b[31merror\x1b[0m 2\nok\nok\n");
+        let mut tail = LogTail::open(&log.0, 100).unwrap();
+        let mut view = LogView::new();
+        view.height = 2;
+
+        // Matches against the rendered text, so colour codes don't get in the way
+        view.start_search("error [0-9]", &mut tail).unwrap();
+        assert_eq!(view.current_match, Some(2));
+        assert_eq!(view.match_count(&tail), 2);
+        assert!(view.next_match(&mut tail, true));
+        assert_eq!(view.current_match, Some(4));
+        assert_eq!(view.scroll, 3);
+        assert!(!view.next_match(&mut tail, true));
+        assert!(view.next_match(&mut tail, false));
+        assert_eq!(view.current_match, Some(2));
+
+        // In raw mode the escape codes are part of the searched text
+        view.raw = true;
+        assert_eq!(view.match_count(&tail), 1);
+
+        assert!(view.start_search("missing", &mut tail).is_err());
+        assert!(view.start_search("(", &mut tail).is_err());
+    }
+
+    #[test]
+    fn frozen_view_stays_on_its_lines() {
+        let mut view = LogView::new();
+        view.scroll = 5;
+        view.current_match = Some(3);
+        view.appended(2, 100);
+        assert_eq!((view.scroll, view.current_match), (7, Some(5)));
+
+        // A following view keeps following
+        view.follow();
+        view.appended(2, 100);
+        assert_eq!(view.scroll, 0);
+    }
+
+    #[test]
+    fn paging_is_bounded_by_the_loaded_lines() {
+        let contents: String = (0..30).map(|i| format!("{}\n", i)).collect();
+        let log = TempLog::new("paging", &contents);
+        let mut tail = LogTail::open(&log.0, 100).unwrap();
+        let mut view = LogView::new();
+        view.height = 10;
+        view.page_up(&mut tail);
+        view.page_up(&mut tail);
+        view.page_up(&mut tail);
+        assert_eq!(view.scroll, 20);
+        view.page_down();
+        assert_eq!(view.scroll, 10);
+        view.top(&mut tail);
+        assert_eq!(view.scroll, 20);
+    }
+
+    #[test]
+    fn merged_log_interleaves_in_read_order() {
+        let older = TempLog::new("merged_a", "a1\na2\n");
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        let newer = TempLog::new("merged_b", "b1\npartial");
+        let files: Vec<String> = [&newer, &older].iter().map(|log| log.0.to_string_lossy().to_string()).collect();
+        let tails: HashMap<String, LogTail> = files.iter().map(|f| (f.clone(), LogTail::open(Path::new(f), 10).unwrap())).collect();
+
+        // History comes per log, least recently written first, without unfinished lines
+        let mut merged = MergedLog::new(files, &tails, 4);
+        assert_eq!(merged.lines, ["a1", "a2", "b1"]);
+        assert_eq!(merged.sources, [1, 1, 0]);
+
+        // New lines are appended as read and the oldest drop off past the limit
+        merged.push(0, "b2".to_string());
+        merged.push(1, "a3".to_string());
+        assert_eq!(merged.lines, ["a2", "b1", "b2", "a3"]);
+        assert_eq!(merged.sources, [1, 0, 0, 1]);
+        assert!(!merged.load_more());
+    }
+}
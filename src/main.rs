@@ -1,5 +1,5 @@
 use clap::{Parser, Subcommand};
-use std::{env, fs, path::PathBuf, time::{Duration, SystemTime, UNIX_EPOCH}};
+use std::{env, fs, path::Path, time::{SystemTime, UNIX_EPOCH}};
 use crossterm::{execute, terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen}};
 use ratatui::{backend::CrosstermBackend, Terminal};
 
@@ -10,8 +10,22 @@ mod types;
 mod monitor;
 mod app;
 mod scheduler;
+mod stats;
+mod watchdog;
+mod hooks;
+mod events;
+mod sqlite_store;
+mod schema;
+mod config;
+mod service;
+mod daemon;
+mod logview;
+mod proclist;
+mod ansi;
+mod trash;
+mod launch;
 
-use crate::{process::*, state::*, ui::*, app::App, types::{ScheduledJob, ProcessInfo}};
+use crate::{process::*, state::*, ui::*, app::App, launch::LaunchSpec, types::{ScheduledJob, ProcessInfo, ProcessOptions, ResourceLimits, RestartPolicy, WatchAction}};
 
 #[derive(Parser)]
 #[command(name = "ksai_proc")]
@@ -28,12 +42,17 @@ struct Cli {
     #[arg(short, long)]
     name: Option<String>,
 
+    /// Profile whose processes, schedules and logs to use (isolated from other profiles)
+    #[arg(long, global = true, value_parser = parse_profile_clap)]
+    profile: Option<String>,
+
     /// Launch a process directly (backwards compatibility)
     #[arg(allow_hyphen_values = true)]
     script_args: Vec<String>,
 }
 
 #[derive(Subcommand)]
+#[allow(clippy::large_enum_variant)] // parsed once at startup
 enum Commands {
     /// Run a new process
     Run {
@@ -49,6 +68,28 @@ enum Commands {
         #[arg(long, value_parser = parse_timeout_clap)]
         timeout: Option<f64>,
 
+        /// Restart the process when it exits (default: process.restart_policy from the config)
+        #[arg(long, value_enum)]
+        restart: Option<RestartPolicy>,
+
+        /// Tag for filtering and grouping in the TUI (repeatable)
+        #[arg(long = "tag", value_name = "TAG")]
+        tags: Vec<String>,
+
+        /// Environment variable for the process (repeatable)
+        #[arg(long = "env", value_name = "KEY=VALUE", value_parser = parse_env_clap)]
+        env: Vec<(String, String)>,
+
+        /// Launch a template saved from the TUI; other options override its settings
+        #[arg(long)]
+        template: Option<String>,
+
+        #[command(flatten)]
+        limits: LimitArgs,
+
+        #[command(flatten)]
+        hooks: HookArgs,
+
         #[arg(allow_hyphen_values = true)]
         script_args: Vec<String>,
     },
@@ -66,7 +107,7 @@ enum Commands {
     Remove { pid: String },
     /// Restart a process by PID
     Restart { pid: String },
-    /// View logs for a process by PID
+    /// View logs for a process by PID or name
     Logs {
         pid: String,
         /// Number of lines to show (default: 20)
@@ -76,8 +117,68 @@ enum Commands {
         #[arg(short, long)]
         follow: bool,
     },
+    /// Show recorded CPU/memory history for a process (PID or name)
+    Stats {
+        target: String,
+        /// How far back to look (e.g. 10m, 1h, 1d)
+        #[arg(long, default_value = "1h")]
+        since: String,
+        /// Number of samples to show (default: 20)
+        #[arg(short, long, default_value_t = 20)]
+        lines: usize,
+    },
+    /// Show the lifecycle event journal
+    Events {
+        /// Only show events for this process name
+        #[arg(long)]
+        name: Option<String>,
+        /// Keep printing new events as they happen
+        #[arg(short, long)]
+        follow: bool,
+        /// Number of past events to show (default: 20)
+        #[arg(short, long, default_value_t = 20)]
+        lines: usize,
+    },
+    /// Show past and current runs (full history with the SQLite backend)
+    Runs {
+        /// Only show runs of this process name
+        #[arg(long)]
+        name: Option<String>,
+        /// Number of runs to show (default: 20)
+        #[arg(short, long, default_value_t = 20)]
+        lines: usize,
+    },
     /// Clean up dead processes and old logs
     Prune,
+    /// List or remove launch templates (saved from the TUI launch form)
+    Templates {
+        #[command(subcommand)]
+        cmd: TemplateCommands,
+    },
+    /// List, restore or empty deleted logs
+    Trash {
+        #[command(subcommand)]
+        cmd: TrashCommands,
+    },
+    /// List profiles and where their files are kept
+    Profiles,
+    /// Inspect the configuration file
+    Config {
+        #[command(subcommand)]
+        cmd: ConfigCommands,
+    },
+    /// Upgrade the state and schedule files to the current schema version
+    Migrate {
+        /// Only report what would change
+        #[arg(long)]
+        check: bool,
+    },
+    /// Validate the state and schedule files (and repair them with --repair)
+    Doctor {
+        /// Restore corrupt files from backup and clean up stale entries
+        #[arg(long)]
+        repair: bool,
+    },
     /// Revive processes that have crashed
     Revive,
     /// Scheduler commands
@@ -85,9 +186,173 @@ enum Commands {
          #[command(subcommand)]
          cmd: ScheduleCommands,
     },
+    /// Manage global event hooks (applied to every process)
+    Hooks {
+        #[command(subcommand)]
+        cmd: HookCommands,
+    },
+    /// Control the scheduler daemon and start it at boot
+    Daemon {
+        #[command(subcommand)]
+        cmd: DaemonCommands,
+    },
     /// Internal scheduler daemon (hidden)
     #[command(hide = true)]
     InternalScheduler,
+    /// Internal supervisor that starts a managed process and records its exit status (hidden)
+    #[command(hide = true)]
+    InternalSupervise {
+        spec: String,
+    },
+}
+
+#[derive(clap::Args)]
+struct LimitArgs {
+    /// Hard cap on the address space (e.g. 512M, 2G)
+    #[arg(long, value_parser = parse_size_clap)]
+    max_memory: Option<u64>,
+
+    /// Hard cap on consumed CPU time (e.g. 30m, 1h)
+    #[arg(long, value_parser = parse_duration_clap)]
+    max_cpu_time: Option<u64>,
+
+    /// Maximum number of open file descriptors
+    #[arg(long)]
+    nofile: Option<u64>,
+
+    /// Watchdog: act when the process tree's RSS exceeds this (e.g. 1G)
+    #[arg(long, value_parser = parse_size_clap)]
+    watch_rss: Option<u64>,
+
+    /// Watchdog: act when the process tree's CPU% stays above this
+    #[arg(long)]
+    watch_cpu: Option<f32>,
+
+    /// Watchdog: how long --watch-cpu must be exceeded (default: 30s)
+    #[arg(long, value_parser = parse_duration_clap)]
+    watch_cpu_for: Option<u64>,
+
+    /// Watchdog: what to do when a threshold is hit
+    #[arg(long, value_enum, default_value_t = WatchAction::Kill)]
+    watch_action: WatchAction,
+}
+
+impl LimitArgs {
+    fn to_limits(&self) -> ResourceLimits {
+        ResourceLimits {
+            max_memory: self.max_memory,
+            max_cpu_time: self.max_cpu_time,
+            nofile: self.nofile,
+            watch_rss: self.watch_rss,
+            watch_cpu: self.watch_cpu,
+            watch_cpu_secs: self.watch_cpu_for,
+            watch_action: self.watch_action,
+        }
+    }
+}
+
+#[derive(clap::Args)]
+struct HookArgs {
+    /// Command to run when the process exits
+    #[arg(long)]
+    on_exit: Option<String>,
+
+    /// Command to run when the process exits with a non-zero code
+    #[arg(long)]
+    on_crash: Option<String>,
+
+    /// Command to run when the process is restarted or revived
+    #[arg(long)]
+    on_restart: Option<String>,
+
+    /// Command to run when the process is killed for exceeding --timeout
+    #[arg(long)]
+    on_timeout: Option<String>,
+
+    /// Command to run when the watchdog acts on the process
+    #[arg(long)]
+    on_unhealthy: Option<String>,
+
+    /// http:// URL to POST every event to as JSON
+    #[arg(long)]
+    webhook: Option<String>,
+}
+
+impl HookArgs {
+    fn to_hooks(&self) -> hooks::Hooks {
+        hooks::Hooks {
+            on_exit: self.on_exit.clone(),
+            on_crash: self.on_crash.clone(),
+            on_restart: self.on_restart.clone(),
+            on_timeout: self.on_timeout.clone(),
+            on_unhealthy: self.on_unhealthy.clone(),
+            webhook: self.webhook.clone(),
+        }
+    }
+}
+
+#[derive(Subcommand)]
+enum DaemonCommands {
+    /// Start the daemon if it isn't running
+    Start,
+    /// Stop the daemon (managed processes keep running unless scheduler.on_shutdown = "stop")
+    Stop,
+    /// Stop the daemon and start a new one
+    Restart,
+    /// Make the running daemon re-read the config and the schedule (SIGHUP)
+    Reload,
+    /// Install a systemd user service (or an @reboot crontab entry) that starts the daemon at boot
+    Install {
+        /// Use crontab even if systemd is available
+        #[arg(long)]
+        cron: bool,
+    },
+    /// Remove the systemd service and crontab entry
+    Uninstall,
+    /// Show how the daemon is installed and whether it is running
+    Status,
+}
+
+#[derive(Subcommand)]
+enum TemplateCommands {
+    /// List the templates
+    List,
+    /// Delete a template
+    Remove { name: String },
+}
+
+#[derive(Subcommand)]
+enum TrashCommands {
+    /// List deleted logs, newest first
+    List,
+    /// Move a deleted log back into the log directory
+    Restore {
+        /// Trash entry or original log file name (the newest copy is restored)
+        name: String,
+    },
+    /// Delete everything in the trash now
+    Empty,
+}
+
+#[derive(Subcommand)]
+enum ConfigCommands {
+    /// Show the effective configuration, including defaults
+    Show,
+}
+
+#[derive(Subcommand)]
+enum HookCommands {
+    /// Show the global hooks
+    Show,
+    /// Set a global hook (on_exit, on_crash, on_restart, on_timeout, on_unhealthy or webhook)
+    Set {
+        key: String,
+        value: String,
+    },
+    /// Remove a global hook
+    Unset {
+        key: String,
+    },
 }
 
 #[derive(Subcommand)]
@@ -113,82 +378,125 @@ enum ScheduleCommands {
     Stop {
         name: String,
     },
+    /// Enable a stopped job again
+    Start {
+        name: String,
+    },
+    /// Run a job now, even if it is disabled or not due yet (skipped if it is already running)
+    Run {
+        name: String,
+    },
     /// Remove a scheduled job
     Remove {
         name: String,
     },
 }
 
+fn parse_profile_clap(s: &str) -> Result<String, String> {
+    config::validate_profile(s).map(|_| s.to_string())
+}
+
 fn parse_timeout_clap(s: &str) -> Result<f64, String> {
     parse_timeout(s).ok_or_else(|| format!("Invalid timeout format: {}", s))
 }
 
-fn get_scheduled_file(exe_dir: &std::path::Path) -> PathBuf {
-    env::var("KSAI_PROC_SCHEDULE_JSON")
-        .map(PathBuf::from)
-        .unwrap_or_else(|_| exe_dir.join("logs/scheduledscripts.json"))
+fn parse_env_clap(s: &str) -> Result<(String, String), String> {
+    parse_env(s).ok_or_else(|| format!("Invalid environment variable (expected KEY=VALUE): {}", s))
 }
 
-fn ensure_scheduler_running(exe_dir: &std::path::Path, state_file: &std::path::Path) {
-    // Check if scheduler is running by reaping first to get fresh status
-    let procs = reap_processes(state_file);
-    let scheduler_running = procs.iter().any(|(_, p)| p.display_name == "ksai_scheduler_daemon" && p.status == "running");
-    
-    // Debug
-    // println!("DEBUG: ensure_scheduler_running: scheduler_running={}, procs={}", scheduler_running, procs.len());
-
-    if !scheduler_running {
-        // Spawn it
-        let ksai_proc_exe = exe_dir.join("ksai_proc");
-        let log_dir = env::var("KSAI_PROC_LOG_DIR")
-            .map(PathBuf::from)
-            .unwrap_or_else(|_| exe_dir.join("logs"));
-        let log_file = log_dir.join("scheduler.log");
-        
-        let log_handle = std::fs::OpenOptions::new().create(true).append(true).open(&log_file).ok();
-
-        if let Some(file) = log_handle {
-             match std::process::Command::new(&ksai_proc_exe)
-                .arg("internal-scheduler")
-                .stdout(std::process::Stdio::from(file.try_clone().unwrap()))
-                .stderr(std::process::Stdio::from(file))
-                .spawn() {
-                    Ok(child) => {
-                         // Register it so we know it's running
-                         register_process(state_file, child.id(), "ksai_proc internal-scheduler", None, &log_file, "scheduler", &exe_dir.to_string_lossy(), "ksai_scheduler_daemon");
-                    },
-                    Err(e) => eprintln!("Failed to start scheduler daemon: {}", e),
-                }
+fn parse_size_clap(s: &str) -> Result<u64, String> {
+    parse_size(s).ok_or_else(|| format!("Invalid size format: {}", s))
+}
+
+fn parse_duration_clap(s: &str) -> Result<u64, String> {
+    scheduler::parse_frequency(s).ok_or_else(|| format!("Invalid duration format: {}", s))
+}
+
+fn ensure_scheduler_running(exe_dir: &std::path::Path, paths: &config::Paths) {
+    // Every command reaps first so the state it works with is fresh
+    reap_processes(&paths.state_file);
+
+    if daemon::running_pid(&paths.state_file).is_some() {
+        return;
+    }
+    match daemon::spawn(&exe_dir.join("ksai_proc"), paths) {
+        // Wait for the pidfile so the next command (or a racing one) sees the daemon
+        Ok(_) => {
+            daemon::wait_until_running(&paths.state_file, std::time::Duration::from_secs(2));
         }
+        Err(e) => eprintln!("Failed to start scheduler daemon: {}", e),
     }
 }
 
 fn main() {
+    let cli = Cli::parse();
+    let _hooks = hooks::FlushOnExit;
+
     let exe_dir = env::current_exe().unwrap().parent().unwrap().to_path_buf();
-    let state_file = env::var("KSAI_PROC_LOG_JSON")
-        .map(PathBuf::from)
-        .unwrap_or_else(|_| exe_dir.join("logs/runningscripts.json"));
-    let log_dir = env::var("KSAI_PROC_LOG_DIR")
-        .map(PathBuf::from)
-        .unwrap_or_else(|_| exe_dir.join("logs"));
-    let scheduled_file = get_scheduled_file(&exe_dir);
+    let cfg = config::settings();
+    let paths = match config::resolve_paths(cfg, cli.profile.as_deref(), &exe_dir) {
+        Ok(paths) => paths,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    };
+    if let Some(backend) = &cfg.state_backend {
+        set_backend(backend);
+    }
+    let state_file = paths.state_file.clone();
+    let scheduled_file = paths.scheduled_file.clone();
+    let log_dir = paths.log_dir.clone();
 
     fs::create_dir_all(&log_dir).ok();
+    for file in [&state_file, &scheduled_file] {
+        if let Some(dir) = file.parent() {
+            fs::create_dir_all(dir).ok();
+        }
+    }
 
-    let cli = Cli::parse();
+    // A supervisor forks before it touches the state, so it mustn't have a connection open yet
+    if !matches!(cli.command, Some(Commands::Profiles | Commands::Config { .. } | Commands::InternalSupervise { .. }))
+        && let Err(e) = open_store(&state_file)
+    {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
 
-    if let Some(cmd) = &cli.command {
-        match cmd {
-            Commands::InternalScheduler => {}
-            _ => ensure_scheduler_running(&exe_dir, &state_file),
-        }
+    // Every command that uses the state needs the daemon, including a bare launch and the TUI.
+    // Doctor and migrate must see the files as they are, before anything rewrites them.
+    if !matches!(
+        cli.command,
+        Some(Commands::InternalScheduler | Commands::InternalSupervise { .. } | Commands::Doctor { .. } | Commands::Migrate { .. } | Commands::Profiles | Commands::Config { .. } | Commands::Daemon { .. })
+    ) {
+        ensure_scheduler_running(&exe_dir, &paths);
     }
 
     match cli.command {
-        Some(Commands::Run { script_args, no_tui: _, name, timeout }) => {
+        Some(Commands::Run { script_args, no_tui: _, name, timeout, restart, tags, env, template, limits, hooks }) => {
+            // Options given on the command line override the template's
+            let spec = match &template {
+                Some(template) => match launch::read_templates(&state_file).remove(template) {
+                    Some(spec) => spec,
+                    None => {
+                        eprintln!("Error: No template named '{}'. See `ksai_proc templates list`.", template);
+                        std::process::exit(1);
+                    }
+                },
+                None => LaunchSpec::default(),
+            };
+            let script_args = if script_args.is_empty() { spec.command } else { script_args };
             if !script_args.is_empty() {
-                let final_timeout = timeout.or(cli.timeout);
-                if let Err(e) = launch_process_with_name(&exe_dir, &state_file, &log_dir, &script_args, final_timeout, name, None) {
+                let final_timeout = timeout.or(cli.timeout).or(spec.timeout_sec);
+                let options = ProcessOptions {
+                    limits: limits.to_limits(),
+                    hooks: hooks.to_hooks(),
+                    restart_policy: restart.or(spec.restart_policy).unwrap_or(cfg.process.restart_policy),
+                    tags,
+                    env: spec.env.into_iter().chain(env).collect(),
+                };
+                let working_dir = Some(spec.working_dir).filter(|d| !d.is_empty());
+                if let Err(e) = launch_process_with_name(&exe_dir, &state_file, &log_dir, &script_args, final_timeout, name.or(spec.name), working_dir, options) {
                     eprintln!("Error: {}", e);
                     std::process::exit(1);
                 }
@@ -268,7 +576,8 @@ fn main() {
         }
         Some(Commands::Stop { pid, name }) => {
             let mut message = String::new();
-            
+            let mut stopping = Vec::new();
+
             update_state(&state_file, |state| {
                 let target_pid = if let Some(n) = &name {
                     let found = state.iter().find(|(_, p)| p.display_name == *n).map(|(pid, _)| pid.clone());
@@ -284,18 +593,10 @@ fn main() {
                 };
 
                 if let Some(pid_str) = target_pid {
-                    if let Some(proc) = state.get_mut(&pid_str) {
+                    if let Some(proc) = state.get(&pid_str) {
                          if proc.status == "running" {
-                             let pid_val: i32 = pid_str.parse().unwrap_or(0);
-                             unsafe { libc::kill(-pid_val, libc::SIGKILL); }
-                             // Sleep inside lock to ensure status update reflects reality (process fully dead)
-                             // before releasing lock? Or just mark it 'killed' immediately?
-                             // 200ms is a bit long for a lock. 
-                             // But if we don't sleep, `waitpid` might not have reaped it yet? 
-                             // Actually `kill` returns immediately. 
-                             // If we mark it "killed", the scheduler will see "killed" and ignore it (correct).
-                             std::thread::sleep(std::time::Duration::from_millis(200));
-                             proc.status = "killed (manual)".to_string();
+                             // Marked "stopping" so the scheduler ignores it while we stop it outside the lock
+                             stopping = mark_stopping(state, std::slice::from_ref(&pid_str));
                              message = format!("Process {} stopped.", pid_str);
                          } else {
                              message = format!("Process {} is not running (status: {}).", pid_str, proc.status);
@@ -307,38 +608,38 @@ fn main() {
                      message = "Error: You must specify either a PID or a valid --name.".to_string();
                 }
             });
+            stop_all(&stopping);
+            finish_stopping(&state_file, &stopping, "killed (manual)", "manual");
             println!("{}", message);
         }
         Some(Commands::Remove { pid }) => {
-            let mut message = String::new();
-             update_state(&state_file, |state| {
-                if let Some(proc) = state.remove(&pid) {
-                    if proc.status == "running" {
-                        let pid_val: i32 = pid.parse().unwrap_or(0);
-                        unsafe { libc::kill(-pid_val, libc::SIGKILL); }
-                        std::thread::sleep(std::time::Duration::from_millis(200));
-                    }
-                    fs::remove_file(&proc.log_file).ok();
-                    message = format!("Process {} removed and logs deleted.", pid);
+            let mut removed = None;
+            update_state(&state_file, |state| {
+                removed = state.remove(&pid);
+            });
+            // Stopped once the entry is gone, so the lock isn't held for the stop timeout
+            let message = if let Some(proc) = removed {
+                if proc.status == "running" {
+                    stop_group(&pid, &proc);
+                }
+                trash::discard(&log_dir, Path::new(&proc.log_file)).ok();
+                events::record(&state_file, "removed", &pid, &proc.display_name, None);
+                if cfg.trash_retention_secs() > 0 {
+                    format!("Process {} removed and logs moved to the trash.", pid)
                 } else {
-                    message = format!("Process {} not found.", pid);
+                    format!("Process {} removed and logs deleted.", pid)
                 }
-             });
-             println!("{}", message);
+            } else {
+                format!("Process {} not found.", pid)
+            };
+            println!("{}", message);
         }
         Some(Commands::Restart { pid }) => {
             let mut proc_to_restart: Option<ProcessInfo> = None;
             let mut message = String::new();
 
              update_state(&state_file, |state| {
-                if let Some(proc) = state.get(&pid).cloned() {
-                    let old_pid: i32 = pid.parse().unwrap_or(0);
-                    if proc.status == "running" {
-                        unsafe { libc::kill(-old_pid, libc::SIGKILL); }
-                        std::thread::sleep(std::time::Duration::from_millis(200));
-                    }
-                    
-                    state.remove(&pid);
+                if let Some(proc) = state.remove(&pid) {
                     proc_to_restart = Some(proc);
                 } else {
                     message = format!("Process {} not found.", pid);
@@ -346,26 +647,13 @@ fn main() {
              });
 
             if let Some(proc) = proc_to_restart {
-                let parts: Vec<&str> = proc.cmd_str.split_whitespace().collect();
-                if !parts.is_empty() {
-                    let log_handle = std::fs::OpenOptions::new().create(true).append(true).open(&proc.log_file).unwrap();
-                    if let Ok(child) = unsafe {
-                        use std::os::unix::process::CommandExt;
-                        std::process::Command::new(parts[0])
-                            .args(&parts[1..])
-                            .current_dir(&proc.working_dir)
-                            .stdout(std::process::Stdio::from(log_handle.try_clone().unwrap()))
-                            .stderr(std::process::Stdio::from(log_handle))
-                            .pre_exec(|| {
-                                libc::setsid();
-                                Ok(())
-                            })
-                            .spawn()
-                    } {
-                        let pid_val: u32 = child.id();
-                        register_process(&state_file, pid_val, &proc.cmd_str, proc.timeout_sec, std::path::Path::new(&proc.log_file), &proc.script_name, &proc.working_dir, &proc.display_name);
-                        println!("Process restarted with new PID {}.", pid_val);
-                    }
+                if proc.status == "running" {
+                    stop_group(&pid, &proc);
+                    events::record(&state_file, "killed", &pid, &proc.display_name, Some("restart".to_string()));
+                }
+                match respawn_process(&state_file, &proc) {
+                    Ok(pid_val) => println!("Process restarted with new PID {}.", pid_val),
+                    Err(e) => eprintln!("Error: {}", e),
                 }
             } else {
                  if !message.is_empty() {
@@ -374,8 +662,9 @@ fn main() {
             }
         }
         Some(Commands::Logs { pid, lines, follow }) => {
-            let state = read_state(&state_file);
-            if let Some(proc) = state.get(&pid) {
+            let store = store(&state_file);
+            let found = store.processes().remove(&pid).or_else(|| store.find_by_name(&pid).map(|(_, p)| p));
+            if let Some(proc) = found {
                 if follow {
                     let mut cmd = std::process::Command::new("tail");
                     cmd.arg("-f").arg("-n").arg(lines.to_string()).arg(&proc.log_file);
@@ -389,6 +678,67 @@ fn main() {
                 println!("Process {} not found.", pid);
             }
         }
+        Some(Commands::Stats { target, since, lines }) => {
+            let Some(since_secs) = scheduler::parse_frequency(&since) else {
+                println!("Error: Invalid duration '{}'. Use e.g. 10m, 1h, 1d.", since);
+                return;
+            };
+
+            // Accept either a PID or a display name; history is stored per name
+            let state = read_state(&state_file);
+            let name = state.get(&target)
+                .map(|p| p.display_name.clone())
+                .unwrap_or(target);
+
+            let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+            let samples = stats::read_samples(&stats::history_file(&log_dir, &name), now.saturating_sub(since_secs));
+            if samples.is_empty() {
+                println!("No statistics recorded for '{}' in the last {}.", name, since);
+                return;
+            }
+
+            let to_mb = |b: u64| b as f64 / 1024.0 / 1024.0;
+            let avg_cpu = samples.iter().map(|s| s.cpu as f64).sum::<f64>() / samples.len() as f64;
+            let max_cpu = samples.iter().map(|s| s.cpu).fold(0.0, f32::max);
+            let avg_mem = samples.iter().map(|s| s.mem).sum::<u64>() / samples.len() as u64;
+            let max_mem = samples.iter().map(|s| s.mem).max().unwrap_or(0);
+
+            println!("Stats for '{}' over the last {} ({} samples)", name, since, samples.len());
+            println!("CPU: avg {:.1}%  max {:.1}%", avg_cpu, max_cpu);
+            println!("RAM: avg {:.1}M  max {:.1}M", to_mb(avg_mem), to_mb(max_mem));
+            println!();
+            println!("{:<20} {:>8} {:>10}", "Time", "CPU%", "RAM");
+            println!("{}", "-".repeat(40));
+            for sample in samples.iter().skip(samples.len().saturating_sub(lines)) {
+                let time = chrono::DateTime::from_timestamp(sample.ts as i64, 0)
+                    .map(|d| d.format("%Y-%m-%d %H:%M:%S").to_string())
+                    .unwrap_or_else(|| "Unknown".to_string());
+                println!("{:<20} {:>8.1} {:>9.1}M", time, sample.cpu, to_mb(sample.mem));
+            }
+        }
+        Some(Commands::Runs { name, lines }) => {
+            let runs = store(&state_file).runs(name.as_deref(), lines);
+            if runs.is_empty() {
+                println!("No runs recorded.");
+                return;
+            }
+            let fmt_time = |ts: f64| chrono::DateTime::from_timestamp(ts as i64, 0)
+                .map(|d| d.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M:%S").to_string())
+                .unwrap_or_else(|| "Unknown".to_string());
+            println!("{:<8} {:<20} {:<20} {:<5} {:<20} {:<20}", "PID", "NAME", "STATUS", "EXIT", "STARTED", "ENDED");
+            println!("{}", "-".repeat(98));
+            for run in runs {
+                println!(
+                    "{:<8} {:<20} {:<20} {:<5} {:<20} {:<20}",
+                    run.pid,
+                    run.name,
+                    run.status,
+                    run.exit_code.map(|c| c.to_string()).unwrap_or_else(|| "-".to_string()),
+                    fmt_time(run.start_time),
+                    run.end_time.map(fmt_time).unwrap_or_else(|| "-".to_string()),
+                );
+            }
+        }
         Some(Commands::Prune) => {
              let mut removed_count = 0;
             update_state(&state_file, |state| {
@@ -398,39 +748,187 @@ fn main() {
             });
             println!("Pruned {} non-running processes.", removed_count);
         }
+        Some(Commands::Templates { cmd }) => match cmd {
+            TemplateCommands::List => {
+                let templates = launch::read_templates(&state_file);
+                if templates.is_empty() {
+                    println!("No templates. Save one from the TUI launch form with Ctrl-S.");
+                    return;
+                }
+                for (name, spec) in templates {
+                    println!("{}", name);
+                    println!("  command:   {}", launch::join_args(&spec.command));
+                    if let Some(process_name) = &spec.name {
+                        println!("  name:      {}", process_name);
+                    }
+                    if !spec.working_dir.is_empty() {
+                        println!("  directory: {}", spec.working_dir);
+                    }
+                    if let Some(timeout) = spec.timeout_sec {
+                        println!("  timeout:   {}s", timeout);
+                    }
+                    if let Some(policy) = spec.restart_policy {
+                        println!("  restart:   {}", launch::policy_name(policy));
+                    }
+                    for (key, value) in &spec.env {
+                        println!("  env:       {}={}", key, value);
+                    }
+                }
+            }
+            TemplateCommands::Remove { name } => {
+                if launch::remove_template(&state_file, &name) {
+                    println!("Template '{}' removed.", name);
+                } else {
+                    println!("Template '{}' not found.", name);
+                }
+            }
+        },
+        Some(Commands::Trash { cmd }) => match cmd {
+            TrashCommands::List => {
+                let entries = trash::list(&log_dir);
+                if entries.is_empty() {
+                    println!("The trash is empty.");
+                    return;
+                }
+                println!("{:<20} {:>10}  NAME", "DELETED", "SIZE");
+                println!("{}", "-".repeat(60));
+                for entry in entries {
+                    let time = chrono::DateTime::from_timestamp(entry.deleted_at as i64, 0)
+                        .map(|d| d.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M:%S").to_string())
+                        .unwrap_or_else(|| "Unknown".to_string());
+                    println!("{:<20} {:>9.1}K  {}", time, entry.size as f64 / 1024.0, entry.name);
+                }
+                println!("\nDeleted logs are kept for {}.", cfg.logs.trash_retention);
+            }
+            TrashCommands::Restore { name } => match trash::restore(&log_dir, &name) {
+                Ok(path) => println!("Restored {}", path.display()),
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+            },
+            TrashCommands::Empty => println!("Deleted {} logs from the trash.", trash::purge(&log_dir, None)),
+        },
+        Some(Commands::Profiles) => {
+            println!("Config: {}", config::config_file().display());
+            for name in config::known_profiles(cfg) {
+                let Ok(p) = config::resolve_paths(cfg, Some(&name), &exe_dir) else { continue };
+                let marker = if name == paths.profile { "*" } else { " " };
+                println!("{} {:<15} {}", marker, name, p.state_file.parent().unwrap_or(&p.state_file).display());
+            }
+        }
+        Some(Commands::Config { cmd: ConfigCommands::Show }) => {
+            let path = config::config_file();
+            println!("# Config file: {}{}", path.display(), if path.exists() { "" } else { " (not found, using defaults)" });
+            println!("# Profile: {}", paths.profile);
+            println!("# State file: {}", state_file.display());
+            println!("# Schedule file: {}", scheduled_file.display());
+            println!("# Log dir: {}", log_dir.display());
+            println!("# State backend: {}", backend_name());
+            match toml::to_string_pretty(cfg) {
+                Ok(s) => print!("\n{}", s),
+                Err(e) => eprintln!("Error: {}", e),
+            }
+            // The invalid file was already reported and the defaults shown; fail so scripts notice
+            if config::read_file(&path).is_err() {
+                std::process::exit(1);
+            }
+        }
+        Some(Commands::Migrate { check }) => {
+            println!("{} (current schema version {}):", if check { "Checking migrations" } else { "Migrating" }, schema::SCHEMA_VERSION);
+            let results = [
+                migrate_file::<std::collections::HashMap<String, ProcessInfo>>(&state_file, check),
+                migrate_file::<Vec<ScheduledJob>>(&scheduled_file, check),
+            ];
+            if backend_name() == "sqlite" {
+                let db = sqlite_store::db_file(&state_file);
+                match sqlite_store::SqliteStore::open(&db).and_then(|s| s.schema_version()) {
+                    Ok(v) => println!("  {}: schema version {}", db.display(), v),
+                    Err(e) => println!("  {}: {}", db.display(), e),
+                }
+            }
+
+            let mut failed = false;
+            let mut pending = 0;
+            for result in results {
+                match result {
+                    Ok(n) => pending += n,
+                    Err(e) => {
+                        println!("  Error: {}", e);
+                        failed = true;
+                    }
+                }
+            }
+            if check && pending > 0 {
+                println!("Run `ksai_proc migrate` to apply {} change(s).", pending);
+            }
+            if failed {
+                std::process::exit(1);
+            }
+        }
+        Some(Commands::Doctor { repair }) => {
+            println!("Checking state files:");
+            let mut problems = check_file::<std::collections::HashMap<String, ProcessInfo>>(&state_file, repair);
+            problems += check_file::<Vec<ScheduledJob>>(&scheduled_file, repair);
+
+            if backend_name() == "sqlite" {
+                let db = sqlite_store::db_file(&state_file);
+                match sqlite_store::SqliteStore::open(&db).map_err(|e| e.to_string()).and_then(|s| s.integrity_check()) {
+                    Ok(_) => println!("  [ok]      {}", db.display()),
+                    Err(e) => {
+                        println!("  [corrupt] {}: {}", db.display(), e);
+                        problems += 1;
+                    }
+                }
+            }
+
+            println!("Checking tracked processes:");
+            // Read without the auto-recovery so a plain check never modifies anything
+            let state: std::collections::HashMap<String, ProcessInfo> = if backend_name() == "sqlite" {
+                read_state(&state_file)
+            } else {
+                load_file(&state_file).unwrap_or_default()
+            };
+            let stale: Vec<&String> = state
+                .iter()
+                .filter(|(pid, p)| p.status == "running" && pid.parse::<u32>().is_ok_and(|pid| unsafe { libc::kill(pid as i32, 0) } != 0 || !is_same_process(pid, p)))
+                .map(|(pid, _)| pid)
+                .collect();
+            for pid in &stale {
+                println!("  [{}] PID {} ({}) is marked running but is gone", if repair { "repair" } else { "stale" }, pid, state[*pid].display_name);
+            }
+            if repair {
+                reap_processes(&state_file);
+            } else {
+                problems += stale.len();
+            }
+            for (pid, p) in &state {
+                if !std::path::Path::new(&p.log_file).exists() {
+                    println!("  [warn]    PID {} ({}) has no log file at {}", pid, p.display_name, p.log_file);
+                }
+            }
+
+            if problems > 0 {
+                println!("{} problem(s) found. Run `ksai_proc doctor --repair` to fix them.", problems);
+                std::process::exit(1);
+            }
+            println!("No problems found.");
+        }
         Some(Commands::Revive) => {
             println!("Reviving crashed processes...");
             revive_dead_processes(&exe_dir, &state_file, &log_dir);
             println!("Revival check complete.");
         }
         Some(Commands::Schedule { cmd }) => {
-            ensure_scheduler_running(&exe_dir, &state_file);
             match cmd {
                 ScheduleCommands::Add { name, every, start_at, command } => {
-                     // Check if name exists
-                     let mut jobs = read_scheduled_jobs(&scheduled_file);
-                     if jobs.iter().any(|j| j.name == name) {
-                         println!("Error: Scheduled job with name '{}' already exists.", name);
-                         return;
-                     }
                      if command.is_empty() {
                          println!("Error: No command provided.");
                          return;
                      }
-
-                     use chrono::{NaiveDateTime, Local, TimeZone};
-                     let start_timestamp = if start_at == "now" {
-                         SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()
-                     } else {
-                         // Try parsing "YYYY-MM-DD HH:MM:SS" (Local)
-                         if let Ok(dt) = NaiveDateTime::parse_from_str(&start_at, "%Y-%m-%d %H:%M:%S") {
-                             Local.from_local_datetime(&dt).unwrap().timestamp() as u64
-                         } else if let Ok(dt) = NaiveDateTime::parse_from_str(format!("{} 00:00:00", start_at).as_str(), "%Y-%m-%d %H:%M:%S") {
-                             Local.from_local_datetime(&dt).unwrap().timestamp() as u64
-                         } else {
-                             println!("Error: Invalid date format. Use 'YYYY-MM-DD HH:MM:SS' or 'YYYY-MM-DD' or 'now'");
-                             return;
-                         }
+                     let Some(start_timestamp) = scheduler::parse_start_at(&start_at) else {
+                         println!("Error: Invalid date format. Use 'YYYY-MM-DD HH:MM:SS' or 'YYYY-MM-DD' or 'now'");
+                         return;
                      };
 
                      let job = ScheduledJob {
@@ -442,11 +940,13 @@ fn main() {
                          working_dir: env::current_dir().unwrap().to_string_lossy().to_string(),
                          last_run: None,
                          enabled: true,
+                         run_now: false,
                      };
-                     
-                     jobs.push(job);
-                     write_scheduled_jobs(&scheduled_file, &jobs);
-                     println!("Scheduled job '{}' added.", name);
+
+                     match scheduler::add_job(&scheduled_file, job) {
+                         Ok(_) => println!("Scheduled job '{}' added.", name),
+                         Err(e) => println!("Error: {}", e),
+                     }
                 }
                 ScheduleCommands::List => {
                     let jobs = read_scheduled_jobs(&scheduled_file);
@@ -462,21 +962,28 @@ fn main() {
                     }
                 }
                 ScheduleCommands::Stop { name } => {
-                    let mut jobs = read_scheduled_jobs(&scheduled_file);
-                    if let Some(job) = jobs.iter_mut().find(|j| j.name == name) {
-                        job.enabled = false;
-                         write_scheduled_jobs(&scheduled_file, &jobs);
+                    if scheduler::modify_job(&scheduled_file, &name, |job| job.enabled = false) {
                         println!("Scheduled job '{}' disabled.", name);
                     } else {
                         println!("Scheduled job '{}' not found.", name);
                     }
                 }
+                ScheduleCommands::Start { name } => {
+                    if scheduler::modify_job(&scheduled_file, &name, |job| job.enabled = true) {
+                        println!("Scheduled job '{}' enabled.", name);
+                    } else {
+                        println!("Scheduled job '{}' not found.", name);
+                    }
+                }
+                ScheduleCommands::Run { name } => {
+                    if scheduler::modify_job(&scheduled_file, &name, |job| job.run_now = true) {
+                        println!("Scheduled job '{}' will run now.", name);
+                    } else {
+                        println!("Scheduled job '{}' not found.", name);
+                    }
+                }
                 ScheduleCommands::Remove { name } => {
-                    let mut jobs = read_scheduled_jobs(&scheduled_file);
-                    let len_before = jobs.len();
-                    jobs.retain(|j| j.name != name);
-                    if jobs.len() < len_before {
-                        write_scheduled_jobs(&scheduled_file, &jobs);
+                    if scheduler::remove_job(&scheduled_file, &name) {
                          println!("Scheduled job '{}' removed.", name);
                     } else {
                          println!("Scheduled job '{}' not found.", name);
@@ -484,32 +991,141 @@ fn main() {
                 }
             }
         }
+        Some(Commands::Hooks { cmd }) => {
+            let mut global = hooks::read_global_hooks(&state_file);
+            match cmd {
+                HookCommands::Show => {
+                    println!("Global hooks ({}):", hooks::global_hooks_file(&state_file).display());
+                    if global.is_empty() {
+                        println!("  (none)");
+                    }
+                    for key in ["on_exit", "on_crash", "on_restart", "on_timeout", "on_unhealthy", "webhook"] {
+                        if let Some(Some(value)) = global.slot(key) {
+                            println!("  {:<13} {}", key, value);
+                        }
+                    }
+                }
+                HookCommands::Set { key, value } => {
+                    let Some(slot) = global.slot(&key) else {
+                        println!("Error: Unknown hook '{}'.", key);
+                        return;
+                    };
+                    *slot = Some(value);
+                    match hooks::write_global_hooks(&state_file, &global) {
+                        Ok(_) => println!("Global hook '{}' set.", key),
+                        Err(e) => eprintln!("Error: {}", e),
+                    }
+                }
+                HookCommands::Unset { key } => {
+                    let Some(slot) = global.slot(&key) else {
+                        println!("Error: Unknown hook '{}'.", key);
+                        return;
+                    };
+                    *slot = None;
+                    match hooks::write_global_hooks(&state_file, &global) {
+                        Ok(_) => println!("Global hook '{}' removed.", key),
+                        Err(e) => eprintln!("Error: {}", e),
+                    }
+                }
+            }
+        }
+        Some(Commands::Events { name, follow, lines }) => {
+            events::follow(&state_file, name.as_deref(), lines, follow);
+        }
+        Some(Commands::Daemon { cmd }) => match cmd {
+            DaemonCommands::Start | DaemonCommands::Restart => {
+                if matches!(cmd, DaemonCommands::Restart) {
+                    match daemon::stop(&state_file, daemon::stop_timeout()) {
+                        Some(pid) => println!("Scheduler daemon (PID {}) stopped.", pid),
+                        None => println!("Scheduler daemon was not running."),
+                    }
+                } else if let Some(pid) = daemon::running_pid(&state_file) {
+                    println!("Scheduler daemon is already running (PID {}).", pid);
+                    return;
+                }
+                reap_processes(&state_file);
+                if let Err(e) = daemon::spawn(&exe_dir.join("ksai_proc"), &paths) {
+                    eprintln!("Error: Failed to start scheduler daemon: {}", e);
+                    std::process::exit(1);
+                }
+                match daemon::wait_until_running(&state_file, std::time::Duration::from_secs(5)) {
+                    Some(pid) => println!("Scheduler daemon started (PID {}).", pid),
+                    None => {
+                        eprintln!("Error: Scheduler daemon did not start; see {}", log_dir.join("scheduler.log").display());
+                        std::process::exit(1);
+                    }
+                }
+            }
+            DaemonCommands::Stop => match daemon::stop(&state_file, daemon::stop_timeout()) {
+                Some(pid) => println!("Scheduler daemon (PID {}) stopped.", pid),
+                None => println!("Scheduler daemon is not running."),
+            },
+            DaemonCommands::Reload => match daemon::reload(&state_file) {
+                Some(pid) => println!("Asked the scheduler daemon (PID {}) to reload.", pid),
+                None => println!("Scheduler daemon is not running."),
+            },
+            DaemonCommands::Install { cron } => {
+                let exe = env::current_exe().unwrap();
+                match service::install(&exe, &paths, cron) {
+                    Ok("systemd") => println!("Installed {}; the daemon will start at login/boot.", service::unit_file(&paths.profile).display()),
+                    Ok(_) => println!("Installed an @reboot crontab entry; the daemon will start at boot."),
+                    Err(e) => {
+                        eprintln!("Error: {}", e);
+                        std::process::exit(1);
+                    }
+                }
+            }
+            DaemonCommands::Uninstall => {
+                let removed = service::uninstall(&paths);
+                if removed.is_empty() {
+                    println!("The daemon is not installed for profile '{}'.", paths.profile);
+                }
+                for what in removed {
+                    println!("Removed {}.", what);
+                }
+            }
+            DaemonCommands::Status => {
+                println!("Profile:  {}", paths.profile);
+                service::print_status(&paths);
+                match daemon::running_pid(&state_file) {
+                    Some(pid) => println!("daemon:   running (PID {}, {})", pid, daemon::pid_file(&state_file).display()),
+                    None => println!("daemon:   not running"),
+                }
+            }
+        },
         Some(Commands::InternalScheduler) => {
+            events::set_actor(events::Actor::Daemon);
             scheduler::start_scheduler_daemon(&state_file, &scheduled_file, &log_dir);
         }
+        Some(Commands::InternalSupervise { spec }) => {
+            events::set_actor(events::Actor::Supervisor);
+            match serde_json::from_str(&spec) {
+                Ok(spec) => supervise(&spec),
+                Err(e) => println!("error: invalid supervisor spec: {}", e),
+            }
+        }
         None => {
             if !cli.script_args.is_empty() {
-                ensure_scheduler_running(&exe_dir, &state_file);
-                if let Err(e) = launch_process_with_name(&exe_dir, &state_file, &log_dir, &cli.script_args, cli.timeout, cli.name, None) {
+                let options = ProcessOptions { restart_policy: cfg.process.restart_policy, ..Default::default() };
+                if let Err(e) = launch_process_with_name(&exe_dir, &state_file, &log_dir, &cli.script_args, cli.timeout, cli.name, None, options) {
                     eprintln!("Error: {}", e);
                     std::process::exit(1);
                 }
                 println!("Process launched successfully.");
                 return;
             }
-            
-            ensure_scheduler_running(&exe_dir, &state_file);
 
+            events::set_actor(events::Actor::Tui);
             revive_dead_processes(&exe_dir, &state_file, &log_dir);
 
-            if let Err(e) = run_tui(&state_file, &log_dir) {
+            if let Err(e) = run_tui(&state_file, &scheduled_file, &log_dir) {
                 eprintln!("TUI error: {}", e);
             }
         }
     }
 }
 
-fn run_tui(state_file: &std::path::Path, log_dir: &std::path::Path) -> std::io::Result<()> {
+fn run_tui(state_file: &std::path::Path, scheduled_file: &std::path::Path, log_dir: &std::path::Path) -> std::io::Result<()> {
     enable_raw_mode()?;
     let mut stdout = std::io::stdout();
     execute!(stdout, EnterAlternateScreen)?;
@@ -517,7 +1133,7 @@ fn run_tui(state_file: &std::path::Path, log_dir: &std::path::Path) -> std::io::
     let mut terminal = Terminal::new(backend)?;
 
     let mut app = App::new(reap_processes(state_file));
-    let result = run_app(&mut terminal, &mut app, state_file, log_dir);
+    let result = run_app(&mut terminal, &mut app, state_file, scheduled_file, log_dir);
 
     disable_raw_mode()?;
     execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
@@ -1,5 +1,5 @@
 use std::collections::HashMap;
-use sysinfo::{Pid, ProcessRefreshKind, RefreshKind, System, ProcessesToUpdate};
+use sysinfo::{Pid, Process, ProcessRefreshKind, RefreshKind, System, ProcessesToUpdate};
 
 #[derive(Clone, Debug, Default)]
 pub struct ProcessStats {
@@ -11,11 +11,48 @@ pub struct ProcessStats {
     pub max_cpu: f32,
     pub max_mem: u64,
     pub thread_count: u64,
+    pub fd_count: u64,
+    pub process_count: u64,
+}
+
+impl ProcessStats {
+    fn add(&mut self, pid: Pid, proc: &Process) {
+        self.cpu_usage += proc.cpu_usage();
+        self.mem_usage += proc.memory();
+        self.virtual_mem += proc.virtual_memory();
+
+        let disk_usage = proc.disk_usage();
+        self.disk_read += disk_usage.total_read_bytes;
+        self.disk_written += disk_usage.total_written_bytes;
+
+        self.thread_count += thread_count(pid, proc);
+        self.fd_count += proc.open_files().unwrap_or(0) as u64;
+        self.process_count += 1;
+    }
+
+    // Replaces the live values while keeping the tracked maximums
+    fn update_from(&mut self, sample: ProcessStats) {
+        let (max_cpu, max_mem) = (self.max_cpu.max(sample.cpu_usage), self.max_mem.max(sample.mem_usage));
+        *self = ProcessStats { max_cpu, max_mem, ..sample };
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn thread_count(pid: Pid, _proc: &Process) -> u64 {
+    std::fs::read_dir(format!("/proc/{}/task", pid))
+        .map(|entries| entries.count() as u64)
+        .unwrap_or(1)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn thread_count(_pid: Pid, proc: &Process) -> u64 {
+    proc.tasks().map(|t| t.len() as u64).unwrap_or(1)
 }
 
 pub struct Monitor {
     system: System,
     stats: HashMap<u32, ProcessStats>,
+    tree_stats: HashMap<u32, ProcessStats>,
 }
 
 impl Monitor {
@@ -28,65 +65,73 @@ impl Monitor {
                     .with_memory(sysinfo::MemoryRefreshKind::everything()),
             ),
             stats: HashMap::new(),
+            tree_stats: HashMap::new(),
         }
     }
 
     pub fn update(&mut self, pids: &[u32]) {
-        // Prepare list of Pids to refresh
-        let sys_pids: Vec<Pid> = pids.iter().map(|&p| Pid::from_u32(p)).collect();
-        
-        // Use refresh_processes_specifics for efficiency if available, or just refresh_processes
-        // sysinfo 0.30+ supports this. Checking Cargo.toml, version is 0.37.2.
+        // Managed services fork workers, so the whole process table is needed to walk descendants.
+        // Threads are counted from /proc instead of being listed as separate processes.
         self.system.refresh_processes_specifics(
-            ProcessesToUpdate::Some(&sys_pids),
-            true, // maintain order? (deprecated in newer versions, or remove_dead) - checking docs:
-            // refresh_processes(processes_to_update: ProcessesToUpdate, remove_dead: bool) is likely usage
-             ProcessRefreshKind::everything()
+            ProcessesToUpdate::All,
+            true,
+            ProcessRefreshKind::nothing()
+                .with_cpu()
+                .with_memory()
+                .with_disk_usage()
+                .without_tasks(),
         );
-        // refresh_memory is needed for total memory info if we want usage % relative to total, 
-        // but process.memory() returns bytes.
-        // We might want to refresh global CPU usage too for context but strict requirement is per process.
-        
+        self.system.refresh_cpu_usage();
+        self.system.refresh_memory();
+
+        let mut children: HashMap<Pid, Vec<Pid>> = HashMap::new();
+        for (pid, proc) in self.system.processes() {
+            if let Some(parent) = proc.parent() {
+                children.entry(parent).or_default().push(*pid);
+            }
+        }
+
         for &pid in pids {
             let sys_pid = Pid::from_u32(pid);
-            if let Some(proc) = self.system.process(sys_pid) {
-                let entry = self.stats.entry(pid).or_default();
-                
-                entry.cpu_usage = proc.cpu_usage();
-                entry.mem_usage = proc.memory();
-                entry.virtual_mem = proc.virtual_memory();
-                
-                let disk_usage = proc.disk_usage();
-                entry.disk_read = disk_usage.total_read_bytes;
-                entry.disk_written = disk_usage.total_written_bytes;
-                
-                // Track max values
-                if entry.cpu_usage > entry.max_cpu {
-                    entry.max_cpu = entry.cpu_usage;
+            let Some(proc) = self.system.process(sys_pid) else {
+                continue;
+            };
+
+            let mut main = ProcessStats::default();
+            main.add(sys_pid, proc);
+
+            let mut tree = ProcessStats::default();
+            let mut stack = vec![sys_pid];
+            while let Some(current) = stack.pop() {
+                if let Some(p) = self.system.process(current) {
+                    tree.add(current, p);
                 }
-                if entry.mem_usage > entry.max_mem {
-                    entry.max_mem = entry.mem_usage;
+                if let Some(kids) = children.get(&current) {
+                    stack.extend(kids);
                 }
-                
-                // thread count is optional in some sysinfo versions, let's check
-                // proc.tasks? or proc.thread_kind()?
-                // for 0.37, strictly speaking, getting thread count might require iterating tasks if not exposed directly.
-                // But usually, `proc.tasks()` returns an iterator/hashmap if implicitly refreshed.
-                // Use a simple default if complex. 
-                // Wait, sysinfo `Process` struct usually doesn't show thread count directly 
-                // unless we iterate `tasks`. `proc.tasks` is available if `ProcessRefreshKind::with_tasks()` is used? 
-                // Default `everything()` includes tasks?
-                // Actually `proc.tasks()` was removed/changed in recent versions.
-                // Let's rely on standard `proc` methods. If thread count is not easy, we skip or find alternative.
-                // checking crate docs for 0.30+: It seems to be separate.
-                // Let's assume 0 threads for now to be safe or check if `proc.thread_kind` is useful.
-                // Actually, let's just use 1 if unknown.
-                entry.thread_count = 1; // Placeholder until verified
             }
+
+            self.stats.entry(pid).or_default().update_from(main);
+            self.tree_stats.entry(pid).or_default().update_from(tree);
         }
     }
 
     pub fn get_stats(&self, pid: u32) -> Option<&ProcessStats> {
         self.stats.get(&pid)
     }
+
+    // Across all cores, in percent
+    pub fn system_cpu(&self) -> f32 {
+        self.system.global_cpu_usage()
+    }
+
+    // Used and total RAM in bytes
+    pub fn system_memory(&self) -> (u64, u64) {
+        (self.system.used_memory(), self.system.total_memory())
+    }
+
+    // Aggregated over the process and all of its descendants
+    pub fn get_tree_stats(&self, pid: u32) -> Option<&ProcessStats> {
+        self.tree_stats.get(&pid)
+    }
 }
@@ -1,22 +1,62 @@
 use std::{env, fs::{self, OpenOptions}, io, os::unix::process::CommandExt, path::{Path, PathBuf}, process::{Command, Stdio}, time::{SystemTime, UNIX_EPOCH}};
-use crate::state::*;
+use serde::{Deserialize, Serialize};
+use crate::{config, events, hooks::{self, HookEvent}, state::*, types::{ProcessInfo, ProcessOptions, ResourceLimits}};
 
 pub fn parse_timeout(s: &str) -> Option<f64> {
     let s = s.trim();
-    if s.ends_with('s') {
-        s[..s.len() - 1].parse().ok()
+    if let Some(secs) = s.strip_suffix('s') {
+        secs.parse().ok()
     } else {
         s.parse().ok()
     }
 }
 
+// Sizes like 512M, 2G, 64K or plain bytes
+pub fn parse_size(s: &str) -> Option<u64> {
+    let s = s.trim();
+    let (num, mult) = match s.chars().last()?.to_ascii_uppercase() {
+        'K' => (&s[..s.len() - 1], 1024),
+        'M' => (&s[..s.len() - 1], 1024 * 1024),
+        'G' => (&s[..s.len() - 1], 1024 * 1024 * 1024),
+        _ => (s, 1),
+    };
+    num.parse::<u64>().ok()?.checked_mul(mult)
+}
+
+// KEY=VALUE, where the key is a valid variable name and the value may be empty
+pub fn parse_env(s: &str) -> Option<(String, String)> {
+    let (key, value) = s.split_once('=')?;
+    let valid = key.chars().next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    valid.then(|| (key.to_string(), value.to_string()))
+}
+
+// Signal names with or without the SIG prefix, or plain numbers
+pub fn parse_signal(s: &str) -> Option<i32> {
+    let s = s.trim().to_ascii_uppercase();
+    if let Ok(n) = s.parse::<i32>() {
+        return (1..=64).contains(&n).then_some(n);
+    }
+    match s.strip_prefix("SIG").unwrap_or(&s) {
+        "HUP" => Some(libc::SIGHUP),
+        "INT" => Some(libc::SIGINT),
+        "QUIT" => Some(libc::SIGQUIT),
+        "KILL" => Some(libc::SIGKILL),
+        "USR1" => Some(libc::SIGUSR1),
+        "USR2" => Some(libc::SIGUSR2),
+        "TERM" => Some(libc::SIGTERM),
+        _ => None,
+    }
+}
+
 fn find_script(script_dir: &Path, name: &str) -> Option<PathBuf> {
-    for ext in ["py", "sh", ""] {
-        let path = if ext.is_empty() {
-            script_dir.join("scripts").join(name)
-        } else {
-            script_dir.join("scripts").join(format!("{}.{}", name, ext))
-        };
+    let extensions = &config::settings().process.script_extensions;
+    let candidates = extensions
+        .iter()
+        .map(|ext| format!("{}.{}", name, ext.trim_start_matches('.')))
+        .chain(std::iter::once(name.to_string()));
+    for file_name in candidates {
+        let path = script_dir.join("scripts").join(file_name);
         if path.is_file() {
             return Some(path);
         }
@@ -25,7 +65,7 @@ fn find_script(script_dir: &Path, name: &str) -> Option<PathBuf> {
 }
 
 
-
+#[allow(clippy::too_many_arguments)]
 pub fn launch_process_with_name(
     script_dir: &Path,
     state_file: &Path,
@@ -34,10 +74,14 @@ pub fn launch_process_with_name(
     timeout_sec: Option<f64>,
     display_name: Option<String>,
     working_dir: Option<String>,
+    options: ProcessOptions,
 ) -> io::Result<()> {
     let script_name = &script_args[0];
     let args = &script_args[1..];
-    let cwd = working_dir.unwrap_or_else(|| env::current_dir().unwrap().to_string_lossy().to_string());
+    let cwd = match working_dir {
+        Some(dir) => dir,
+        None => env::current_dir()?.to_string_lossy().to_string(),
+    };
 
     let (cmd, cmd_args, _script_path) = if let Some(path) = find_script(script_dir, script_name) {
         if path.extension().and_then(|s| s.to_str()) == Some("py") {
@@ -58,6 +102,7 @@ pub fn launch_process_with_name(
     };
 
     let cmd_str = format!("{} {}", cmd, cmd_args.join(" "));
+    let argv: Vec<String> = std::iter::once(cmd.clone()).chain(cmd_args.iter().cloned()).collect();
 
     {
         use sysinfo::{Pid, System};
@@ -68,24 +113,23 @@ pub fn launch_process_with_name(
         let final_name = display_name.clone().unwrap_or_else(|| script_name.clone());
 
         for (pid_str, proc) in state {
-            if proc.status == "running" {
-                if let Ok(pid) = pid_str.parse::<u32>() {
-                    let sys_pid = Pid::from_u32(pid);
-                    let status = sys.process(sys_pid).map(|p| p.status());
-                    let is_alive = match status {
-                        Some(sysinfo::ProcessStatus::Zombie) => false,
-                        Some(_) => true,
-                        None => false,
-                    };
-                    if is_alive {
-                        // Check for duplicate command/cwd (existing check)
-                        if proc.cmd_str == cmd_str && proc.working_dir == cwd {
-                             return Err(io::Error::new(io::ErrorKind::Other, format!("Process '{}' is already running in {} (PID {})", cmd_str, cwd, pid)));
-                        }
-                        // Check for duplicate display name (NEW check)
-                        if proc.display_name == final_name {
-                             return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("A process with the name '{}' is already running (PID {})", final_name, pid)));
-                        }
+            if proc.status == "running"
+                && let Ok(pid) = pid_str.parse::<u32>() {
+                let sys_pid = Pid::from_u32(pid);
+                let status = sys.process(sys_pid).map(|p| p.status());
+                let is_alive = match status {
+                    Some(sysinfo::ProcessStatus::Zombie) => false,
+                    Some(_) => is_same_process(pid, &proc),
+                    None => false,
+                };
+                if is_alive {
+                    // Check for duplicate command/cwd (existing check)
+                    if proc.cmd_str == cmd_str && proc.working_dir == cwd {
+                         return Err(io::Error::other(format!("Process '{}' is already running in {} (PID {})", cmd_str, cwd, pid)));
+                    }
+                    // Check for duplicate display name (NEW check)
+                    if proc.display_name == final_name {
+                         return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("A process with the name '{}' is already running (PID {})", final_name, pid)));
                     }
                 }
             }
@@ -97,32 +141,295 @@ pub fn launch_process_with_name(
     let clean_name: String = script_name.chars().filter(|c| c.is_alphanumeric() || *c == '_' || *c == '-').collect();
     let log_file = log_dir.join(format!("{}_{}.log", clean_name, timestamp % 100000));
 
-    let log_handle = OpenOptions::new().create(true).append(true).open(&log_file)?;
+    let spawned = spawn_detached(state_file, &cmd, &cmd_args, &cwd, &log_file, &options)?;
 
+    let final_name = display_name.unwrap_or_else(|| script_name.clone());
+    
+    register_process(state_file, spawned.pid, &cmd_str, &argv, timeout_sec, &log_file, script_name, &cwd, &final_name, &options, 0);
 
-    let mut child = Command::new(&cmd);
+    Ok(())
+}
+
+// Applied in the child between fork and exec, so only async-signal-safe calls belong here
+fn apply_limits(limits: &ResourceLimits) -> io::Result<()> {
+    let set = |resource, value: Option<u64>| -> io::Result<()> {
+        if let Some(v) = value {
+            let rlim = libc::rlimit { rlim_cur: v as libc::rlim_t, rlim_max: v as libc::rlim_t };
+            if unsafe { libc::setrlimit(resource, &rlim) } != 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        Ok(())
+    };
+    set(libc::RLIMIT_AS, limits.max_memory)?;
+    set(libc::RLIMIT_CPU, limits.max_cpu_time)?;
+    set(libc::RLIMIT_NOFILE, limits.nofile)?;
+    Ok(())
+}
+
+// Kernel start time of a process in clock ticks since boot (field 22 of /proc/<pid>/stat)
+pub fn start_ticks(pid: u32) -> Option<u64> {
+    #[cfg(target_os = "linux")]
+    {
+        let stat = fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+        // The command name (field 2) can contain spaces and parentheses, so count from the last ')'
+        let rest = stat.get(stat.rfind(')')? + 2..)?;
+        rest.split_whitespace().nth(19)?.parse().ok()
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = pid;
+        None
+    }
+}
+
+pub fn boot_id() -> Option<String> {
+    fs::read_to_string("/proc/sys/kernel/random/boot_id").ok().map(|s| s.trim().to_string())
+}
 
-    child
-        .args(&cmd_args)
-        .current_dir(&cwd)
-        .stdout(Stdio::from(log_handle.try_clone()?))
+// False when the PID now belongs to something else: recorded before a reboot, or reused since.
+// Entries from older versions have nothing recorded and are trusted as before.
+pub fn is_same_process(pid: u32, proc: &ProcessInfo) -> bool {
+    if let Some(recorded) = &proc.boot_id
+        && boot_id().is_some_and(|current| current != *recorded)
+    {
+        return false;
+    }
+    match proc.start_ticks {
+        Some(recorded) => start_ticks(pid) == Some(recorded),
+        None => true,
+    }
+}
+
+// Signals the process group only if the PID still belongs to the tracked process
+pub fn kill_group(pid_str: &str, proc: &ProcessInfo, signal: i32) -> bool {
+    let Ok(pid) = pid_str.parse::<u32>() else { return false };
+    if pid <= 1 {
+        return false;
+    }
+    if !is_same_process(pid, proc) {
+        eprintln!("Warning: PID {} no longer belongs to '{}'; not sending a signal.", pid, proc.display_name);
+        return false;
+    }
+    unsafe { libc::kill(-(pid as i32), signal) == 0 }
+}
+
+// Stops the process group with the configured stop signal, escalating to SIGKILL after stop_timeout
+pub fn stop_group(pid_str: &str, proc: &ProcessInfo) -> bool {
+    let settings = config::settings();
+    let signal = settings.stop_signal();
+    if !kill_group(pid_str, proc, signal) {
+        return false;
+    }
+    if signal != libc::SIGKILL {
+        let pid: i32 = pid_str.parse().unwrap_or(0);
+        // No deadline at all for a timeout beyond what an Instant can hold
+        let deadline = std::time::Instant::now().checked_add(std::time::Duration::from_secs(settings.stop_timeout_secs()));
+        // Our own children linger as zombies until reaped, which still counts as stopped
+        let gone = || unsafe { libc::waitpid(pid, std::ptr::null_mut(), libc::WNOHANG) == pid || libc::kill(pid, 0) != 0 };
+        while !gone() && deadline.is_none_or(|d| std::time::Instant::now() < d) {
+            std::thread::sleep(std::time::Duration::from_millis(50));
+        }
+        if !gone() {
+            unsafe { libc::kill(-pid, libc::SIGKILL) };
+        }
+    }
+    std::thread::sleep(std::time::Duration::from_millis(200));
+    true
+}
+
+// Status of a process that is being stopped with the state lock released; the reaper and
+// scheduler only look at "running" processes, so they leave it alone meanwhile
+pub const STOPPING: &str = "stopping";
+
+// Marks the running processes among pids as stopping and returns copies of them to stop once
+// the lock is released. Called inside update_state.
+pub fn mark_stopping(state: &mut std::collections::HashMap<String, ProcessInfo>, pids: &[String]) -> Vec<(String, ProcessInfo)> {
+    let mut marked = Vec::new();
+    for pid_str in pids {
+        if let Some(proc) = state.get_mut(pid_str).filter(|p| p.status == "running") {
+            marked.push((pid_str.clone(), proc.clone()));
+            proc.status = STOPPING.to_string();
+            proc.stopping_since = Some(SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs_f64());
+        }
+    }
+    marked
+}
+
+// Stops the processes in parallel, so a bulk stop takes one stop_timeout rather than one per process.
+// Must not be called inside update_state, since it can take that long.
+pub fn stop_all(procs: &[(String, ProcessInfo)]) {
+    std::thread::scope(|scope| {
+        for (pid_str, proc) in procs {
+            scope.spawn(move || stop_group(pid_str, proc));
+        }
+    });
+}
+
+// Records the final status of processes stopped after mark_stopping, unless they were removed meanwhile
+pub fn finish_stopping(state_file: &Path, stopped: &[(String, ProcessInfo)], status: &str, reason: &str) {
+    if stopped.is_empty() {
+        return;
+    }
+    update_state(state_file, |state| {
+        for (pid_str, _) in stopped {
+            if let Some(proc) = state.get_mut(pid_str).filter(|p| p.status == STOPPING) {
+                proc.status = status.to_string();
+                events::record(state_file, "killed", pid_str, &proc.display_name, Some(reason.to_string()));
+            }
+        }
+    });
+}
+
+// What a supervisor needs to start a command and report how it exited
+#[derive(Serialize, Deserialize)]
+pub struct SuperviseSpec {
+    pub state_file: PathBuf,
+    pub cmd: String,
+    pub args: Vec<String>,
+    pub cwd: PathBuf,
+    pub log_file: PathBuf,
+    pub options: ProcessOptions,
+}
+
+// A command started by spawn_detached. Its supervisor records the exit status only once this is
+// dropped, so the caller must register the PID first.
+pub struct Spawned {
+    pub pid: u32,
+    _registered: std::process::ChildStdin,
+}
+
+// Starts a command in its own session, with stdout/stderr appended to the log file, under a
+// supervisor (`internal-supervise`) that waits for it and records its exit status. Managed
+// processes outlive the CLI or TUI that started them, and only a parent can collect an exit status.
+pub fn spawn_detached(state_file: &Path, cmd: &str, args: &[String], cwd: &str, log_file: &Path, options: &ProcessOptions) -> io::Result<Spawned> {
+    let spec = SuperviseSpec {
+        state_file: std::path::absolute(state_file)?,
+        cmd: cmd.to_string(),
+        args: args.to_vec(),
+        cwd: std::path::absolute(cwd)?,
+        log_file: std::path::absolute(log_file)?,
+        options: options.clone(),
+    };
+    let log_handle = OpenOptions::new().create(true).append(true).open(log_file)?;
+    let mut supervisor = Command::new(env::current_exe()?);
+    supervisor
+        .arg("internal-supervise")
+        .arg(serde_json::to_string(&spec)?)
+        // So it doesn't keep the caller's directory busy; the paths in the spec are absolute
+        .current_dir("/")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
         .stderr(Stdio::from(log_handle));
+    unsafe {
+        supervisor.pre_exec(|| {
+            libc::setsid();
+            Ok(())
+        })
+    };
+    let mut supervisor = supervisor.spawn()?;
+
+    // The first supervisor process forks and exits right away, so it is reaped here and the one
+    // that stays is adopted by init instead of lingering as our zombie once it is done
+    let registered = supervisor.stdin.take().unwrap();
+    let mut line = String::new();
+    let read = io::BufRead::read_line(&mut io::BufReader::new(supervisor.stdout.take().unwrap()), &mut line);
+    supervisor.wait()?;
+    read?;
+    match line.trim().parse::<u32>() {
+        Ok(pid) => Ok(Spawned { pid, _registered: registered }),
+        Err(_) => Err(io::Error::other(line.trim().strip_prefix("error: ").unwrap_or("the supervisor exited without starting the command").to_string())),
+    }
+}
 
+// Body of `internal-supervise`: starts the command, reports its PID (or an error) on stdout,
+// waits for it and records its exit status
+pub fn supervise(spec: &SuperviseSpec) {
+    use std::io::Write;
+    // Still single-threaded here, so forking is safe; see spawn_detached
+    match unsafe { libc::fork() } {
+        -1 => {
+            println!("error: {}", io::Error::last_os_error());
+            return;
+        }
+        0 => {}
+        _ => unsafe { libc::_exit(0) },
+    }
+
+    let log_handle = match OpenOptions::new().create(true).append(true).open(&spec.log_file) {
+        Ok(f) => f,
+        Err(e) => {
+            println!("error: {}", e);
+            return;
+        }
+    };
+    let limits = spec.options.limits;
+    let mut command = Command::new(&spec.cmd);
+    command
+        .args(&spec.args)
+        .envs(&spec.options.env)
+        .current_dir(&spec.cwd)
+        .stdin(Stdio::null())
+        .stdout(Stdio::from(log_handle.try_clone().expect("log file handle")))
+        .stderr(Stdio::from(log_handle));
     unsafe {
-            child
-                .pre_exec(|| {
-                    libc::setsid();
-                    Ok(())
-                })
+        command.pre_exec(move || {
+            libc::setsid();
+            apply_limits(&limits)
+        })
     };
+    let mut child = match command.spawn() {
+        Ok(child) => child,
+        Err(e) => {
+            println!("error: {}", e);
+            return;
+        }
+    };
+    let pid = child.id();
+    println!("{}", pid);
+    io::stdout().flush().ok();
+    // Nothing else is written to the caller; hook commands shouldn't inherit the pipe either
+    if let Ok(null) = fs::File::open("/dev/null") {
+        unsafe { libc::dup2(std::os::fd::AsRawFd::as_raw_fd(&null), 1) };
+    }
 
-    let child = child.spawn()?;
+    // The caller closes our stdin once the PID is registered
+    io::copy(&mut io::stdin(), &mut io::sink()).ok();
 
-    let final_name = display_name.unwrap_or_else(|| script_name.clone());
-    
-    register_process(state_file, child.id(), &cmd_str, timeout_sec, &log_file, script_name, &cwd, &final_name);
+    // Waiting with WNOWAIT leaves the process a zombie until the status is recorded, so the
+    // reaper never sees it gone and marks it completed without an exit code
+    let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+    let waited = loop {
+        let r = unsafe { libc::waitid(libc::P_PID, pid, &mut info, libc::WEXITED | libc::WNOWAIT) };
+        if r == 0 || io::Error::last_os_error().kind() != io::ErrorKind::Interrupted {
+            break r == 0;
+        }
+    };
+    if waited {
+        // Signals are reported shell-style as 128 + signal number
+        let status = unsafe { info.si_status() };
+        let code = if info.si_code == libc::CLD_EXITED { status } else { 128 + status };
+        record_exits(&spec.state_file, &[(pid, code)]);
+    }
+    child.wait().ok();
+}
 
-    Ok(())
+// Starts a fresh instance of a tracked process from its recorded command and registers it
+pub fn respawn_process(state_file: &Path, proc: &ProcessInfo) -> io::Result<u32> {
+    // cmd_str loses the boundaries of arguments containing spaces, so it is only a fallback
+    let argv: Vec<String> = if proc.argv.is_empty() {
+        proc.cmd_str.split_whitespace().map(str::to_string).collect()
+    } else {
+        proc.argv.clone()
+    };
+    if argv.is_empty() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "empty command"));
+    }
+
+    let spawned = spawn_detached(state_file, &argv[0], &argv[1..], &proc.working_dir, Path::new(&proc.log_file), &proc.options)?;
+    register_process(state_file, spawned.pid, &proc.cmd_str, &argv, proc.timeout_sec, Path::new(&proc.log_file), &proc.script_name, &proc.working_dir, &proc.display_name, &proc.options, proc.restarts + 1);
+    hooks::fire(state_file, vec![HookEvent::new("restart", &spawned.pid.to_string(), proc, None)]);
+    Ok(spawned.pid)
 }
 
 pub fn revive_dead_processes(_script_dir: &Path, state_file: &Path, _log_dir: &Path) {
@@ -137,11 +444,12 @@ pub fn revive_dead_processes(_script_dir: &Path, state_file: &Path, _log_dir: &P
     let initial_state = read_state(state_file);
     let pids_to_revive: Vec<_> = initial_state.iter()
         .filter_map(|(pid_str, proc)| {
-            if proc.status != "running" {
+            // Daemon entries left by older versions are dropped by the daemon, never respawned
+            if proc.status != "running" || proc.display_name == crate::scheduler::DAEMON_NAME {
                 return None;
             }
             let pid: u32 = pid_str.parse().ok()?;
-            if sys.process(Pid::from_u32(pid)).is_none() {
+            if sys.process(Pid::from_u32(pid)).is_none() || !is_same_process(pid, proc) {
                 Some((pid_str.clone(), proc.clone()))
             } else {
                 None
@@ -163,36 +471,119 @@ pub fn revive_dead_processes(_script_dir: &Path, state_file: &Path, _log_dir: &P
             state.remove(&old_pid);
         });
         
+        events::record(state_file, "revived", &old_pid, &proc.display_name, None);
         if let Ok(mut f) = OpenOptions::new().append(true).open(&proc.log_file) {
             writeln!(f, "\n--- 🔄 AUTO-REVIVED (was PID {}) @ {} ---", old_pid, Local::now()).ok();
         }
 
-        let parts: Vec<&str> = proc.cmd_str.split_whitespace().collect();
-        if !parts.is_empty() {
-            let log_handle_res = OpenOptions::new().create(true).append(true).open(&proc.log_file);
-            
-            if let Ok(log_handle) = log_handle_res {
-                if let Ok(child) = unsafe {
-                    Command::new(parts[0])
-                        .args(&parts[1..])
-                        .current_dir(&proc.working_dir)
-                        .stdout(Stdio::from(log_handle.try_clone().unwrap()))
-                        .stderr(Stdio::from(log_handle))
-                        .pre_exec(|| {
-                            libc::setsid();
-                            Ok(())
-                        })
-                        .spawn()
-                } {
-                    let new_pid = child.id();
-                    println!("  -> Started with new PID: {}", new_pid);
-                    register_process(state_file, new_pid, &proc.cmd_str, proc.timeout_sec, Path::new(&proc.log_file), &proc.script_name, &proc.working_dir, &proc.display_name);
-                } else {
-                    eprintln!("Failed to spawn process for revival: {}", proc.cmd_str);
-                }
-            } else {
-                eprintln!("Failed to open log file for revival: {}", proc.log_file);
+        match respawn_process(state_file, &proc) {
+            Ok(new_pid) => println!("  -> Started with new PID: {}", new_pid),
+            Err(e) => eprintln!("Failed to spawn process for revival: {} ({})", proc.cmd_str, e),
+        }
+    }
+}
+
+// Respawns processes that exited on their own when their restart policy asks for it (run by the daemon)
+// Restarts by policy wait 1s, 2s, 4s, ... (doubling with each restart, up to 5 minutes) after
+// the previous start, so a process that crashes right away isn't respawned in a tight loop.
+// One that ran longer than that is restarted at once.
+const RESTART_BACKOFF_BASE_SECS: f64 = 1.0;
+const RESTART_BACKOFF_MAX_SECS: f64 = 300.0;
+
+pub fn restart_backoff(restarts: u32) -> f64 {
+    (RESTART_BACKOFF_BASE_SECS * 2f64.powi(restarts.min(16) as i32)).min(RESTART_BACKOFF_MAX_SECS)
+}
+
+pub fn apply_restart_policies(state_file: &Path) {
+    use std::io::Write;
+    use chrono::Local;
+
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs_f64();
+    let mut to_restart = Vec::new();
+    update_state(state_file, |state| {
+        for (pid_str, proc) in state.iter_mut() {
+            // Left "completed" until the backoff has passed; a later tick picks it up
+            if proc.status == "completed"
+                && proc.options.restart_policy.should_restart(proc.exit_code)
+                && now >= proc.start_time + restart_backoff(proc.restarts)
+            {
+                // Marked before respawning so a second daemon tick can't restart it twice
+                proc.status = "restarted (policy)".to_string();
+                to_restart.push((pid_str.clone(), proc.clone()));
             }
         }
+    });
+
+    for (old_pid, proc) in to_restart {
+        let reason = match proc.exit_code {
+            Some(code) => format!("exit code {}", code),
+            None => "exited".to_string(),
+        };
+        if let Ok(mut f) = OpenOptions::new().append(true).open(&proc.log_file) {
+            writeln!(f, "\n--- 🔄 RESTARTED by policy after {} (was PID {}) @ {} ---", reason, old_pid, Local::now()).ok();
+        }
+        match respawn_process(state_file, &proc) {
+            Ok(new_pid) => println!("Restart policy: restarted '{}' ({}) with new PID {}", proc.display_name, reason, new_pid),
+            Err(e) => eprintln!("Restart policy: failed to restart '{}': {}", proc.display_name, e),
+        }
     }
-}
\ No newline at end of file
+}
+
+// Copy-and-truncate rotation: the process keeps writing to its O_APPEND handle on the same file
+pub fn rotate_log(log_file: &Path, max_size: u64, keep: usize) -> io::Result<bool> {
+    if fs::metadata(log_file)?.len() <= max_size {
+        return Ok(false);
+    }
+    let rotated = |n: usize| PathBuf::from(format!("{}.{}", log_file.display(), n));
+    for n in (1..keep).rev() {
+        if rotated(n).exists() {
+            fs::rename(rotated(n), rotated(n + 1))?;
+        }
+    }
+    fs::copy(log_file, rotated(1))?;
+    OpenOptions::new().write(true).open(log_file)?.set_len(0)?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_size_units() {
+        assert_eq!(parse_size("512"), Some(512));
+        assert_eq!(parse_size("64k"), Some(64 * 1024));
+        assert_eq!(parse_size("2G"), Some(2 * 1024 * 1024 * 1024));
+        assert_eq!(parse_size("G"), None);
+        assert_eq!(parse_size("1.5G"), None);
+    }
+
+    #[test]
+    fn parse_size_overflow_is_invalid() {
+        assert_eq!(parse_size("99999999999999G"), None);
+        assert_eq!(parse_size("18446744073709551615"), Some(u64::MAX));
+    }
+
+    #[test]
+    fn parse_frequency_overflow_is_invalid() {
+        use crate::scheduler::parse_frequency;
+        assert_eq!(parse_frequency("90s"), Some(90));
+        assert_eq!(parse_frequency("2d"), Some(2 * 86400));
+        assert_eq!(parse_frequency("99999999999999999d"), None);
+        assert_eq!(parse_frequency("18446744073709551615s"), Some(u64::MAX));
+        assert_eq!(parse_frequency("307445734561825861m"), None);
+        // A multi-byte last character used to panic when split off by byte
+        assert_eq!(parse_frequency("5é"), None);
+        assert_eq!(parse_frequency("s"), None);
+        assert_eq!(parse_frequency("5"), None);
+    }
+
+    #[test]
+    fn restart_backoff_doubles_up_to_the_cap() {
+        assert_eq!(restart_backoff(0), 1.0);
+        assert_eq!(restart_backoff(1), 2.0);
+        assert_eq!(restart_backoff(5), 32.0);
+        assert_eq!(restart_backoff(9), 300.0);
+        assert_eq!(restart_backoff(u32::MAX), 300.0);
+    }
+}
@@ -0,0 +1,127 @@
+use std::cmp::Reverse;
+use crate::{monitor::Monitor, types::ProcessInfo};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SortMode {
+    // Newest first, the order `list` uses
+    Started,
+    Name,
+    Status,
+    Cpu,
+    Memory,
+    // Longest running first; processes that aren't running come last
+    Uptime,
+}
+
+impl SortMode {
+    pub fn next(self) -> Self {
+        match self {
+            SortMode::Started => SortMode::Name,
+            SortMode::Name => SortMode::Status,
+            SortMode::Status => SortMode::Cpu,
+            SortMode::Cpu => SortMode::Memory,
+            SortMode::Memory => SortMode::Uptime,
+            SortMode::Uptime => SortMode::Started,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            SortMode::Started => "started",
+            SortMode::Name => "name",
+            SortMode::Status => "status",
+            SortMode::Cpu => "cpu",
+            SortMode::Memory => "memory",
+            SortMode::Uptime => "uptime",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum GroupMode {
+    None,
+    // By the first tag; untagged processes come last
+    Tag,
+    Cwd,
+}
+
+impl GroupMode {
+    pub fn next(self) -> Self {
+        match self {
+            GroupMode::None => GroupMode::Tag,
+            GroupMode::Tag => GroupMode::Cwd,
+            GroupMode::Cwd => GroupMode::None,
+        }
+    }
+}
+
+// How the TUI narrows down, orders and groups the process list
+pub struct ListOptions {
+    // Words that must all match: `status:`, `tag:` and `cwd:` test those fields, anything else the name
+    pub filter: String,
+    pub sort: SortMode,
+    pub group: GroupMode,
+    // Only show running processes
+    pub hide_inactive: bool,
+}
+
+impl ListOptions {
+    pub fn new() -> Self {
+        Self { filter: String::new(), sort: SortMode::Started, group: GroupMode::None, hide_inactive: false }
+    }
+
+    pub fn matches(&self, proc: &ProcessInfo) -> bool {
+        if self.hide_inactive && proc.status != "running" {
+            return false;
+        }
+        self.filter.split_whitespace().all(|term| {
+            let term = term.to_lowercase();
+            let contains = |field: &str, value: &str| field.to_lowercase().contains(value);
+            match term.split_once(':') {
+                Some(("status", value)) => contains(&proc.status, value),
+                Some(("tag", value)) => proc.options.tags.iter().any(|tag| contains(tag, value)),
+                Some(("cwd", value)) => contains(&proc.working_dir, value),
+                _ => contains(&proc.display_name, &term),
+            }
+        })
+    }
+
+    // Expects the list newest first, as `reap_processes` returns it; the sorts are stable,
+    // so ties keep that order
+    pub fn sort(&self, procs: &mut [(String, ProcessInfo)], monitor: &Monitor) {
+        let stats = |pid: &str| pid.parse().ok().and_then(|pid| monitor.get_stats(pid));
+        match self.sort {
+            SortMode::Started => {}
+            SortMode::Name => procs.sort_by_key(|(_, p)| p.display_name.to_lowercase()),
+            SortMode::Status => procs.sort_by_key(|(_, p)| (p.status != "running", p.status.clone())),
+            SortMode::Cpu => procs.sort_by(|(a, _), (b, _)| {
+                let cpu = |pid| stats(pid).map_or(0.0, |s| s.cpu_usage);
+                cpu(b).total_cmp(&cpu(a))
+            }),
+            SortMode::Memory => procs.sort_by_key(|(pid, _)| Reverse(stats(pid).map_or(0, |s| s.mem_usage))),
+            SortMode::Uptime => procs.sort_by(|(_, a), (_, b)| {
+                (a.status != "running").cmp(&(b.status != "running")).then(a.start_time.total_cmp(&b.start_time))
+            }),
+        }
+        if self.group != GroupMode::None {
+            procs.sort_by_key(|(_, p)| {
+                let key = self.group_key(p);
+                (key.is_none(), key)
+            });
+        }
+    }
+
+    // The heading a process is listed under; None for the catch-all group at the end
+    pub fn group_key(&self, proc: &ProcessInfo) -> Option<String> {
+        match self.group {
+            GroupMode::None => None,
+            GroupMode::Tag => proc.options.tags.first().cloned(),
+            GroupMode::Cwd => Some(proc.working_dir.clone()).filter(|dir| !dir.is_empty()),
+        }
+    }
+
+    // True when some processes may be left out of the list
+    pub fn is_filtering(&self) -> bool {
+        self.hide_inactive || !self.filter.trim().is_empty()
+    }
+}
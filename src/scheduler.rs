@@ -1,21 +1,80 @@
-use std::{path::Path, thread, time::Duration};
+use std::{path::Path, time::{Duration, Instant}};
 use std::time::{SystemTime, UNIX_EPOCH};
-use crate::state::{read_scheduled_jobs, read_state, write_scheduled_jobs};
-use crate::process::launch_process_with_name;
+use crate::state::{read_scheduled_jobs, read_state, update_scheduled_jobs, update_state};
+use crate::process::{apply_restart_policies, finish_stopping, launch_process_with_name, mark_stopping, revive_dead_processes, rotate_log, stop_all};
+use crate::config::{self, ShutdownAction};
+use crate::{daemon, events, sqlite_store, trash};
+use crate::monitor::Monitor;
+use crate::stats::StatsRecorder;
+use crate::watchdog::{self, Watchdog};
+use crate::types::{ProcessOptions, ScheduledJob};
 
+// Older versions tracked the daemon itself in the state file under this name
+pub const DAEMON_NAME: &str = "ksai_scheduler_daemon";
 
 pub fn start_scheduler_daemon(state_file: &Path, scheduled_file: &Path, log_dir: &Path) {
-    let script_dir = log_dir.parent().unwrap().to_path_buf(); // Assuming log_dir is inside the base dir
+    // scripts/ lives next to the binary, wherever the state and logs are kept
+    let script_dir = std::env::current_exe().ok().and_then(|p| p.parent().map(Path::to_path_buf)).unwrap_or_default();
+
+    let pid_file = match daemon::acquire(state_file) {
+        Ok(Some(file)) => file,
+        Ok(None) => {
+            println!("Scheduler daemon already running (PID {}); exiting.", daemon::running_pid(state_file).unwrap_or(0));
+            return;
+        }
+        Err(e) => {
+            eprintln!("Scheduler: Cannot lock {}: {}", daemon::pid_file(state_file).display(), e);
+            return;
+        }
+    };
     
-    println!("Scheduler daemon started. Monitoring scheduled: {}", scheduled_file.display());
+    println!("Scheduler daemon started (PID {}). Monitoring scheduled: {}", std::process::id(), scheduled_file.display());
     println!("Scheduler daemon using state file: {}", state_file.display());
     println!("Scheduler daemon using log dir: {}", log_dir.display());
 
-    loop {
-        // Run loop every 1 second
-        thread::sleep(Duration::from_secs(1));
+    // The pidfile replaces those entries; they would only ever show up as dead processes
+    update_state(state_file, |state| state.retain(|_, p| p.display_name != DAEMON_NAME));
+
+    // Started at boot (systemd or cron) the state still lists what ran before; bring it back.
+    // When a CLI starts us it has just reaped, so there is nothing left to revive.
+    revive_dead_processes(&script_dir, state_file, log_dir);
+
+    let watched = [
+        state_file.to_path_buf(),
+        scheduled_file.to_path_buf(),
+        sqlite_store::db_file(state_file),
+        daemon::pid_file(state_file),
+    ];
+    let wakeup = match daemon::Wakeup::install(&watched) {
+        Ok(wakeup) => wakeup,
+        Err(e) => {
+            eprintln!("Scheduler: Cannot set up signal handling: {}", e);
+            return;
+        }
+    };
+
+    let mut monitor = Monitor::new();
+    let mut recorder = StatsRecorder::new(log_dir);
+    let mut watchdog = Watchdog::new();
+    let mut last_sample: Option<Instant> = None;
+    let mut last_events_compact = 0;
+
+    while !daemon::shutdown_requested() {
+        if !daemon::still_owns(&pid_file, state_file) {
+            println!("Scheduler: {} was removed or replaced; exiting.", daemon::pid_file(state_file).display());
+            return;
+        }
+
+        if daemon::take_reload() {
+            match config::reload() {
+                Ok(_) => println!("Scheduler: Reloaded {} and the schedule.", config::config_file().display()),
+                Err(e) => eprintln!("Scheduler: Keeping the current config, {} is invalid: {}", config::config_file().display(), e),
+            }
+        }
+        let settings = config::settings();
+        let tick = Duration::from_millis(settings.scheduler.tick_ms);
         
-        // Reap zombies (children that exited)
+        // Reap any stray children; managed processes get their exit codes from their supervisors
         unsafe {
             let mut status = 0;
             loop {
@@ -28,19 +87,56 @@ pub fn start_scheduler_daemon(state_file: &Path, scheduled_file: &Path, log_dir:
             }
         }
 
-        
         // Clean up dead processes first so we have accurate state
         use crate::state::reap_processes;
         let _ = reap_processes(state_file);
+        apply_restart_policies(state_file);
 
         let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+        if now >= last_events_compact + events::COMPACT_INTERVAL_SECS {
+            events::compact(state_file, now as f64);
+            last_events_compact = now;
+        }
+
         let mut scheduled_jobs = read_scheduled_jobs(scheduled_file);
-        let mut changed = false;
+        let mut checked = Vec::new();
 
         let running_processes = read_state(state_file);
 
+        // Sampling only makes sense at a steady pace, however often other events wake us
+        let sample_due = last_sample.is_none_or(|t| t.elapsed() >= tick);
+        if sample_due {
+            last_sample = Some(Instant::now());
+            let running_pids: Vec<u32> = running_processes.iter()
+                .filter(|(_, p)| p.status == "running")
+                .filter_map(|(pid_s, _)| pid_s.parse().ok())
+                .collect();
+            monitor.update(&running_pids);
+            recorder.tick(&monitor, &running_processes);
+            watchdog::enforce(state_file, watchdog.check(&monitor, &running_processes));
+        }
+
+        if sample_due && let Some(max_size) = settings.log_max_size() {
+            let mut log_files: Vec<&String> = running_processes.values().filter(|p| p.status == "running").map(|p| &p.log_file).collect();
+            log_files.sort();
+            log_files.dedup();
+            for log_file in log_files {
+                // Logs deleted with `c` in the TUI or `remove` have nothing left to rotate
+                if let Err(e) = rotate_log(Path::new(log_file), max_size, settings.logs.keep)
+                    && e.kind() != std::io::ErrorKind::NotFound
+                {
+                    eprintln!("Scheduler: Failed to rotate {}: {}", log_file, e);
+                }
+            }
+        }
+
+        if sample_due {
+            trash::purge(log_dir, Some(settings.trash_retention_secs()));
+        }
+
         for job in scheduled_jobs.iter_mut() {
-            if !job.enabled {
+            // A manual trigger runs the job even if it is disabled or not due yet
+            if !job.enabled && !job.run_now {
                 continue;
             }
 
@@ -57,19 +153,19 @@ pub fn start_scheduler_daemon(state_file: &Path, scheduled_file: &Path, log_dir:
             
             // So, check if (now >= start_at).
             
-            if now < job.start_at {
+            if now < job.start_at && !job.run_now {
                 continue;
             }
 
             // Parse frequency
-            let freq_secs = parse_frequency(&job.frequency).unwrap_or(60); // Default 1m
+            let freq_secs = parse_frequency(&job.frequency).unwrap_or_else(|| settings.default_frequency_secs());
 
             // We should check this job every `freq_secs`.
             // Ideally, we want to align with the frequency windows, but simpler is:
             // If last_run + freq <= now, then we perform the check.
             
-            let should_check = match job.last_run {
-                Some(last) => now >= last + freq_secs,
+            let should_check = job.run_now || match job.last_run {
+                Some(last) => now >= last.saturating_add(freq_secs),
                 None => true, // Check immediately if never run (and past start_at)
             };
 
@@ -88,6 +184,7 @@ pub fn start_scheduler_daemon(state_file: &Path, scheduled_file: &Path, log_dir:
                 } else {
                     // Not running, start it!
                     println!("Scheduler: Starting job '{}'...", job.name);
+                    events::record(state_file, "scheduled-fired", "-", &target_name, Some(format!("every {}", job.frequency)));
                     
                     // We need to construct script_args from command + args
                     let mut script_args = vec![job.command.clone()];
@@ -102,6 +199,7 @@ pub fn start_scheduler_daemon(state_file: &Path, scheduled_file: &Path, log_dir:
                         None, // Timeout? User didn't specify. Assuming None.
                         Some(target_name.clone()),
                         Some(job.working_dir.clone()),
+                        ProcessOptions::default(),
                     ) {
                         Ok(_) => println!("Scheduler: Successfully started '{}'.", target_name),
                         Err(e) => eprintln!("Scheduler: Failed to start '{}': {}", target_name, e),
@@ -118,28 +216,140 @@ pub fn start_scheduler_daemon(state_file: &Path, scheduled_file: &Path, log_dir:
                 // This seems consistent with "every 1 minute... check".
                 
                 job.last_run = Some(now);
-                changed = true;
+                job.run_now = false;
+                checked.push(job.name.clone());
             }
         }
 
-        if changed {
-            write_scheduled_jobs(scheduled_file, &scheduled_jobs);
+        // Only last_run is ours to write; the CLI may have stopped or removed jobs since we read them
+        if !checked.is_empty() {
+            update_scheduled_jobs(scheduled_file, |jobs| {
+                for job in jobs.iter_mut().filter(|j| checked.contains(&j.name)) {
+                    job.last_run = Some(now);
+                    job.run_now = false;
+                }
+            });
+        }
+
+        if daemon::shutdown_requested() {
+            break;
         }
+
+        // Sleep until the next job is due or the next sample, unless something happens first
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs_f64();
+        let mut timeout = last_sample.map_or(Duration::ZERO, |t| tick.saturating_sub(t.elapsed()));
+        for due in scheduled_jobs.iter().filter_map(|j| next_run(j, settings.default_frequency_secs())) {
+            timeout = timeout.min(Duration::from_secs_f64((due as f64 - now).max(0.0)));
+        }
+        // Our own writes above show up as changes too; only what happens from here on should wake us
+        wakeup.changed();
+        wakeup.wait(timeout);
     }
+
+    shutdown(state_file);
 }
 
-fn parse_frequency(freq: &str) -> Option<u64> {
-    let len = freq.len();
-    if len < 2 { return None; }
-    
-    let (num_str, unit) = freq.split_at(len - 1);
-    let num: u64 = num_str.parse().ok()?;
-    
-    match unit {
-        "s" => Some(num),
-        "m" => Some(num * 60),
-        "h" => Some(num * 3600),
-        "d" => Some(num * 86400),
-        _ => None,
+// Called after the last tick has finished, so the state and schedule on disk are up to date
+fn shutdown(state_file: &Path) {
+    println!("Scheduler: Shutting down (PID {}).", std::process::id());
+    if config::settings().scheduler.on_shutdown == ShutdownAction::Stop {
+        let mut stopping = Vec::new();
+        update_state(state_file, |state| {
+            let running: Vec<String> = state.iter().filter(|(_, p)| p.status == "running").map(|(pid, _)| pid.clone()).collect();
+            stopping = mark_stopping(state, &running);
+        });
+        stop_all(&stopping);
+        finish_stopping(state_file, &stopping, "killed (shutdown)", "daemon shutdown");
+    }
+    println!("Scheduler daemon stopped.");
+}
+
+// When the daemon will next check a job; None for disabled jobs
+pub fn next_run(job: &ScheduledJob, default_frequency_secs: u64) -> Option<u64> {
+    if !job.enabled {
+        return None;
+    }
+    let freq_secs = parse_frequency(&job.frequency).unwrap_or(default_frequency_secs);
+    Some(job.last_run.map_or(job.start_at, |last| last.saturating_add(freq_secs).max(job.start_at)))
+}
+
+// Job changes shared by the CLI and the TUI, each made under the schedule file's lock
+
+pub fn add_job(scheduled_file: &Path, job: ScheduledJob) -> Result<(), String> {
+    save_job(scheduled_file, None, job)
+}
+
+// Replaces `original`, keeping what the daemon tracks for it (last run, enabled, working dir)
+pub fn save_job(scheduled_file: &Path, original: Option<&str>, mut job: ScheduledJob) -> Result<(), String> {
+    let mut result = Ok(());
+    update_scheduled_jobs(scheduled_file, |jobs| {
+        if jobs.iter().any(|j| j.name == job.name && Some(j.name.as_str()) != original) {
+            result = Err(format!("Scheduled job with name '{}' already exists.", job.name));
+            return;
+        }
+        match original {
+            None => jobs.push(job.clone()),
+            Some(name) => match jobs.iter_mut().find(|j| j.name == name) {
+                Some(existing) => {
+                    job.last_run = existing.last_run;
+                    job.enabled = existing.enabled;
+                    job.run_now = existing.run_now;
+                    job.working_dir = std::mem::take(&mut existing.working_dir);
+                    *existing = job.clone();
+                }
+                None => result = Err(format!("Scheduled job '{}' not found.", name)),
+            },
+        }
+    });
+    result
+}
+
+// Returns false if there is no such job
+pub fn modify_job(scheduled_file: &Path, name: &str, f: impl FnOnce(&mut ScheduledJob)) -> bool {
+    let mut found = false;
+    update_scheduled_jobs(scheduled_file, |jobs| {
+        if let Some(job) = jobs.iter_mut().find(|j| j.name == name) {
+            f(job);
+            found = true;
+        }
+    });
+    found
+}
+
+pub fn remove_job(scheduled_file: &Path, name: &str) -> bool {
+    let mut found = false;
+    update_scheduled_jobs(scheduled_file, |jobs| {
+        let len_before = jobs.len();
+        jobs.retain(|j| j.name != name);
+        found = jobs.len() < len_before;
+    });
+    found
+}
+
+// "now", "YYYY-MM-DD HH:MM:SS" or "YYYY-MM-DD" in local time
+pub fn parse_start_at(start_at: &str) -> Option<u64> {
+    use chrono::{NaiveDateTime, Local, TimeZone};
+    if start_at == "now" {
+        return Some(SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs());
     }
+    let dt = NaiveDateTime::parse_from_str(start_at, "%Y-%m-%d %H:%M:%S")
+        .or_else(|_| NaiveDateTime::parse_from_str(&format!("{} 00:00:00", start_at), "%Y-%m-%d %H:%M:%S"))
+        .ok()?;
+    Local.from_local_datetime(&dt).single().map(|d| d.timestamp() as u64)
+}
+
+// Durations like 30s, 5m, 1h or 1d in seconds; None for anything else, including values too large for a u64
+pub fn parse_frequency(freq: &str) -> Option<u64> {
+    let (num_str, mult) = if let Some(n) = freq.strip_suffix('s') {
+        (n, 1)
+    } else if let Some(n) = freq.strip_suffix('m') {
+        (n, 60)
+    } else if let Some(n) = freq.strip_suffix('h') {
+        (n, 3600)
+    } else if let Some(n) = freq.strip_suffix('d') {
+        (n, 86400)
+    } else {
+        return None;
+    };
+    num_str.parse::<u64>().ok()?.checked_mul(mult)
 }
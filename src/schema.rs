@@ -0,0 +1,107 @@
+use std::collections::{BTreeMap, HashMap};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{json, Value};
+use crate::{launch::LaunchSpec, types::{ProcessInfo, ScheduledJob}};
+
+// Bump this and append a step to MIGRATIONS whenever the on-disk format changes
+pub const SCHEMA_VERSION: u32 = 2;
+
+// Files written before versioning are a bare map/array and count as version 1
+const LEGACY_VERSION: u32 = 1;
+
+// A migration turns the payload of version N into version N + 1 and describes what it changed
+type Migration = fn(&mut Value, &str) -> Vec<String>;
+
+const MIGRATIONS: &[(u32, Migration)] = &[
+    (1, migrate_v1_to_v2),
+];
+
+// Data files that are stored inside a versioned envelope: {"schema_version": N, "<KEY>": ...}
+pub trait Versioned: DeserializeOwned + Serialize + Default {
+    const KEY: &'static str;
+}
+
+impl Versioned for HashMap<String, ProcessInfo> {
+    const KEY: &'static str = "processes";
+}
+
+impl Versioned for Vec<ScheduledJob> {
+    const KEY: &'static str = "jobs";
+}
+
+impl Versioned for Vec<LaunchSpec> {
+    const KEY: &'static str = "history";
+}
+
+impl Versioned for BTreeMap<String, LaunchSpec> {
+    const KEY: &'static str = "templates";
+}
+
+pub enum LoadError {
+    Corrupt(String),
+    // Written by a newer ksai_proc; must not be overwritten
+    TooNew(u32),
+}
+
+impl std::fmt::Display for LoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LoadError::Corrupt(e) => write!(f, "{}", e),
+            LoadError::TooNew(v) => write!(f, "schema version {} is newer than this ksai_proc supports ({})", v, SCHEMA_VERSION),
+        }
+    }
+}
+
+pub struct Migrated {
+    pub from_version: u32,
+    pub payload: Value,
+    pub changes: Vec<String>,
+}
+
+// Runs every migration between the file's version and SCHEMA_VERSION, in order
+pub fn migrate(value: Value, key: &str) -> Result<Migrated, LoadError> {
+    let (from_version, mut payload) = match value {
+        Value::Object(mut obj) if obj.contains_key("schema_version") => {
+            let version = obj.get("schema_version").and_then(Value::as_u64)
+                .ok_or_else(|| LoadError::Corrupt("schema_version is not a number".to_string()))? as u32;
+            (version, obj.remove(key).unwrap_or(Value::Null))
+        }
+        legacy => (LEGACY_VERSION, legacy),
+    };
+    if from_version > SCHEMA_VERSION {
+        return Err(LoadError::TooNew(from_version));
+    }
+
+    let mut changes = Vec::new();
+    for (version, step) in MIGRATIONS {
+        if *version >= from_version {
+            changes.extend(step(&mut payload, key).into_iter().map(|c| format!("v{} -> v{}: {}", version, version + 1, c)));
+        }
+    }
+    Ok(Migrated { from_version, payload, changes })
+}
+
+pub fn wrap<T: Versioned>(value: &T) -> serde_json::Result<Value> {
+    Ok(json!({ "schema_version": SCHEMA_VERSION, T::KEY: serde_json::to_value(value)? }))
+}
+
+// v2 moved the data into a versioned envelope and requires a display name on every process
+fn migrate_v1_to_v2(payload: &mut Value, key: &str) -> Vec<String> {
+    let mut changes = vec!["wrap in a versioned envelope".to_string()];
+    if key != "processes" {
+        return changes;
+    }
+    let Some(processes) = payload.as_object_mut() else {
+        return changes;
+    };
+    for (pid, proc) in processes.iter_mut() {
+        let Some(proc) = proc.as_object_mut() else { continue };
+        let has_name = proc.get("display_name").and_then(Value::as_str).is_some_and(|n| !n.is_empty());
+        if !has_name {
+            let name = proc.get("script_name").and_then(Value::as_str).unwrap_or("unknown").to_string();
+            changes.push(format!("PID {}: set missing display_name to '{}'", pid, name));
+            proc.insert("display_name".to_string(), Value::String(name));
+        }
+    }
+    changes
+}
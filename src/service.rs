@@ -0,0 +1,195 @@
+use std::{env, fs, io::{self, Write}, path::{Path, PathBuf}, process::{Command, Stdio}};
+use crate::config::{self, Paths};
+
+// Environment that pins the daemon to exactly one profile's files, shared by every way of starting it
+pub fn daemon_env(paths: &Paths) -> Vec<(&'static str, String)> {
+    // The daemon runs from /, so relative paths from the environment must be resolved here
+    let abs = |p: &Path| std::path::absolute(p).unwrap_or_else(|_| p.to_path_buf()).to_string_lossy().to_string();
+    let mut vars = vec![
+        ("KSAI_PROC_PROFILE", paths.profile.clone()),
+        ("KSAI_PROC_LOG_JSON", abs(&paths.state_file)),
+        ("KSAI_PROC_SCHEDULE_JSON", abs(&paths.scheduled_file)),
+        ("KSAI_PROC_LOG_DIR", abs(&paths.log_dir)),
+    ];
+    if let Ok(config) = env::var("KSAI_PROC_CONFIG") {
+        vars.push(("KSAI_PROC_CONFIG", abs(Path::new(&config))));
+    }
+    vars
+}
+
+pub fn unit_name(profile: &str) -> String {
+    if profile == config::DEFAULT_PROFILE {
+        "ksai_proc.service".to_string()
+    } else {
+        format!("ksai_proc-{}.service", profile)
+    }
+}
+
+pub fn unit_file(profile: &str) -> PathBuf {
+    config::config_home().join("systemd/user").join(unit_name(profile))
+}
+
+// Tags our line in the crontab so uninstall only removes what install added
+fn cron_marker(profile: &str) -> String {
+    format!("# ksai_proc daemon ({})", profile)
+}
+
+// systemd expands %-specifiers in almost every setting, so a literal % has to be doubled
+fn systemd_escape(s: &str) -> String {
+    s.replace('%', "%%")
+}
+
+// One double-quoted word, as ExecStart= and Environment= split on whitespace
+fn systemd_quote(s: &str) -> String {
+    format!("\"{}\"", systemd_escape(&s.replace('\\', "\\\\").replace('"', "\\\"")))
+}
+
+fn unit_contents(exe: &Path, paths: &Paths) -> String {
+    let mut unit = format!(
+        "[Unit]\nDescription=ksai_proc scheduler daemon (profile {})\n\n[Service]\nType=simple\nExecStart={} internal-scheduler\n",
+        paths.profile,
+        systemd_quote(&exe.to_string_lossy())
+    );
+    for (key, value) in daemon_env(paths) {
+        unit.push_str(&format!("Environment={}\n", systemd_quote(&format!("{}={}", key, value))));
+    }
+    unit.push_str("ExecReload=/bin/kill -HUP $MAINPID\n");
+    // The path runs to the end of the line and can't be quoted (it must start with /), so spaces are
+    // fine as they are and only specifiers need escaping
+    unit.push_str(&format!("StandardOutput=append:{}\n", systemd_escape(&paths.log_dir.join("scheduler.log").to_string_lossy())));
+    unit.push_str("StandardError=inherit\n");
+    // Managed processes share the daemon's cgroup; stopping the service must not take them down
+    unit.push_str("KillMode=process\nRestart=on-failure\nRestartSec=5\n\n[Install]\nWantedBy=default.target\n");
+    unit
+}
+
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
+
+fn cron_line(exe: &Path, paths: &Paths) -> String {
+    let vars: Vec<String> = daemon_env(paths).iter().map(|(k, v)| format!("{}={}", k, shell_quote(v))).collect();
+    let command = format!(
+        "{} {} internal-scheduler >> {} 2>&1",
+        vars.join(" "),
+        shell_quote(&exe.to_string_lossy()),
+        shell_quote(&paths.log_dir.join("scheduler.log").to_string_lossy()),
+    );
+    // cron turns an unescaped % into a newline, even inside quotes
+    format!("@reboot {} {}", command.replace('%', "\\%"), cron_marker(&paths.profile))
+}
+
+fn systemctl(args: &[&str]) -> io::Result<bool> {
+    Command::new("systemctl")
+        .arg("--user")
+        .args(args)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map(|s| s.success())
+}
+
+fn systemctl_output(args: &[&str]) -> Option<String> {
+    let out = Command::new("systemctl").arg("--user").args(args).stderr(Stdio::null()).output().ok()?;
+    Some(String::from_utf8_lossy(&out.stdout).trim().to_string())
+}
+
+// An empty crontab and a missing one look the same to us
+fn read_crontab() -> String {
+    Command::new("crontab")
+        .arg("-l")
+        .stderr(Stdio::null())
+        .output()
+        .ok()
+        .filter(|o| o.status.success())
+        .map(|o| String::from_utf8_lossy(&o.stdout).to_string())
+        .unwrap_or_default()
+}
+
+fn write_crontab(contents: &str) -> io::Result<()> {
+    let mut child = Command::new("crontab").arg("-").stdin(Stdio::piped()).spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(contents.as_bytes())?;
+    }
+    if child.wait()?.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other("crontab rejected the new table"))
+    }
+}
+
+fn without_marker(crontab: &str, profile: &str) -> String {
+    let marker = cron_marker(profile);
+    crontab.lines().filter(|l| !l.ends_with(&marker)).map(|l| format!("{}\n", l)).collect()
+}
+
+fn install_systemd(exe: &Path, paths: &Paths) -> io::Result<()> {
+    let unit = unit_file(&paths.profile);
+    if let Some(dir) = unit.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(&unit, unit_contents(exe, paths))?;
+    let name = unit_name(&paths.profile);
+    if systemctl(&["daemon-reload"]).unwrap_or(false) && systemctl(&["enable", &name]).unwrap_or(false) {
+        return Ok(());
+    }
+    fs::remove_file(&unit).ok();
+    Err(io::Error::other("systemctl --user is not available"))
+}
+
+fn install_cron(exe: &Path, paths: &Paths) -> io::Result<()> {
+    let mut crontab = without_marker(&read_crontab(), &paths.profile);
+    crontab.push_str(&cron_line(exe, paths));
+    crontab.push('\n');
+    write_crontab(&crontab)
+}
+
+// Returns how the daemon will be started at boot
+pub fn install(exe: &Path, paths: &Paths, cron_only: bool) -> io::Result<&'static str> {
+    if !cron_only {
+        match install_systemd(exe, paths) {
+            Ok(_) => return Ok("systemd"),
+            Err(e) => eprintln!("Warning: {}; falling back to an @reboot crontab entry.", e),
+        }
+    }
+    install_cron(exe, paths).map(|_| "cron")
+}
+
+// Removes both kinds of installation; returns what was found
+pub fn uninstall(paths: &Paths) -> Vec<String> {
+    let mut removed = Vec::new();
+    let unit = unit_file(&paths.profile);
+    if unit.exists() {
+        systemctl(&["disable", &unit_name(&paths.profile)]).ok();
+        fs::remove_file(&unit).ok();
+        systemctl(&["daemon-reload"]).ok();
+        removed.push(format!("systemd unit {}", unit.display()));
+    }
+    let crontab = read_crontab();
+    let cleaned = without_marker(&crontab, &paths.profile);
+    if cleaned.len() != crontab.len() && write_crontab(&cleaned).is_ok() {
+        removed.push("@reboot crontab entry".to_string());
+    }
+    removed
+}
+
+pub fn print_status(paths: &Paths) {
+    let unit = unit_file(&paths.profile);
+    let name = unit_name(&paths.profile);
+    if unit.exists() {
+        let enabled = systemctl_output(&["is-enabled", &name]).unwrap_or_else(|| "unknown".to_string());
+        let active = systemctl_output(&["is-active", &name]).unwrap_or_else(|| "unknown".to_string());
+        println!("systemd:  {} ({}, {})", unit.display(), enabled, active);
+        let user = env::var("USER").unwrap_or_default();
+        if !Path::new("/var/lib/systemd/linger").join(&user).exists() {
+            println!("          Lingering is off, so it starts at login rather than boot (`loginctl enable-linger {}`).", user);
+        }
+    } else {
+        println!("systemd:  not installed");
+    }
+    let marker = cron_marker(&paths.profile);
+    match read_crontab().lines().find(|l| l.ends_with(&marker)) {
+        Some(_) => println!("cron:     @reboot entry installed"),
+        None => println!("cron:     not installed"),
+    }
+}
@@ -0,0 +1,267 @@
+use std::{collections::HashMap, path::{Path, PathBuf}, time::{Duration, SystemTime, UNIX_EPOCH}};
+use rusqlite::{params, Connection, OptionalExtension, TransactionBehavior};
+use serde_json::{json, Value};
+use crate::{schema::{self, Versioned}, state::{RunRecord, StateStore}, types::{ProcessInfo, ScheduledJob}};
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS processes (
+        pid TEXT PRIMARY KEY,
+        display_name TEXT NOT NULL,
+        status TEXT NOT NULL,
+        start_time REAL NOT NULL,
+        data TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS processes_name ON processes(display_name);
+    CREATE TABLE IF NOT EXISTS jobs (
+        position INTEGER NOT NULL,
+        name TEXT NOT NULL,
+        data TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS runs (
+        pid TEXT NOT NULL,
+        display_name TEXT NOT NULL,
+        cmd TEXT NOT NULL,
+        start_time REAL NOT NULL,
+        end_time REAL,
+        status TEXT NOT NULL,
+        exit_code INTEGER,
+        PRIMARY KEY (pid, start_time)
+    );
+    CREATE INDEX IF NOT EXISTS runs_name ON runs(display_name, start_time);
+";
+
+// Stored in PRAGMA user_version; bump it and extend `migrate` when the tables change
+pub const SQLITE_SCHEMA_VERSION: u32 = 2;
+
+// Rows in databases from before v2 hold data in the JSON schema of that time
+const V1_ROW_VERSION: u32 = 2;
+
+// Processes and jobs share one database next to the JSON files it replaces
+pub fn db_file(path: &Path) -> PathBuf {
+    path.with_file_name("ksai_proc.db")
+}
+
+pub struct SqliteStore {
+    conn: Connection,
+}
+
+impl SqliteStore {
+    pub fn open(path: &Path) -> rusqlite::Result<Self> {
+        let conn = Connection::open(path)?;
+        // The TUI, CLI and daemon all hold their own connection
+        conn.busy_timeout(Duration::from_secs(5))?;
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        conn.pragma_update(None, "synchronous", "NORMAL")?;
+        let mut store = Self { conn };
+        store.migrate()?;
+        Ok(store)
+    }
+
+    pub fn schema_version(&self) -> rusqlite::Result<u32> {
+        self.conn.query_row("PRAGMA user_version", [], |r| r.get(0))
+    }
+
+    // In one transaction, so two processes opening a new database don't both alter it
+    fn migrate(&mut self) -> rusqlite::Result<()> {
+        let tx = self.conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let version: u32 = tx.query_row("PRAGMA user_version", [], |r| r.get(0))?;
+        if version > SQLITE_SCHEMA_VERSION {
+            return Err(rusqlite::Error::InvalidParameterName(format!(
+                "database schema version {} is newer than this ksai_proc supports ({})", version, SQLITE_SCHEMA_VERSION
+            )));
+        }
+        if version < 1 {
+            tx.execute_batch(SCHEMA)?;
+        }
+        // Each row records the JSON schema version of its data, which is migrated like the JSON files
+        if version < 2 {
+            tx.execute_batch(&format!(
+                "ALTER TABLE processes ADD COLUMN schema_version INTEGER NOT NULL DEFAULT {0};
+                 ALTER TABLE jobs ADD COLUMN schema_version INTEGER NOT NULL DEFAULT {0};",
+                V1_ROW_VERSION
+            ))?;
+        }
+        if version < SQLITE_SCHEMA_VERSION {
+            tx.pragma_update(None, "user_version", SQLITE_SCHEMA_VERSION)?;
+        }
+        tx.commit()
+    }
+
+    pub fn integrity_check(&self) -> Result<(), String> {
+        let result: String = self.conn
+            .query_row("PRAGMA integrity_check", [], |r| r.get(0))
+            .map_err(|e| e.to_string())?;
+        if result == "ok" { Ok(()) } else { Err(result) }
+    }
+
+    fn load_processes(conn: &Connection) -> rusqlite::Result<HashMap<String, ProcessInfo>> {
+        let mut stmt = conn.prepare("SELECT pid, schema_version, data FROM processes")?;
+        let rows = stmt.query_map([], |r| Ok((r.get::<_, String>(0)?, r.get::<_, u32>(1)?, r.get::<_, String>(2)?)))?;
+        let mut state = HashMap::new();
+        for row in rows {
+            let (pid, version, data) = row?;
+            match decode_process(&pid, version, &data) {
+                Ok(proc) => {
+                    state.insert(pid, proc);
+                }
+                Err(e) => eprintln!("ERROR: Skipping unreadable process row {}: {}", pid, e),
+            }
+        }
+        Ok(state)
+    }
+
+    fn load_jobs(conn: &Connection) -> rusqlite::Result<Vec<ScheduledJob>> {
+        let mut stmt = conn.prepare("SELECT name, schema_version, data FROM jobs ORDER BY position")?;
+        let rows = stmt.query_map([], |r| Ok((r.get::<_, String>(0)?, r.get::<_, u32>(1)?, r.get::<_, String>(2)?)))?;
+        let mut jobs = Vec::new();
+        for row in rows {
+            let (name, version, data) = row?;
+            match decode_job(version, &data) {
+                Ok(job) => jobs.push(job),
+                Err(e) => eprintln!("ERROR: Skipping unreadable job row '{}': {}", name, e),
+            }
+        }
+        Ok(jobs)
+    }
+
+    // Only rows that actually changed are written, so a reap of hundreds of idle processes is cheap
+    fn try_update_processes(&mut self, f: &mut dyn FnMut(&mut HashMap<String, ProcessInfo>)) -> rusqlite::Result<()> {
+        let tx = self.conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let before = Self::load_processes(&tx)?;
+        let mut after = before.clone();
+        f(&mut after);
+
+        for pid in before.keys().filter(|pid| !after.contains_key(*pid)) {
+            tx.execute("DELETE FROM processes WHERE pid = ?1", params![pid])?;
+        }
+
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs_f64();
+        for (pid, proc) in &after {
+            let data = serde_json::to_string(proc).unwrap_or_default();
+            let unchanged = before.get(pid)
+                .is_some_and(|old| serde_json::to_string(old).is_ok_and(|old_data| old_data == data));
+            if unchanged {
+                continue;
+            }
+            tx.execute(
+                "INSERT OR REPLACE INTO processes (pid, display_name, status, start_time, schema_version, data) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![pid, proc.display_name, proc.status, proc.start_time, schema::SCHEMA_VERSION, data],
+            )?;
+
+            // Run history outlives the process entry, which `remove` and `prune` delete
+            let end_time = if proc.status == "running" { None } else { Some(now) };
+            tx.execute(
+                "INSERT INTO runs (pid, display_name, cmd, start_time, end_time, status, exit_code)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+                 ON CONFLICT(pid, start_time) DO UPDATE SET
+                    status = excluded.status,
+                    exit_code = excluded.exit_code,
+                    end_time = COALESCE(runs.end_time, excluded.end_time)",
+                params![pid, proc.display_name, proc.cmd_str, proc.start_time, end_time, proc.status, proc.exit_code],
+            )?;
+        }
+        tx.commit()
+    }
+
+    fn try_update_jobs(&mut self, f: &mut dyn FnMut(&mut Vec<ScheduledJob>)) -> rusqlite::Result<()> {
+        let tx = self.conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let mut jobs = Self::load_jobs(&tx)?;
+        f(&mut jobs);
+        tx.execute("DELETE FROM jobs", [])?;
+        for (i, job) in jobs.iter().enumerate() {
+            tx.execute(
+                "INSERT INTO jobs (position, name, schema_version, data) VALUES (?1, ?2, ?3, ?4)",
+                params![i as i64, job.name, schema::SCHEMA_VERSION, serde_json::to_string(job).unwrap_or_default()],
+            )?;
+        }
+        tx.commit()
+    }
+}
+
+// Runs a row through the same migrations as a JSON file of its schema version; `collection`
+// turns the row's data into the payload such a file would hold
+fn decode<T: Versioned>(version: u32, data: &str, collection: impl FnOnce(Value) -> Value) -> Result<T, String> {
+    let data: Value = serde_json::from_str(data).map_err(|e| e.to_string())?;
+    let file = json!({ "schema_version": version, T::KEY: collection(data) });
+    let migrated = schema::migrate(file, T::KEY).map_err(|e| e.to_string())?;
+    serde_json::from_value(migrated.payload).map_err(|e| e.to_string())
+}
+
+fn decode_process(pid: &str, version: u32, data: &str) -> Result<ProcessInfo, String> {
+    decode::<HashMap<String, ProcessInfo>>(version, data, |data| json!({ pid: data }))?
+        .remove(pid)
+        .ok_or_else(|| "row has no data".to_string())
+}
+
+fn decode_job(version: u32, data: &str) -> Result<ScheduledJob, String> {
+    decode::<Vec<ScheduledJob>>(version, data, |data| json!([data]))?
+        .pop()
+        .ok_or_else(|| "row has no data".to_string())
+}
+
+impl StateStore for SqliteStore {
+    fn processes(&self) -> HashMap<String, ProcessInfo> {
+        Self::load_processes(&self.conn).unwrap_or_else(|e| {
+            eprintln!("Error: failed to read processes: {}", e);
+            HashMap::new()
+        })
+    }
+
+    fn update_processes(&mut self, f: &mut dyn FnMut(&mut HashMap<String, ProcessInfo>)) {
+        if let Err(e) = self.try_update_processes(f) {
+            eprintln!("Error: failed to update processes: {}", e);
+        }
+    }
+
+    fn jobs(&self) -> Vec<ScheduledJob> {
+        Self::load_jobs(&self.conn).unwrap_or_else(|e| {
+            eprintln!("Error: failed to read scheduled jobs: {}", e);
+            Vec::new()
+        })
+    }
+
+    fn update_jobs(&mut self, f: &mut dyn FnMut(&mut Vec<ScheduledJob>)) {
+        if let Err(e) = self.try_update_jobs(f) {
+            eprintln!("Error: failed to update scheduled jobs: {}", e);
+        }
+    }
+
+    fn runs(&self, name: Option<&str>, limit: usize) -> Vec<RunRecord> {
+        let query = || -> rusqlite::Result<Vec<RunRecord>> {
+            let mut stmt = self.conn.prepare(
+                "SELECT pid, display_name, cmd, start_time, end_time, status, exit_code FROM runs
+                 WHERE ?1 IS NULL OR display_name = ?1
+                 ORDER BY start_time DESC LIMIT ?2",
+            )?;
+            let rows = stmt.query_map(params![name, limit as i64], |r| {
+                Ok(RunRecord {
+                    pid: r.get(0)?,
+                    name: r.get(1)?,
+                    cmd: r.get(2)?,
+                    start_time: r.get(3)?,
+                    end_time: r.get(4)?,
+                    status: r.get(5)?,
+                    exit_code: r.get(6)?,
+                })
+            })?;
+            rows.collect()
+        };
+        query().unwrap_or_else(|e| {
+            eprintln!("Error: failed to read run history: {}", e);
+            Vec::new()
+        })
+    }
+
+    fn find_by_name(&self, name: &str) -> Option<(String, ProcessInfo)> {
+        self.conn
+            .query_row(
+                "SELECT pid, schema_version, data FROM processes WHERE display_name = ?1 ORDER BY start_time DESC LIMIT 1",
+                params![name],
+                |r| Ok((r.get::<_, String>(0)?, r.get::<_, u32>(1)?, r.get::<_, String>(2)?)),
+            )
+            .optional()
+            .ok()
+            .flatten()
+            .and_then(|(pid, version, data)| decode_process(&pid, version, &data).ok().map(|p| (pid, p)))
+    }
+}
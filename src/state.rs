@@ -1,216 +1,559 @@
-use std::{collections::HashMap, fs::{self, OpenOptions}, io::{self, BufReader, Seek, SeekFrom}, path::Path, time::{SystemTime, UNIX_EPOCH}};
-use sysinfo::{Pid, System};
+use std::{collections::{BTreeMap, HashMap}, env, fs::{self, File, OpenOptions}, io::{self, Write}, path::{Path, PathBuf}, sync::{Arc, Mutex, MutexGuard, OnceLock}, time::{SystemTime, UNIX_EPOCH}};
+use serde::Serialize;
 use fs2::FileExt; // Added for file locking
-use crate::types::ProcessInfo;
+use crate::{config, events, hooks::{self, HookEvent}, process, schema::{self, LoadError, Migrated, Versioned}, sqlite_store::{self, SqliteStore}, types::{ProcessInfo, ProcessOptions, ScheduledJob}};
 
-// Helper for atomic state access (Read-Only)
-pub fn read_state(state_file: &Path) -> HashMap<String, ProcessInfo> {
-    if !state_file.exists() {
-        return HashMap::new();
+// Data files are replaced by rename, so the lock lives on a separate file that never moves
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+pub fn lock_file(path: &Path) -> PathBuf {
+    sibling(path, ".lock")
+}
+
+// Last state that was successfully loaded before the most recent write
+pub fn backup_file(path: &Path) -> PathBuf {
+    sibling(path, ".bak")
+}
+
+fn tmp_file(path: &Path) -> PathBuf {
+    sibling(path, ".tmp")
+}
+
+fn lock(path: &Path, exclusive: bool) -> Option<File> {
+    let file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(lock_file(path)).ok()?;
+    let locked = if exclusive { file.lock_exclusive() } else { file.lock_shared() };
+    locked.ok().map(|_| file)
+}
+
+// A missing or empty file is a valid empty state; older schema versions are migrated in memory
+pub fn load_file<T: Versioned>(path: &Path) -> Result<T, LoadError> {
+    let bytes = match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(T::default()),
+        Err(e) => return Err(LoadError::Corrupt(e.to_string())),
+    };
+    if bytes.iter().all(|b| b.is_ascii_whitespace()) {
+        return Ok(T::default());
+    }
+    let value = serde_json::from_slice(&bytes).map_err(|e| LoadError::Corrupt(e.to_string()))?;
+    let migrated = schema::migrate(value, T::KEY)?;
+    if migrated.payload.is_null() {
+        return Ok(T::default());
+    }
+    serde_json::from_value(migrated.payload).map_err(|e| LoadError::Corrupt(e.to_string()))
+}
+
+// Reports which migrations loading the file would apply, for `ksai_proc migrate`
+pub fn pending_migrations<T: Versioned>(path: &Path) -> Result<Option<Migrated>, LoadError> {
+    let bytes = match fs::read(path) {
+        Ok(bytes) if !bytes.iter().all(|b| b.is_ascii_whitespace()) => bytes,
+        Ok(_) => return Ok(None),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(LoadError::Corrupt(e.to_string())),
+    };
+    let value = serde_json::from_slice(&bytes).map_err(|e| LoadError::Corrupt(e.to_string()))?;
+    schema::migrate(value, T::KEY).map(Some)
+}
+
+// temp file + fsync + rename, keeping the previous file as the backup. Caller holds the exclusive lock.
+pub fn write_atomic<T: Versioned>(path: &Path, value: &T) -> io::Result<()> {
+    let tmp = tmp_file(path);
+    {
+        let mut f = File::create(&tmp)?;
+        serde_json::to_writer_pretty(&mut f, &schema::wrap(value)?)?;
+        f.write_all(b"\n")?;
+        f.sync_all()?;
     }
-    
-    if let Ok(file) = OpenOptions::new().read(true).open(state_file) {
-        // Shared lock for reading
-        if file.lock_shared().is_ok() {
-            let reader = BufReader::new(&file);
-            let state = serde_json::from_reader(reader).unwrap_or_default();
-            let _ = file.unlock();
-            return state;
+
+    if path.exists() {
+        let bak = backup_file(path);
+        let _ = fs::remove_file(&bak);
+        if fs::hard_link(path, &bak).is_err() {
+            fs::copy(path, &bak)?;
+        }
+    }
+    fs::rename(&tmp, path)?;
+    if let Some(dir) = path.parent()
+        && let Ok(d) = File::open(dir)
+    {
+        let _ = d.sync_all();
+    }
+    Ok(())
+}
+
+// Moves a corrupt file aside and restores the backup. Caller holds the exclusive lock.
+fn recover<T: Versioned>(path: &Path, err: &str) -> T {
+    let ts = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+    let aside = sibling(path, &format!(".corrupt-{}", ts));
+    eprintln!("ERROR: {} is corrupt ({}). Moved it to {}.", path.display(), err, aside.display());
+    let _ = fs::rename(path, &aside);
+
+    let bak = backup_file(path);
+    let restored = match load_file::<T>(&bak) {
+        Ok(_) if !bak.exists() => {
+            eprintln!("ERROR: No backup found. Starting with an empty state.");
+            T::default()
+        }
+        Ok(value) => {
+            eprintln!("ERROR: Restored {} from backup {}.", path.display(), bak.display());
+            value
+        }
+        Err(e) => {
+            eprintln!("ERROR: Backup is unusable too ({}). Starting with an empty state.", e);
+            T::default()
+        }
+    };
+    // The corrupt file was moved aside, so this doesn't rotate it into the backup
+    if let Err(e) = write_atomic(path, &restored) {
+        eprintln!("ERROR: Failed to write restored state: {}", e);
+    }
+    restored
+}
+
+// None means the file belongs to a newer ksai_proc and must be left alone
+fn load_locked<T: Versioned>(path: &Path) -> Option<T> {
+    match load_file(path) {
+        Ok(value) => Some(value),
+        Err(LoadError::Corrupt(e)) => Some(recover(path, &e)),
+        Err(e @ LoadError::TooNew(_)) => {
+            eprintln!("ERROR: {}: {}. Not touching it; upgrade ksai_proc.", path.display(), e);
+            None
+        }
+    }
+}
+
+pub fn read_json<T: Versioned>(path: &Path) -> T {
+    if let Some(guard) = lock(path, false) {
+        if let Ok(value) = load_file(path) {
+            let _ = guard.unlock();
+            return value;
         }
+        // Recovery writes, so it needs the exclusive lock
+        let _ = guard.unlock();
+        drop(guard);
+        if let Some(guard) = lock(path, true) {
+            let value = load_locked(path);
+            let _ = guard.unlock();
+            return value.unwrap_or_default();
+        }
+    }
+    T::default()
+}
+
+// Read -> Modify -> Write under the exclusive lock
+pub fn update_json<T, R, F>(path: &Path, f: F) -> Option<R>
+where
+    T: Versioned,
+    F: FnOnce(&mut T) -> R,
+{
+    let guard = lock(path, true)?;
+    let Some(mut value) = load_locked::<T>(path) else {
+        let _ = guard.unlock();
+        return None;
+    };
+    let result = f(&mut value);
+    if let Err(e) = write_atomic(path, &value) {
+        eprintln!("Error: failed to write {}: {}", path.display(), e);
+    }
+    let _ = guard.unlock();
+    Some(result)
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct RunRecord {
+    pub pid: String,
+    pub name: String,
+    pub cmd: String,
+    pub start_time: f64,
+    pub end_time: Option<f64>,
+    pub status: String,
+    pub exit_code: Option<i32>,
+}
+
+// Where processes, scheduled jobs and run history are kept
+pub trait StateStore {
+    fn processes(&self) -> HashMap<String, ProcessInfo>;
+    // Read -> Modify -> Write as one transaction
+    fn update_processes(&mut self, f: &mut dyn FnMut(&mut HashMap<String, ProcessInfo>));
+    fn jobs(&self) -> Vec<ScheduledJob>;
+    fn update_jobs(&mut self, f: &mut dyn FnMut(&mut Vec<ScheduledJob>));
+    // Most recent first, optionally for one display name
+    fn runs(&self, name: Option<&str>, limit: usize) -> Vec<RunRecord>;
+
+    fn find_by_name(&self, name: &str) -> Option<(String, ProcessInfo)> {
+        self.processes()
+            .into_iter()
+            .filter(|(_, p)| p.display_name == name)
+            .max_by(|a, b| a.1.start_time.total_cmp(&b.1.start_time))
+    }
+}
+
+// Processes and jobs live in separate JSON files, so a JsonStore is opened on the file being accessed
+pub struct JsonStore {
+    path: PathBuf,
+}
+
+impl JsonStore {
+    pub fn new(path: &Path) -> Self {
+        Self { path: path.to_path_buf() }
+    }
+}
+
+impl StateStore for JsonStore {
+    fn processes(&self) -> HashMap<String, ProcessInfo> {
+        read_json(&self.path)
+    }
+
+    fn update_processes(&mut self, f: &mut dyn FnMut(&mut HashMap<String, ProcessInfo>)) {
+        update_json(&self.path, f);
+    }
+
+    fn jobs(&self) -> Vec<ScheduledJob> {
+        read_json(&self.path)
+    }
+
+    fn update_jobs(&mut self, f: &mut dyn FnMut(&mut Vec<ScheduledJob>)) {
+        update_json(&self.path, f);
+    }
+
+    // The JSON backend keeps no separate history, only entries that haven't been removed or pruned
+    fn runs(&self, name: Option<&str>, limit: usize) -> Vec<RunRecord> {
+        let mut runs: Vec<RunRecord> = self.processes()
+            .into_iter()
+            .filter(|(_, p)| name.is_none_or(|n| p.display_name == n))
+            .map(|(pid, p)| RunRecord {
+                pid,
+                name: p.display_name,
+                cmd: p.cmd_str,
+                start_time: p.start_time,
+                end_time: None,
+                status: p.status,
+                exit_code: p.exit_code,
+            })
+            .collect();
+        runs.sort_by(|a, b| b.start_time.total_cmp(&a.start_time));
+        runs.truncate(limit);
+        runs
+    }
+}
+
+static BACKEND: OnceLock<String> = OnceLock::new();
+
+// The `state_backend` setting from config.toml
+pub fn set_backend(name: &str) {
+    let _ = BACKEND.set(name.to_string());
+}
+
+// "json" (default) or "sqlite"; KSAI_PROC_STATE_BACKEND overrides the config
+pub fn backend_name() -> String {
+    env::var("KSAI_PROC_STATE_BACKEND")
+        .ok()
+        .or_else(|| BACKEND.get().cloned())
+        .unwrap_or_else(|| "json".to_string())
+}
+
+// Database connections opened so far, one per file for the life of the process, so the pragmas
+// and schema check run once rather than on every read and update
+static SQLITE_STORES: Mutex<BTreeMap<PathBuf, Arc<Mutex<SqliteStore>>>> = Mutex::new(BTreeMap::new());
+
+// A connection from SQLITE_STORES. Callers never nest state accesses, so holding its lock
+// for one call can't deadlock.
+struct SharedSqlite(Arc<Mutex<SqliteStore>>);
+
+impl SharedSqlite {
+    fn get(&self) -> MutexGuard<'_, SqliteStore> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl StateStore for SharedSqlite {
+    fn processes(&self) -> HashMap<String, ProcessInfo> {
+        self.get().processes()
+    }
+
+    fn update_processes(&mut self, f: &mut dyn FnMut(&mut HashMap<String, ProcessInfo>)) {
+        self.get().update_processes(f)
+    }
+
+    fn jobs(&self) -> Vec<ScheduledJob> {
+        self.get().jobs()
+    }
+
+    fn update_jobs(&mut self, f: &mut dyn FnMut(&mut Vec<ScheduledJob>)) {
+        self.get().update_jobs(f)
+    }
+
+    fn runs(&self, name: Option<&str>, limit: usize) -> Vec<RunRecord> {
+        self.get().runs(name, limit)
+    }
+
+    fn find_by_name(&self, name: &str) -> Option<(String, ProcessInfo)> {
+        self.get().find_by_name(name)
     }
-    // Fallback if locking fails (should rare) or file issues
-    HashMap::new()
 }
 
-// Helper for atomic state access (Write-Only - typically not used directly to avoid race, but kept for compatibility if needed)
-pub fn write_state(state_file: &Path, state: &HashMap<String, ProcessInfo>) {
-    if let Ok(mut file) = OpenOptions::new().write(true).create(true).truncate(true).open(state_file) {
-        if file.lock_exclusive().is_ok() {
-            let _ = serde_json::to_writer_pretty(&file, state);
-            let _ = file.unlock();
+// Never falls back to JSON: other invocations would keep using the database and the state
+// would be split between the two. A connection must not be carried across fork(), so
+// process::supervise forks before anything opens the store.
+pub fn open_store(path: &Path) -> Result<Box<dyn StateStore>, String> {
+    if backend_name() == "sqlite" {
+        let db = sqlite_store::db_file(path);
+        let mut stores = SQLITE_STORES.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(store) = stores.get(&db) {
+            return Ok(Box::new(SharedSqlite(store.clone())));
         }
+        return match SqliteStore::open(&db) {
+            Ok(store) => {
+                let store = Arc::new(Mutex::new(store));
+                stores.insert(db, store.clone());
+                Ok(Box::new(SharedSqlite(store)))
+            }
+            Err(e) => Err(format!("could not open the SQLite state {}: {}", db.display(), e)),
+        };
     }
+    Ok(Box::new(JsonStore::new(path)))
+}
+
+// For callers with no way to hand the error back. main checks the store
+// opens before doing anything, so this is only reached if it breaks while running.
+pub fn store(path: &Path) -> Box<dyn StateStore> {
+    open_store(path).unwrap_or_else(|e| {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    })
 }
 
+pub fn read_state(state_file: &Path) -> HashMap<String, ProcessInfo> {
+    store(state_file).processes()
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn register_process(
     state_file: &Path,
     pid: u32,
     cmd_str: &str,
+    argv: &[String],
     timeout_sec: Option<f64>,
     log_file: &Path,
     script_name: &str,
     working_dir: &str,
     display_name: &str,
+    options: &ProcessOptions,
+    restarts: u32,
 ) {
-    // Atomic update: Read -> Modify -> Write under exclusive lock
-    if let Ok(mut file) = OpenOptions::new().read(true).write(true).create(true).open(state_file) {
-        if file.lock_exclusive().is_ok() {
-             // Read
-            let _ = file.seek(SeekFrom::Start(0));
-            let mut state: HashMap<String, ProcessInfo> = if file.metadata().unwrap().len() > 0 {
-                serde_json::from_reader(BufReader::new(&file)).unwrap_or_default()
-            } else {
-                HashMap::new()
-            };
-
-            // Modify
-            state.insert(
-                pid.to_string(),
-                ProcessInfo {
-                    cmd_str: cmd_str.to_string(),
-                    log_file: log_file.to_string_lossy().to_string(),
-                    status: "running".to_string(),
-                    start_time: SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs_f64(),
-                    timeout_sec,
-                    script_name: script_name.to_string(),
-                    working_dir: working_dir.to_string(),
-                    display_name: display_name.to_string(),
-                },
-            );
-
-            // Write
-            let _ = file.seek(SeekFrom::Start(0));
-            let _ = file.set_len(0);
-            let _ = serde_json::to_writer_pretty(&file, &state);
-            
-            let _ = file.unlock();
-        }
-    }
+    update_state(state_file, |state| {
+        state.insert(
+            pid.to_string(),
+            ProcessInfo {
+                cmd_str: cmd_str.to_string(),
+                argv: argv.to_vec(),
+                log_file: log_file.to_string_lossy().to_string(),
+                status: "running".to_string(),
+                start_time: SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs_f64(),
+                timeout_sec,
+                script_name: script_name.to_string(),
+                working_dir: working_dir.to_string(),
+                display_name: display_name.to_string(),
+                options: options.clone(),
+                exit_code: None,
+                restarts,
+                start_ticks: process::start_ticks(pid),
+                boot_id: process::boot_id(),
+                stopping_since: None,
+            },
+        );
+    });
+    events::record(state_file, "spawned", &pid.to_string(), display_name, Some(cmd_str.to_string()));
 }
 
 pub fn reap_processes(state_file: &Path) -> Vec<(String, ProcessInfo)> {
-    // Atomic update: Read -> Modify -> Write under exclusive lock
     let mut procs = Vec::new();
+    let mut events = Vec::new();
+    let mut timed_out = Vec::new();
+    let mut stopping = Vec::new();
 
-    if let Ok(mut file) = OpenOptions::new().read(true).write(true).create(true).open(state_file) {
-        if file.lock_exclusive().is_ok() {
-             // Read
-            let _ = file.seek(SeekFrom::Start(0));
-            let mut state: HashMap<String, ProcessInfo> = if file.metadata().unwrap().len() > 0 {
-                serde_json::from_reader(BufReader::new(&file)).unwrap_or_default()
-            } else {
-                HashMap::new()
-            };
-
-            let mut sys = System::new();
-            sys.refresh_all(); // Initial refresh
-            let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs_f64();
-
-            for (pid_str, proc) in state.iter_mut() {
-                if proc.status != "running" {
-                    continue;
-                }
+    update_state(state_file, |state| {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs_f64();
+        // A stop takes at most stop_timeout plus a moment for SIGKILL to land
+        let stale_after = config::settings().stop_timeout_secs() as f64 + 10.0;
 
-                let pid: u32 = pid_str.parse().unwrap_or(0);
-                
-                // Direct existence check
-                let exists = unsafe { libc::kill(pid as i32, 0) == 0 };
+        for (pid_str, proc) in state.iter_mut() {
+            let stale_stop = proc.status == process::STOPPING
+                && proc.stopping_since.is_none_or(|since| now - since > stale_after);
+            if proc.status != "running" && !stale_stop {
+                continue;
+            }
+
+            let pid: u32 = pid_str.parse().unwrap_or(0);
                 
-                let is_alive = if !exists {
-                    false
+            // Direct existence check
+            let exists = unsafe { libc::kill(pid as i32, 0) == 0 };
+            // A live PID that isn't the process we started (reboot or wraparound) means ours is gone
+            let reused = exists && !process::is_same_process(pid, proc);
+
+            // A zombie still counts as alive: its supervisor is about to record the exit status
+            // and reap it (see process::supervise)
+            let is_alive = exists && !reused;
+
+            // Whoever was stopping it died before finishing: keep it if it survived, otherwise
+            // record it as killed
+            if stale_stop {
+                if is_alive {
+                    proc.status = "running".to_string();
                 } else {
-                    // It exists, check if it's a zombie. 
-                    #[cfg(target_os = "linux")]
-                    {
-                        if let Ok(status) = std::fs::read_to_string(format!("/proc/{}/status", pid)) {
-                            if let Some(state_line) = status.lines().find(|l| l.starts_with("State:")) {
-                                let state_part = state_line.split_whitespace().nth(1).unwrap_or("");
-                                // Z = Zombie, X = Dead, t = Tracing stop
-                                !(state_part.starts_with('Z') || state_part.starts_with('X'))
-                            } else {
-                                true 
-                            }
-                        } else {
-                            true // If we can't read /proc but kill(0) succeeded, assume alive
-                        }
-                    }
-                    #[cfg(not(target_os = "linux"))]
-                    {
-                        let sys_pid = Pid::from_u32(pid);
-                        sys.refresh_processes(sysinfo::ProcessesToUpdate::Some(&[sys_pid]), true);
-                        sys.process(sys_pid).map(|p| p.status() != sysinfo::ProcessStatus::Zombie).unwrap_or(false)
-                    }
-                };
-
-                if let Some(timeout) = proc.timeout_sec {
-                    if is_alive && (now - proc.start_time) > timeout {
-                        unsafe { libc::kill(-(pid as i32), libc::SIGKILL); }
-                        std::thread::sleep(std::time::Duration::from_millis(200)); 
-                        proc.status = "killed (timeout)".to_string();
-                        if let Ok(mut f) = std::fs::OpenOptions::new().append(true).open(&proc.log_file) {
-                            use std::io::Write;
-                            writeln!(f, "\n--- ❌ Timeout ({}s) reached. Killed by manager. ---", timeout).ok();
-                        }
-                    }
+                    proc.status = "killed (stop interrupted)".to_string();
+                    events::record(state_file, "killed", pid_str, &proc.display_name, Some("stop interrupted".to_string()));
+                    continue;
                 }
+            }
 
-                if !is_alive && proc.status == "running" {
-                    // println!("DEBUG: reap_processes: PID {} marked as completed (is_alive=false)", pid_str);
-                    proc.status = "completed".to_string();
-                }
+            // Stopped below once the lock is released
+            if let Some(timeout) = proc.timeout_sec
+                && is_alive && (now - proc.start_time) > timeout {
+                timed_out.push(pid_str.clone());
+                continue;
             }
-            
-            // Capture result before writing
-            procs = state.iter().map(|(k, v)| (k.clone(), v.clone())).collect();
 
-            // Write
-            let _ = file.seek(SeekFrom::Start(0));
-            let _ = file.set_len(0);
-            let _ = serde_json::to_writer_pretty(&file, &state);
+            if !is_alive {
+                // println!("DEBUG: reap_processes: PID {} marked as completed (is_alive=false)", pid_str);
+                proc.status = if reused { "lost (PID reused)" } else { "completed" }.to_string();
+                events.extend(hooks::exit_events(pid_str, proc));
+                events::record(state_file, "exited", pid_str, &proc.display_name, reused.then(|| "PID now belongs to another process".to_string()));
+            }
+        }
             
-            let _ = file.unlock();
+        stopping = process::mark_stopping(state, &timed_out);
+        // Capture result before writing
+        procs = state.iter().map(|(k, v)| (k.clone(), v.clone())).collect();
+    });
+
+    if !stopping.is_empty() {
+        process::stop_all(&stopping);
+        for entry in &stopping {
+            let (pid_str, proc) = entry;
+            let timeout = proc.timeout_sec.unwrap_or_default();
+            if let Ok(mut f) = std::fs::OpenOptions::new().append(true).open(&proc.log_file) {
+                writeln!(f, "\n--- ❌ Timeout ({}s) reached. Killed by manager. ---", timeout).ok();
+            }
+            process::finish_stopping(state_file, std::slice::from_ref(entry), "killed (timeout)", &format!("timeout ({}s)", timeout));
+            let mut proc = proc.clone();
+            proc.status = "killed (timeout)".to_string();
+            events.push(HookEvent::new("timeout", pid_str, &proc, Some(format!("timeout of {}s reached", timeout))));
+            if let Some((_, listed)) = procs.iter_mut().find(|(pid, _)| pid == pid_str) {
+                *listed = proc;
+            }
         }
     }
-    
+
+    // Hooks run after the lock is released so they can't stall other writers
+    hooks::fire(state_file, events);
+
     procs.sort_by(|a, b| b.1.start_time.partial_cmp(&a.1.start_time).unwrap());
     procs
 }
 
-pub fn read_scheduled_jobs(path: &Path) -> Vec<crate::types::ScheduledJob> {
-     // Shared lock for reading
-    if !path.exists() {
-        return Vec::new();
-    }
-    if let Ok(file) = OpenOptions::new().read(true).open(path) {
-        if file.lock_shared().is_ok() {
-             let reader = BufReader::new(&file);
-             let jobs = serde_json::from_reader(reader).unwrap_or_default();
-             let _ = file.unlock();
-             return jobs;
+// Records exit statuses collected by a process's supervisor and marks it completed
+pub fn record_exits(state_file: &Path, exits: &[(u32, i32)]) {
+    let mut events = Vec::new();
+    update_state(state_file, |state| {
+        for (pid, code) in exits {
+            let pid_str = pid.to_string();
+            if let Some(proc) = state.get_mut(&pid_str)
+                && proc.status == "running"
+            {
+                proc.exit_code = Some(*code);
+                proc.status = "completed".to_string();
+                events.extend(hooks::exit_events(&pid_str, proc));
+                events::record(state_file, "exited", &pid_str, &proc.display_name, Some(format!("exit code {}", code)));
+            }
         }
-    }
-    Vec::new()
+    });
+    hooks::fire(state_file, events);
 }
 
-pub fn write_scheduled_jobs(path: &Path, jobs: &Vec<crate::types::ScheduledJob>) {
-    // Exclusive lock for writing
-    if let Ok(mut file) = OpenOptions::new().write(true).create(true).truncate(true).open(path) {
-        if file.lock_exclusive().is_ok() {
-             let _ = serde_json::to_writer_pretty(&file, jobs);
-             let _ = file.unlock();
-        }
-    }
+pub fn read_scheduled_jobs(path: &Path) -> Vec<ScheduledJob> {
+    store(path).jobs()
 }
 
-pub fn update_state<F>(state_file: &Path, f: F) 
+// Read-modify-write under the lock, so the daemon recording a run can't undo a concurrent stop or remove
+pub fn update_scheduled_jobs<F>(path: &Path, f: F)
+where F: FnOnce(&mut Vec<ScheduledJob>)
+{
+    let mut f = Some(f);
+    store(path).update_jobs(&mut |j| if let Some(f) = f.take() { f(j) });
+}
+
+pub fn update_state<F>(state_file: &Path, f: F)
 where F: FnOnce(&mut HashMap<String, ProcessInfo>)
 {
-    if let Ok(mut file) = OpenOptions::new().read(true).write(true).create(true).open(state_file) {
-        if file.lock_exclusive().is_ok() {
-             let _ = file.seek(SeekFrom::Start(0));
-             let mut state: HashMap<String, ProcessInfo> = if file.metadata().unwrap().len() > 0 {
-                 serde_json::from_reader(BufReader::new(&file)).unwrap_or_default()
-             } else {
-                 HashMap::new()
-             };
+    let mut f = Some(f);
+    store(state_file).update_processes(&mut |s| if let Some(f) = f.take() { f(s) });
+}
+
+// Validates a data file and its backup for `ksai_proc doctor`; returns the number of problems left
+pub fn check_file<T: Versioned>(path: &Path, repair: bool) -> usize {
+    let mut problems = 0;
+    match load_file::<T>(path) {
+        Ok(_) => println!("  [ok]      {}", path.display()),
+        Err(e @ LoadError::TooNew(_)) => {
+            println!("  [newer]   {}: {}", path.display(), e);
+            problems += 1;
+        }
+        Err(e) if repair => {
+            println!("  [repair]  {} is corrupt: {}", path.display(), e);
+            if let Some(guard) = lock(path, true) {
+                let _ = load_locked::<T>(path);
+                let _ = guard.unlock();
+            }
+        }
+        Err(e) => {
+            println!("  [corrupt] {}: {}", path.display(), e);
+            problems += 1;
+        }
+    }
 
-             f(&mut state);
+    let bak = backup_file(path);
+    if bak.exists() {
+        match load_file::<T>(&bak) {
+            Ok(_) => println!("  [ok]      {}", bak.display()),
+            Err(e) => println!("  [warn]    backup {} is unreadable: {}", bak.display(), e),
+        }
+    }
 
-             let _ = file.seek(SeekFrom::Start(0));
-             let _ = file.set_len(0);
-             let _ = serde_json::to_writer_pretty(&file, &state);
-             
-             let _ = file.unlock();
+    // Left behind by a crash between writing and renaming
+    let tmp = tmp_file(path);
+    if tmp.exists() {
+        if repair {
+            println!("  [repair]  removing leftover {}", tmp.display());
+            let _ = fs::remove_file(&tmp);
+        } else {
+            println!("  [warn]    leftover {}", tmp.display());
         }
     }
-}
\ No newline at end of file
+    problems
+}
+
+// Prints the migrations a data file needs and applies them unless `check_only`; returns how many there were
+pub fn migrate_file<T: Versioned>(path: &Path, check_only: bool) -> Result<usize, LoadError> {
+    let Some(migrated) = pending_migrations::<T>(path)? else {
+        println!("  {}: missing or empty, nothing to migrate", path.display());
+        return Ok(0);
+    };
+    if migrated.changes.is_empty() {
+        println!("  {}: already at schema version {}", path.display(), schema::SCHEMA_VERSION);
+        return Ok(0);
+    }
+    println!("  {}: schema version {} -> {}", path.display(), migrated.from_version, schema::SCHEMA_VERSION);
+    for change in &migrated.changes {
+        println!("    - {}", change);
+    }
+    if !check_only {
+        // Loading migrates in memory; writing it back persists the new version
+        update_json(path, |_: &mut T| {});
+    }
+    Ok(migrated.changes.len())
+}
@@ -0,0 +1,167 @@
+use std::{collections::{BTreeMap, HashMap}, fs::{self, OpenOptions}, io::{BufRead, BufReader, Write}, path::{Path, PathBuf}, time::{SystemTime, UNIX_EPOCH}};
+use serde::{Deserialize, Serialize};
+use crate::{monitor::Monitor, types::ProcessInfo};
+
+// How often the daemon records a sample for each running process
+pub const SAMPLE_INTERVAL_SECS: u64 = 5;
+// Samples newer than this are kept at full resolution
+const RAW_WINDOW_SECS: u64 = 3600;
+// Older samples are averaged into buckets of this size
+const BUCKET_SECS: u64 = 60;
+// Anything older than this is dropped
+const RETENTION_SECS: u64 = 7 * 86400;
+// How often each history file is compacted
+const COMPACT_INTERVAL_SECS: u64 = 600;
+// How often every history file is compacted, including those of processes that no longer run
+const SWEEP_INTERVAL_SECS: u64 = 3600;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct StatSample {
+    pub ts: u64,
+    pub cpu: f32,
+    pub mem: u64,
+}
+
+pub fn stats_dir(log_dir: &Path) -> PathBuf {
+    log_dir.join("stats")
+}
+
+// History is keyed by display name so it survives restarts and revivals (which change the PID).
+// Sanitising can map different names to the same string ("a/b", "a_b"), so a hash of the raw
+// name keeps their files apart.
+pub fn history_file(log_dir: &Path, display_name: &str) -> PathBuf {
+    let clean_name: String = display_name
+        .chars()
+        .map(|c| if c.is_alphanumeric() || c == '_' || c == '-' { c } else { '_' })
+        .collect();
+    stats_dir(log_dir).join(format!("{}-{:08x}.jsonl", clean_name, name_hash(display_name)))
+}
+
+// FNV-1a, which unlike the std hashers is guaranteed to stay the same across Rust releases
+fn name_hash(name: &str) -> u32 {
+    name.bytes().fold(0x811c9dc5, |hash, b| (hash ^ b as u32).wrapping_mul(0x01000193))
+}
+
+pub fn append_sample(path: &Path, sample: &StatSample) {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).ok();
+    }
+    if let Ok(mut f) = OpenOptions::new().create(true).append(true).open(path)
+        && let Ok(line) = serde_json::to_string(sample)
+    {
+        writeln!(f, "{}", line).ok();
+    }
+}
+
+pub fn read_samples(path: &Path, since: u64) -> Vec<StatSample> {
+    let Ok(file) = fs::File::open(path) else {
+        return Vec::new();
+    };
+    BufReader::new(file)
+        .lines()
+        .map_while(Result::ok)
+        // A partially written trailing line is skipped rather than failing the whole read
+        .filter_map(|l| serde_json::from_str::<StatSample>(&l).ok())
+        .filter(|s| s.ts >= since)
+        .collect()
+}
+
+// Keeps the last hour at full resolution, averages older samples into 1-minute buckets
+// and drops everything past the retention window. Safe to run repeatedly.
+pub fn compact(path: &Path, now: u64) {
+    let samples = read_samples(path, now.saturating_sub(RETENTION_SECS));
+    let raw_cutoff = now.saturating_sub(RAW_WINDOW_SECS);
+
+    let mut buckets: BTreeMap<u64, (f64, u64, u64)> = BTreeMap::new();
+    let mut recent = Vec::new();
+    for s in samples {
+        if s.ts >= raw_cutoff {
+            recent.push(s);
+        } else {
+            let entry = buckets.entry(s.ts - s.ts % BUCKET_SECS).or_insert((0.0, 0, 0));
+            entry.0 += s.cpu as f64;
+            entry.1 += s.mem;
+            entry.2 += 1;
+        }
+    }
+
+    let tmp = path.with_extension("jsonl.tmp");
+    if let Ok(mut f) = fs::File::create(&tmp) {
+        let downsampled = buckets.into_iter().map(|(ts, (cpu, mem, n))| StatSample {
+            ts,
+            cpu: (cpu / n as f64) as f32,
+            mem: mem / n,
+        });
+        for s in downsampled.chain(recent) {
+            if let Ok(line) = serde_json::to_string(&s) {
+                writeln!(f, "{}", line).ok();
+            }
+        }
+        fs::rename(&tmp, path).ok();
+    }
+}
+
+// Records the scheduler daemon's Monitor samples into per-process history files
+pub struct StatsRecorder {
+    log_dir: PathBuf,
+    last_sample: u64,
+    last_compact: HashMap<PathBuf, u64>,
+    last_sweep: u64,
+}
+
+impl StatsRecorder {
+    pub fn new(log_dir: &Path) -> Self {
+        Self {
+            log_dir: log_dir.to_path_buf(),
+            last_sample: 0,
+            last_compact: HashMap::new(),
+            last_sweep: 0,
+        }
+    }
+
+    pub fn tick(&mut self, monitor: &Monitor, processes: &HashMap<String, ProcessInfo>) {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+        if now < self.last_sample + SAMPLE_INTERVAL_SECS {
+            return;
+        }
+        self.last_sample = now;
+
+        let running = processes
+            .iter()
+            .filter(|(_, p)| p.status == "running")
+            .filter_map(|(pid_s, p)| pid_s.parse::<u32>().ok().map(|pid| (pid, p)));
+
+        for (pid, proc) in running {
+            let Some(stats) = monitor.get_stats(pid) else {
+                continue;
+            };
+            let path = history_file(&self.log_dir, &proc.display_name);
+            append_sample(&path, &StatSample { ts: now, cpu: stats.cpu_usage, mem: stats.mem_usage });
+
+            let last = self.last_compact.entry(path.clone()).or_insert(now);
+            if now >= *last + COMPACT_INTERVAL_SECS {
+                compact(&path, now);
+                *last = now;
+            }
+        }
+
+        if now >= self.last_sweep + SWEEP_INTERVAL_SECS {
+            self.sweep(now);
+            self.last_sweep = now;
+        }
+    }
+
+    // Applies the retention to every history file and deletes those with nothing left in it
+    fn sweep(&mut self, now: u64) {
+        let Ok(entries) = fs::read_dir(stats_dir(&self.log_dir)) else {
+            return;
+        };
+        for path in entries.flatten().map(|e| e.path()).filter(|p| p.extension().is_some_and(|e| e == "jsonl")) {
+            compact(&path, now);
+            if fs::metadata(&path).is_ok_and(|m| m.len() == 0) {
+                fs::remove_file(&path).ok();
+                self.last_compact.remove(&path);
+            }
+        }
+    }
+}
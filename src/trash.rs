@@ -0,0 +1,79 @@
+use std::{fs, io, path::{Path, PathBuf}, time::{SystemTime, UNIX_EPOCH}};
+use crate::config;
+
+// Deleted logs are kept here as <unix time>-<file name> until logs.trash_retention runs out
+pub fn trash_dir(log_dir: &Path) -> PathBuf {
+    log_dir.join("trash")
+}
+
+pub struct TrashEntry {
+    pub name: String,
+    pub original: String,
+    pub deleted_at: u64,
+    pub size: u64,
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+// Moves a log into the trash, or deletes it outright when the trash is disabled.
+// The log is copied rather than renamed because a running process keeps appending to the
+// file it has open, which would otherwise keep growing inside the trash.
+pub fn discard(log_dir: &Path, log_file: &Path) -> io::Result<()> {
+    if config::settings().trash_retention_secs() == 0 {
+        return fs::remove_file(log_file);
+    }
+    let file_name = log_file.file_name().ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "not a file"))?;
+    let dir = trash_dir(log_dir);
+    fs::create_dir_all(&dir)?;
+    fs::copy(log_file, dir.join(format!("{}-{}", now(), file_name.to_string_lossy())))?;
+    fs::remove_file(log_file)
+}
+
+// Newest first
+pub fn list(log_dir: &Path) -> Vec<TrashEntry> {
+    let Ok(entries) = fs::read_dir(trash_dir(log_dir)) else {
+        return Vec::new();
+    };
+    let mut list: Vec<TrashEntry> = entries
+        .flatten()
+        .filter_map(|e| {
+            let name = e.file_name().to_string_lossy().to_string();
+            let (ts, original) = name.split_once('-')?;
+            Some(TrashEntry {
+                deleted_at: ts.parse().ok()?,
+                original: original.to_string(),
+                size: e.metadata().map(|m| m.len()).unwrap_or(0),
+                name,
+            })
+        })
+        .collect();
+    list.sort_by(|a, b| b.deleted_at.cmp(&a.deleted_at).then_with(|| a.name.cmp(&b.name)));
+    list
+}
+
+// Accepts the trash entry name or the original file name (the newest copy wins).
+// Returns where the log was restored to.
+pub fn restore(log_dir: &Path, name: &str) -> Result<PathBuf, String> {
+    let entry = list(log_dir)
+        .into_iter()
+        .find(|e| e.name == name || e.original == name)
+        .ok_or_else(|| format!("'{}' is not in the trash", name))?;
+    let target = log_dir.join(&entry.original);
+    if target.exists() {
+        return Err(format!("{} already exists", target.display()));
+    }
+    fs::rename(trash_dir(log_dir).join(&entry.name), &target).map_err(|e| e.to_string())?;
+    Ok(target)
+}
+
+// Deletes entries older than the retention, or everything when max_age is None
+pub fn purge(log_dir: &Path, max_age: Option<u64>) -> usize {
+    let cutoff = max_age.map(|age| now().saturating_sub(age));
+    list(log_dir)
+        .into_iter()
+        .filter(|e| cutoff.is_none_or(|c| e.deleted_at < c))
+        .filter(|e| fs::remove_file(trash_dir(log_dir).join(&e.name)).is_ok())
+        .count()
+}
@@ -1,37 +1,161 @@
+use std::collections::BTreeMap;
 use serde::{Deserialize, Serialize};
+use crate::hooks::Hooks;
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct ProcessInfo {
     pub cmd_str: String,
+    // The command and its arguments as spawned; entries from older versions only have cmd_str
+    #[serde(default)]
+    pub argv: Vec<String>,
     pub log_file: String,
     pub status: String,
     pub start_time: f64,
+    #[serde(default)]
     pub timeout_sec: Option<f64>,
+    #[serde(default)]
     pub script_name: String,
+    #[serde(default)]
     pub working_dir: String,
+    #[serde(default)]
     pub display_name: String,
+    #[serde(default)]
+    pub options: ProcessOptions,
+    #[serde(default)]
+    pub exit_code: Option<i32>, // recorded by the process's supervisor; None if it was stopped or the supervisor died
+    // How many times the process was restarted or revived under this name
+    #[serde(default)]
+    pub restarts: u32,
+    // Identify the process behind the PID so a reused PID is never mistaken for ours
+    #[serde(default)]
+    pub start_ticks: Option<u64>,
+    #[serde(default)]
+    pub boot_id: Option<String>,
+    // When the process was marked "stopping", so a stop that never finished can be noticed
+    #[serde(default)]
+    pub stopping_since: Option<f64>,
 }
 
-pub struct PendingLaunch {
-    pub script_args: Vec<String>,
-    pub timeout_sec: Option<f64>,
-    pub working_dir: String,
+// Per-process settings that are carried over on restart and revival
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct ProcessOptions {
+    #[serde(default)]
+    pub limits: ResourceLimits,
+    #[serde(default)]
+    pub hooks: Hooks,
+    #[serde(default)]
+    pub restart_policy: RestartPolicy,
+    // Free-form labels for filtering and grouping in the TUI
+    #[serde(default)]
+    pub tags: Vec<String>,
+    // Set on top of the environment the process is launched from
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+}
+
+// What the scheduler daemon does when a process exits on its own
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum RestartPolicy {
+    #[default]
+    Never,
+    // Only when the process exited with a non-zero code
+    OnFailure,
+    Always,
+}
+
+impl RestartPolicy {
+    pub fn should_restart(self, exit_code: Option<i32>) -> bool {
+        match self {
+            RestartPolicy::Never => false,
+            RestartPolicy::OnFailure => exit_code.is_some_and(|c| c != 0),
+            RestartPolicy::Always => true,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default)]
+pub struct ResourceLimits {
+    pub max_memory: Option<u64>, // bytes, applied as RLIMIT_AS
+    pub max_cpu_time: Option<u64>, // seconds, applied as RLIMIT_CPU
+    pub nofile: Option<u64>, // RLIMIT_NOFILE
+    // Soft watchdog thresholds enforced by the scheduler daemon
+    pub watch_rss: Option<u64>, // bytes, across the whole process tree
+    pub watch_cpu: Option<f32>, // percent, across the whole process tree
+    pub watch_cpu_secs: Option<u64>, // how long watch_cpu must be exceeded
+    #[serde(default)]
+    pub watch_action: WatchAction,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum WatchAction {
+    #[default]
+    Kill,
+    Restart,
+    // Only record the event and fire on_unhealthy, leaving the process running
+    Alert,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct ScheduledJob {
     pub name: String,
     pub command: String,
+    #[serde(default)]
     pub args: Vec<String>,
     pub frequency: String, // e.g., "1m", "1h", "1d"
+    #[serde(default)]
     pub start_at: u64, // Unix timestamp
+    #[serde(default)]
     pub working_dir: String,
+    #[serde(default)]
     pub last_run: Option<u64>, // Timestamp of last run
+    #[serde(default = "default_enabled")]
     pub enabled: bool,
+    // Set by `schedule run` and the TUI; the daemon starts the job on its next pass and clears it
+    #[serde(default)]
+    pub run_now: bool,
+}
+
+fn default_enabled() -> bool {
+    true
 }
 
 #[derive(PartialEq)]
 pub enum Mode {
     Navigate,
-    Input,
+    Launch,
+    Templates,
+    JobForm,
+    Search,
+    Filter,
+    Confirm,
+}
+
+// How the log pane arranges the logs it shows
+#[derive(PartialEq, Clone, Copy)]
+pub enum LogLayout {
+    // The selected process
+    Single,
+    // One pane per pinned process
+    Split,
+    // The pinned processes' lines interleaved in one pane
+    Merged,
+}
+
+#[derive(PartialEq, Clone, Copy)]
+pub enum Tab {
+    Processes,
+    Jobs,
+}
+
+// Add/edit form for a scheduled job in the TUI
+pub struct JobForm {
+    pub original: Option<String>, // name of the job being edited, None when adding
+    pub fields: [String; 4], // name, every, start at, command
+    pub focus: usize,
+}
+
+impl JobForm {
+    pub const LABELS: [&'static str; 4] = ["Name", "Every", "Start at", "Command"];
 }
\ No newline at end of file
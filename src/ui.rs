@@ -1,26 +1,42 @@
-use std::{collections::VecDeque, env, fs::{self, File, OpenOptions}, io::{self, BufRead, Write}, os::unix::process::CommandExt, path::Path, process::{Command, Stdio}, time::Duration};
+use std::{env, fs::OpenOptions, io::{self, Write}, path::Path, time::Duration};
 use chrono::Local;
-use crossterm::{event::{self, Event, KeyCode}, execute, terminal::{disable_raw_mode, LeaveAlternateScreen}};
+use crossterm::event::{self, Event, KeyCode, KeyModifiers};
 use ratatui::{
     backend::CrosstermBackend,
     layout::{Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
     text::{Line, Span},
-    widgets::{Block, Borders, List, ListItem, Paragraph, Table, Row, Cell},
+    symbols::Marker,
+    widgets::{Axis, Block, Borders, Chart, Clear, Dataset, GraphType, LineGauge, List, ListItem, ListState, Paragraph, Table, Row, Cell, Wrap},
     Frame, Terminal,
 };
-use crate::{process::*, state::*, types::*, app::App};
+use regex::Regex;
+use crate::{ansi, process::*, state::*, types::*, app::App, config::{self, Action}, events, logview::{LogLines, LogTail, LogView, MergedLog}, proclist::GroupMode, scheduler, stats::{self, StatSample}, trash, launch::{self, HistorySearch, LaunchForm}};
+
+// Height of the selected process's charts in the resource view
+const CHART_HEIGHT: u16 = 10;
+// How much of the end of the event journal the events pane reads
+const EVENT_PANE_BYTES: u64 = 64 * 1024;
+// Logs that can be pinned to the split and merged layouts
+const MAX_PANES: usize = 4;
+// History matches shown at once by the launch form's Ctrl-R search
+const SEARCH_ROWS: usize = 5;
+// Process name colours in the merged log, by pane
+const MERGED_COLORS: [Color; MAX_PANES] = [Color::Cyan, Color::Magenta, Color::Green, Color::Yellow];
 
 pub fn run_app(
     terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
     app: &mut App,
     state_file: &Path,
+    scheduled_file: &Path,
     log_dir: &Path,
 ) -> io::Result<()> {
+    let settings = config::settings();
+    let keys = &settings.tui.keys;
     loop {
-        if app.last_reap.elapsed().unwrap_or_default() > Duration::from_millis(500) {
+        if app.last_reap.elapsed().unwrap_or_default() > Duration::from_millis(settings.tui.reap_interval_ms) {
             app.last_reap = std::time::SystemTime::now();
-            app.processes = reap_processes(state_file);
+            refresh_processes(app, state_file);
             
             // Update monitor
             let pids: Vec<u32> = app.processes.iter()
@@ -29,6 +45,32 @@ pub fn run_app(
                 .collect();
             app.monitor.update(&pids);
 
+            // Recorded history for the sparklines and chart (sampled by the scheduler daemon)
+            let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs();
+            if app.show_resources && now >= app.stats_read_at + stats::SAMPLE_INTERVAL_SECS {
+                app.stats_read_at = now;
+                let since = now.saturating_sub(settings.resource_window_secs());
+                app.stats_history = app.processes.iter()
+                    .map(|(_, proc)| {
+                        let samples = stats::read_samples(&stats::history_file(log_dir, &proc.display_name), since);
+                        (proc.display_name.clone(), samples)
+                    })
+                    .collect();
+            }
+
+            if app.show_events {
+                app.recent_events = events::read_recent_events(state_file, EVENT_PANE_BYTES);
+            }
+
+            if app.tab == Tab::Jobs {
+                app.jobs = read_scheduled_jobs(scheduled_file);
+                app.job_index = app.job_index.min(app.jobs.len().saturating_sub(1));
+            }
+
+            // Forget panes and scroll positions of processes that are gone
+            app.panes.retain(|file| app.processes.iter().any(|(_, p)| &p.log_file == file));
+            app.log_views.retain(|file, _| app.processes.iter().any(|(_, p)| &p.log_file == file));
+
             if app.selected_index >= app.processes.len() && !app.processes.is_empty() {
                 app.selected_index = app.processes.len() - 1;
             }
@@ -36,183 +78,415 @@ pub fn run_app(
 
         terminal.draw(|f| ui(f, app))?;
 
-        if event::poll(Duration::from_millis(250))? {
-            if let Event::Key(key) = event::read()? {
-                if !app.error_message.is_empty() {
-                    app.error_message.clear();
-                }
+        if event::poll(Duration::from_millis(250))?
+            && let Event::Key(key) = event::read()? {
+            if !app.error_message.is_empty() {
+                app.error_message.clear();
+            }
 
-                if app.name_input_mode {
-                    match key.code {
-                        KeyCode::Enter => {
-                            if let Some(pending) = app.pending_launch.take() {
-                                let name = if app.input_buffer.is_empty() {
-                                    None
+            match app.mode {
+                Mode::Navigate => {
+                    if app.is_paused {
+                        if key.code == KeyCode::Char(keys.pause) {
+                            app.is_paused = false;
+                        }
+                        continue;
+                    }
+
+                    if key.code == KeyCode::Tab {
+                        app.tab = match app.tab {
+                            Tab::Processes => Tab::Jobs,
+                            Tab::Jobs => Tab::Processes,
+                        };
+                        app.jobs = read_scheduled_jobs(scheduled_file);
+                        continue;
+                    }
+
+                    let log_pane_shown = app.tab == Tab::Processes && !app.show_resources && !app.show_events;
+                    if key.code == KeyCode::Esc && !app.marked.is_empty() {
+                        app.marked.clear();
+                        continue;
+                    }
+                    if log_pane_shown && let Some((view, tail)) = log_pane(app) {
+                        match key.code {
+                            KeyCode::PageUp => view.page_up(tail),
+                            KeyCode::PageDown => view.page_down(),
+                            KeyCode::Esc => view.clear_search(),
+                            _ => {}
+                        }
+                    }
+
+                    let KeyCode::Char(c) = key.code else { continue };
+
+                    if app.tab == Tab::Jobs {
+                        let selected = app.jobs.get(app.job_index).map(|j| j.name.clone());
+                        match keys.action(c) {
+                            Some(Action::Quit) => return Ok(()),
+                            Some(Action::Pause) => app.is_paused = true,
+                            Some(Action::Down) if !app.jobs.is_empty() => {
+                                app.job_index = (app.job_index + 1).min(app.jobs.len() - 1);
+                            }
+                            Some(Action::Up) if app.job_index > 0 => {
+                                app.job_index -= 1;
+                            }
+                            Some(Action::Open) => {
+                                let fields = [String::new(), settings.scheduler.default_frequency.clone(), "now".to_string(), String::new()];
+                                app.job_form = Some(JobForm { original: None, fields, focus: 0 });
+                                app.mode = Mode::JobForm;
+                            }
+                            Some(Action::Edit) => {
+                                if let Some(job) = app.jobs.get(app.job_index) {
+                                    app.job_form = Some(edit_job_form(job));
+                                    app.mode = Mode::JobForm;
+                                }
+                            }
+                            Some(Action::Toggle) => {
+                                if let Some(name) = selected {
+                                    scheduler::modify_job(scheduled_file, &name, |job| job.enabled = !job.enabled);
+                                }
+                            }
+                            Some(Action::Restart) => {
+                                // The daemon notices the change and starts the job right away
+                                if let Some(name) = selected
+                                    && scheduler::modify_job(scheduled_file, &name, |job| job.run_now = true)
+                                {
+                                    app.error_message = format!("Job '{}' will run now", name);
+                                }
+                            }
+                            Some(Action::Remove) => {
+                                if let Some(name) = selected {
+                                    if settings.confirms(Action::Remove) {
+                                        app.confirm = Some((Action::Remove, vec![name]));
+                                        app.mode = Mode::Confirm;
+                                    } else {
+                                        scheduler::remove_job(scheduled_file, &name);
+                                    }
+                                }
+                            }
+                            _ => {}
+                        }
+                        app.jobs = read_scheduled_jobs(scheduled_file);
+                        app.job_index = app.job_index.min(app.jobs.len().saturating_sub(1));
+                        continue;
+                    }
+
+                    match keys.action(c) {
+                        Some(Action::Quit) => return Ok(()),
+                        Some(Action::Resources) => {
+                            app.show_resources = !app.show_resources;
+                            app.show_events = false;
+                            app.stats_read_at = 0;
+                        }
+                        Some(Action::Events) => {
+                            app.show_events = !app.show_events;
+                            app.show_resources = false;
+                            app.recent_events = events::read_recent_events(state_file, EVENT_PANE_BYTES);
+                        }
+                        Some(Action::Tree) if app.show_resources => app.resource_tree_view = !app.resource_tree_view,
+                        Some(Action::Top) => {
+                            if let Some((view, tail)) = log_pane(app) {
+                                view.top(tail);
+                            }
+                        }
+                        Some(action @ (Action::Bottom | Action::Wrap | Action::Raw)) => {
+                            if let Some((view, _)) = log_pane(app) {
+                                match action {
+                                    Action::Bottom => view.follow(),
+                                    Action::Wrap => view.wrap = !view.wrap,
+                                    _ => view.raw = !view.raw,
+                                }
+                            }
+                        }
+                        Some(Action::Pin) => {
+                            if let Some((_, proc)) = app.processes.get(app.selected_index) {
+                                if let Some(i) = app.panes.iter().position(|file| file == &proc.log_file) {
+                                    app.panes.remove(i);
+                                } else if app.panes.len() < MAX_PANES {
+                                    app.panes.push(proc.log_file.clone());
                                 } else {
-                                    Some(app.input_buffer.clone())
-                                };
-                                
-                                let exe_dir = env::current_exe().unwrap().parent().unwrap().to_path_buf();
-                                if let Err(e) = launch_process_with_name(&exe_dir, state_file, log_dir, &pending.script_args, pending.timeout_sec, name, Some(pending.working_dir)) {
-                                    app.error_message = format!("Launch error: {}", e);
+                                    app.error_message = format!("At most {} panes; unpin one first", MAX_PANES);
                                 }
-                                app.processes = reap_processes(state_file);
                             }
-                            app.name_input_mode = false;
-                            app.input_buffer.clear();
-                            app.mode = Mode::Navigate;
                         }
-                        KeyCode::Esc => {
-                            app.name_input_mode = false;
+                        Some(Action::Filter) => {
+                            app.mode = Mode::Filter;
+                            app.input_buffer = app.list.filter.clone();
+                        }
+                        Some(Action::Sort) => {
+                            app.list.sort = app.list.sort.next();
+                            refresh_processes(app, state_file);
+                        }
+                        Some(Action::HideInactive) => {
+                            app.list.hide_inactive = !app.list.hide_inactive;
+                            refresh_processes(app, state_file);
+                        }
+                        Some(Action::Group) => {
+                            app.list.group = app.list.group.next();
+                            refresh_processes(app, state_file);
+                        }
+                        Some(Action::Layout) => {
+                            app.log_layout = match app.log_layout {
+                                LogLayout::Single => LogLayout::Split,
+                                LogLayout::Split => LogLayout::Merged,
+                                LogLayout::Merged => LogLayout::Single,
+                            };
+                            // Start the merged stream over from what the logs hold now
+                            app.merged = None;
+                        }
+                        Some(Action::Search) => {
+                            app.mode = Mode::Search;
                             app.input_buffer.clear();
-                            app.pending_launch = None;
-                            app.mode = Mode::Navigate;
                         }
-                        KeyCode::Backspace => {
-                            app.input_buffer.pop();
+                        // Searching starts at the bottom and goes up, so "next" is the next older match
+                        Some(action @ (Action::NextMatch | Action::PrevMatch)) => {
+                            if let Some((view, tail)) = log_pane(app)
+                                && view.search.is_some()
+                                && !view.next_match(tail, action == Action::NextMatch)
+                            {
+                                app.error_message = "No more matches".to_string();
+                            }
+                        }
+                        Some(Action::Pause) => app.is_paused = true,
+                        Some(Action::Down) if !app.processes.is_empty() => {
+                            app.selected_index = (app.selected_index + 1).min(app.processes.len() - 1);
+                        }
+                        Some(Action::Up) if app.selected_index > 0 => {
+                            app.selected_index -= 1;
                         }
-                        KeyCode::Char(c) => {
-                            app.input_buffer.push(c);
+                        Some(Action::Open) => {
+                            app.launch_form = Some(LaunchForm::new(launch::read_history(state_file)));
+                            app.mode = Mode::Launch;
+                        }
+                        Some(Action::Templates) => {
+                            app.templates = launch::read_templates(state_file).into_iter().collect();
+                            if app.templates.is_empty() {
+                                app.error_message = format!("No templates yet: save one from the launch form ([{}]) with Ctrl-S", keys.open);
+                            } else {
+                                app.template_index = app.template_index.min(app.templates.len() - 1);
+                                app.mode = Mode::Templates;
+                            }
+                        }
+                        Some(Action::Mark) => {
+                            if let Some((pid_str, _)) = app.processes.get(app.selected_index) {
+                                if !app.marked.remove(pid_str) {
+                                    app.marked.insert(pid_str.clone());
+                                }
+                                app.selected_index = (app.selected_index + 1).min(app.processes.len() - 1);
+                            }
+                        }
+                        Some(Action::MarkAll) => {
+                            if app.processes.iter().all(|(pid, _)| app.marked.contains(pid)) {
+                                app.marked.clear();
+                            } else {
+                                app.marked = app.processes.iter().map(|(pid, _)| pid.clone()).collect();
+                            }
+                        }
+                        Some(action @ (Action::Kill | Action::Restart | Action::Remove | Action::ClearLogs)) => {
+                            let targets = action_targets(app);
+                            if targets.len() > 1 || (!targets.is_empty() && settings.confirms(action)) {
+                                app.confirm = Some((action, targets));
+                                app.mode = Mode::Confirm;
+                            } else {
+                                apply_action(app, state_file, log_dir, action, &targets);
+                            }
                         }
                         _ => {}
                     }
-                    continue;
                 }
+                Mode::Launch => {
+                    let Some(form) = app.launch_form.as_mut() else {
+                        app.mode = Mode::Navigate;
+                        continue;
+                    };
+                    let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
 
-                match app.mode {
-                    Mode::Navigate => {
-                        if app.is_paused {
-                            if key.code == KeyCode::Char('p') {
-                                app.is_paused = false;
+                    // Ctrl-R: pick a past launch by fuzzy search
+                    if let Some(search) = form.search.as_mut() {
+                        match key.code {
+                            KeyCode::Char('r') if ctrl => search.selected += 1,
+                            KeyCode::Down => search.selected += 1,
+                            KeyCode::Up => search.selected = search.selected.saturating_sub(1),
+                            KeyCode::Backspace => {
+                                search.query.pop();
+                                search.selected = 0;
                             }
-                            continue;
+                            KeyCode::Char(c) if !ctrl => {
+                                search.query.push(c);
+                                search.selected = 0;
+                            }
+                            KeyCode::Enter => {
+                                let matches = form.search_matches();
+                                let selected = form.search.take().map(|s| s.selected).unwrap_or(0);
+                                if let Some(&i) = matches.get(selected.min(matches.len().saturating_sub(1))) {
+                                    let spec = form.history[i].clone();
+                                    form.fill(&spec);
+                                }
+                            }
+                            KeyCode::Esc => form.search = None,
+                            _ => {}
                         }
+                        continue;
+                    }
 
-                        match key.code {
-                            KeyCode::Char('q') => return Ok(()),
-                            KeyCode::Char('s') => app.show_resources = !app.show_resources,
-                            KeyCode::Char('p') => app.is_paused = true,
-                            KeyCode::Char('j') => {
-                                if !app.processes.is_empty() {
-                                    app.selected_index = (app.selected_index + 1).min(app.processes.len() - 1);
+                    let count = form.fields.len();
+                    match key.code {
+                        KeyCode::Enter => match form.validate() {
+                            Ok(spec) => match spec.start(state_file, log_dir) {
+                                Ok(_) => {
+                                    launch::record_history(state_file, &spec);
+                                    app.launch_form = None;
+                                    app.mode = Mode::Navigate;
+                                    refresh_processes(app, state_file);
                                 }
+                                Err(e) => form.launch_failed(&e),
+                            },
+                            Err((field, message)) => {
+                                form.focus = field;
+                                form.error = Some((field, message));
                             }
-                            KeyCode::Char('k') => {
-                                if app.selected_index > 0 {
-                                    app.selected_index -= 1;
+                        },
+                        KeyCode::Esc => {
+                            app.launch_form = None;
+                            app.mode = Mode::Navigate;
+                        }
+                        KeyCode::Char('r') if ctrl => form.search = Some(HistorySearch { query: String::new(), selected: 0 }),
+                        // Ctrl-S: keep the form as a template named after the process
+                        KeyCode::Char('s') if ctrl => match form.validate() {
+                            Ok(spec) => match spec.name.clone() {
+                                Some(name) => {
+                                    launch::save_template(state_file, &name, &spec);
+                                    app.error_message = format!("Saved template '{}' ([{}] lists templates)", name, keys.templates);
+                                    app.launch_form = None;
+                                    app.mode = Mode::Navigate;
                                 }
-                            }
-                            KeyCode::Char('o') => {
-                                app.mode = Mode::Input;
-                                app.input_buffer.clear();
-                            }
-                            KeyCode::Char('x') => {
-                                if let Some((pid_str, proc)) = app.processes.get(app.selected_index) {
-                                    if proc.status == "running" {
-                                        let pid: i32 = pid_str.parse().unwrap_or(0);
-                                        unsafe { libc::kill(-pid, libc::SIGKILL); }
-                                        std::thread::sleep(std::time::Duration::from_millis(200));
-                                        let mut state = read_state(state_file);
-                                        if let Some(p) = state.get_mut(pid_str) {
-                                            p.status = "killed (manual)".to_string();
-                                        }
-                                        write_state(state_file, &state);
-                                        app.processes = reap_processes(state_file);
-                                    }
+                                None => {
+                                    form.focus = 1;
+                                    form.error = Some((1, "A template needs a name".to_string()));
                                 }
+                            },
+                            Err((field, message)) => {
+                                form.focus = field;
+                                form.error = Some((field, message));
                             }
-                            KeyCode::Char('c') => {
-                                if let Some((_, proc)) = app.processes.get(app.selected_index) {
-                                    let log_file = proc.log_file.clone();
-                                    app.log_readers.remove(&log_file);
-                                    app.log_cache.remove(&log_file);
-                                    match fs::remove_file(&log_file) {
-                                        Ok(_) => app.error_message = format!("Deleted log: {}", Path::new(&log_file).file_name().unwrap().to_string_lossy()),
-                                        Err(e) => app.error_message = format!("Error deleting log: {}", e),
-                                    }
-                                }
+                        },
+                        KeyCode::Up | KeyCode::Down if form.focus == 0 && !form.history.is_empty() => {
+                            form.step_history(key.code == KeyCode::Up);
+                        }
+                        KeyCode::Tab if form.focus == LaunchForm::DIRECTORY && form.complete() => {}
+                        KeyCode::Tab | KeyCode::Down => {
+                            form.focus = (form.focus + 1) % count;
+                            form.completions.clear();
+                        }
+                        KeyCode::BackTab | KeyCode::Up => {
+                            form.focus = (form.focus + count - 1) % count;
+                            form.completions.clear();
+                        }
+                        KeyCode::Backspace => {
+                            form.fields[form.focus].pop();
+                        }
+                        KeyCode::Char(c) if !ctrl => form.fields[form.focus].push(c),
+                        _ => {}
+                    }
+                }
+                Mode::Templates => {
+                    let chosen = match key.code {
+                        KeyCode::Char(c @ '1'..='9') => Some(c as usize - '1' as usize),
+                        KeyCode::Enter => Some(app.template_index),
+                        KeyCode::Down => {
+                            app.template_index = (app.template_index + 1).min(app.templates.len().saturating_sub(1));
+                            None
+                        }
+                        KeyCode::Up => {
+                            app.template_index = app.template_index.saturating_sub(1);
+                            None
+                        }
+                        // Opens the template in the launch form to change it before launching
+                        KeyCode::Char(c) if c == keys.edit => {
+                            if let Some((_, spec)) = app.templates.get(app.template_index) {
+                                let mut form = LaunchForm::new(launch::read_history(state_file));
+                                form.fill(spec);
+                                app.launch_form = Some(form);
+                                app.mode = Mode::Launch;
                             }
-                            KeyCode::Char('R') => {
-                                if let Some((pid_str, proc)) = app.processes.get(app.selected_index).cloned() {
-                                    let old_pid: i32 = pid_str.parse().unwrap_or(0);
-
-                                    if proc.status == "running" {
-                                        unsafe { libc::kill(-old_pid, libc::SIGKILL); }
-                                        std::thread::sleep(std::time::Duration::from_millis(200));
-                                    }
-
-                                    let mut state = read_state(state_file);
-                                    state.remove(&pid_str);
-                                    write_state(state_file, &state);
-
-                                    if let Ok(mut f) = OpenOptions::new().append(true).open(&proc.log_file) {
-                                        writeln!(f, "\n--- 🔄 RESTARTED (PID {}) @ {} ---", old_pid, Local::now()).ok();
-                                    }
-
-                                    let parts: Vec<&str> = proc.cmd_str.split_whitespace().collect();
-                                    if !parts.is_empty() {
-                                        let log_handle = OpenOptions::new().create(true).append(true).open(&proc.log_file).unwrap();
-
-                                        if let Ok(child) = unsafe {
-                                            Command::new(parts[0])
-                                                .args(&parts[1..])
-                                                .current_dir(&proc.working_dir)
-                                                .stdout(Stdio::from(log_handle.try_clone().unwrap()))
-                                                .stderr(Stdio::from(log_handle))
-                                                .pre_exec(|| {
-                                                    libc::setsid();
-                                                    Ok(())
-                                                })
-                                                .spawn()
-                                        } {
-                                            register_process(state_file, child.id(), &proc.cmd_str, proc.timeout_sec, Path::new(&proc.log_file), &proc.script_name, &proc.working_dir, &proc.display_name);
-                                        }
-                                    }
-
-                                    app.processes = reap_processes(state_file);
-                                    app.selected_index = 0;
-                                }
+                            None
+                        }
+                        KeyCode::Char(c) if c == keys.down => {
+                            app.template_index = (app.template_index + 1).min(app.templates.len().saturating_sub(1));
+                            None
+                        }
+                        KeyCode::Char(c) if c == keys.up => {
+                            app.template_index = app.template_index.saturating_sub(1);
+                            None
+                        }
+                        KeyCode::Esc => {
+                            app.mode = Mode::Navigate;
+                            None
+                        }
+                        KeyCode::Char(c) if c == keys.quit => {
+                            app.mode = Mode::Navigate;
+                            None
+                        }
+                        _ => None,
+                    };
+                    if let Some((name, spec)) = chosen.and_then(|i| app.templates.get(i)).cloned() {
+                        app.mode = Mode::Navigate;
+                        match spec.start(state_file, log_dir) {
+                            Ok(_) => {
+                                launch::record_history(state_file, &spec);
+                                app.error_message = format!("Launched template '{}'", name);
                             }
-                            KeyCode::Char('X') => {
-                                if let Some((pid_str, proc)) = app.processes.get(app.selected_index).cloned() {
-                                    let pid: i32 = pid_str.parse().unwrap_or(0);
-
-                                    if proc.status == "running" {
-                                        unsafe { libc::kill(-pid, libc::SIGKILL); }
-                                        std::thread::sleep(std::time::Duration::from_millis(200));
-                                    }
-
-                                    app.log_readers.remove(&proc.log_file);
-                                    app.log_cache.remove(&proc.log_file);
-                                    fs::remove_file(&proc.log_file).ok();
-
-                                    let mut state = read_state(state_file);
-                                    state.remove(&pid_str);
-                                    write_state(state_file, &state);
-
-                                    app.processes = reap_processes(state_file);
-                                    if app.selected_index > 0 {
-                                        app.selected_index -= 1;
-                                    }
-                                }
+                            Err(e) => app.error_message = format!("Launch error: {}", e),
+                        }
+                        refresh_processes(app, state_file);
+                    }
+                }
+                Mode::Search => match key.code {
+                    KeyCode::Enter => {
+                        let pattern = std::mem::take(&mut app.input_buffer);
+                        app.mode = Mode::Navigate;
+                        if let Some((view, log)) = log_pane(app) {
+                            if pattern.is_empty() {
+                                view.clear_search();
+                            } else if let Err(e) = view.start_search(&pattern, log) {
+                                app.error_message = e;
                             }
-                            _ => {}
                         }
                     }
-                    Mode::Input => match key.code {
-                        KeyCode::Enter => {
-                            if !app.input_buffer.is_empty() {
-                                let args: Vec<String> = app.input_buffer.split_whitespace().map(|s| s.to_string()).collect();
-                                app.pending_launch = Some(PendingLaunch {
-                                    script_args: args,
-                                    timeout_sec: None,
-                                    working_dir: env::current_dir().unwrap().to_string_lossy().to_string(),
-                                });
-                                app.name_input_mode = true;
-                                app.input_buffer.clear();
-                            } else {
-                                app.mode = Mode::Navigate;
+                    KeyCode::Esc => {
+                        app.mode = Mode::Navigate;
+                        app.input_buffer.clear();
+                    }
+                    KeyCode::Backspace => {
+                        app.input_buffer.pop();
+                    }
+                    KeyCode::Char(c) => {
+                        app.input_buffer.push(c);
+                    }
+                    _ => {}
+                },
+                Mode::Confirm => {
+                    app.mode = Mode::Navigate;
+                    if let Some((action, targets)) = app.confirm.take()
+                        && matches!(key.code, KeyCode::Char('y') | KeyCode::Char('Y'))
+                    {
+                        if app.tab == Tab::Jobs {
+                            for name in &targets {
+                                scheduler::remove_job(scheduled_file, name);
                             }
+                            app.jobs = read_scheduled_jobs(scheduled_file);
+                            app.job_index = app.job_index.min(app.jobs.len().saturating_sub(1));
+                        } else {
+                            apply_action(app, state_file, log_dir, action, &targets);
+                        }
+                    }
+                }
+                // The list follows the filter as it is typed
+                Mode::Filter => {
+                    match key.code {
+                        KeyCode::Enter => {
+                            app.mode = Mode::Navigate;
+                            app.input_buffer.clear();
+                            continue;
                         }
                         KeyCode::Esc => {
                             app.mode = Mode::Navigate;
@@ -221,175 +495,1234 @@ pub fn run_app(
                         KeyCode::Backspace => {
                             app.input_buffer.pop();
                         }
-                        KeyCode::Char(c) => {
-                            app.input_buffer.push(c);
+                        KeyCode::Char(c) => app.input_buffer.push(c),
+                        _ => continue,
+                    }
+                    app.list.filter = app.input_buffer.clone();
+                    refresh_processes(app, state_file);
+                }
+                Mode::JobForm => {
+                    let Some(form) = app.job_form.as_mut() else {
+                        app.mode = Mode::Navigate;
+                        continue;
+                    };
+                    let count = form.fields.len();
+                    match key.code {
+                        KeyCode::Enter => match save_job_form(scheduled_file, form) {
+                            Ok(_) => {
+                                app.job_form = None;
+                                app.mode = Mode::Navigate;
+                                app.jobs = read_scheduled_jobs(scheduled_file);
+                            }
+                            Err(e) => app.error_message = e,
+                        },
+                        KeyCode::Esc => {
+                            app.job_form = None;
+                            app.mode = Mode::Navigate;
                         }
+                        KeyCode::Tab | KeyCode::Down => form.focus = (form.focus + 1) % count,
+                        KeyCode::BackTab | KeyCode::Up => form.focus = (form.focus + count - 1) % count,
+                        KeyCode::Backspace => {
+                            form.fields[form.focus].pop();
+                        }
+                        KeyCode::Char(c) => form.fields[form.focus].push(c),
                         _ => {}
-                    },
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Re-reads the process list and applies the filter, sort and grouping, keeping the same process selected
+fn refresh_processes(app: &mut App, state_file: &Path) {
+    let selected = app.processes.get(app.selected_index).map(|(pid, _)| pid.clone());
+    let all = reap_processes(state_file);
+    app.total_processes = all.len();
+    let mut shown: Vec<_> = all.into_iter().filter(|(_, p)| app.list.matches(p)).collect();
+    app.list.sort(&mut shown, &app.monitor);
+    app.processes = shown;
+    app.marked.retain(|pid| app.processes.iter().any(|(p, _)| p == pid));
+    if let Some(i) = selected.and_then(|pid| app.processes.iter().position(|(p, _)| *p == pid)) {
+        app.selected_index = i;
+    }
+    app.selected_index = app.selected_index.min(app.processes.len().saturating_sub(1));
+}
+
+// The marked processes in list order, or the selected one when nothing is marked
+fn action_targets(app: &App) -> Vec<String> {
+    if app.marked.is_empty() {
+        app.processes.get(app.selected_index).map(|(pid, _)| pid.clone()).into_iter().collect()
+    } else {
+        app.processes.iter().filter(|(pid, _)| app.marked.contains(pid)).map(|(pid, _)| pid.clone()).collect()
+    }
+}
+
+// Stops, restarts, removes or clears the logs of the given processes, then clears the marks
+fn apply_action(app: &mut App, state_file: &Path, log_dir: &Path, action: Action, pids: &[String]) {
+    match action {
+        Action::Kill => {
+            // The list may be up to a reap old, so re-check the status under the lock
+            let mut stopping = Vec::new();
+            update_state(state_file, |state| stopping = mark_stopping(state, pids));
+            stop_all(&stopping);
+            finish_stopping(state_file, &stopping, "killed (manual)", "manual");
+        }
+        Action::Restart => {
+            let mut restarted = None;
+            for (old_pid, proc) in take_processes(state_file, pids) {
+                if proc.status == "running" {
+                    events::record(state_file, "killed", &old_pid, &proc.display_name, Some("restart".to_string()));
+                }
+                if let Ok(mut f) = OpenOptions::new().append(true).open(&proc.log_file) {
+                    writeln!(f, "\n--- 🔄 RESTARTED (PID {}) @ {} ---", old_pid, Local::now()).ok();
+                }
+                match respawn_process(state_file, &proc) {
+                    Ok(pid) => restarted = Some(pid.to_string()),
+                    Err(e) => app.error_message = format!("Restart error: {}", e),
+                }
+            }
+            refresh_processes(app, state_file);
+            // Keep a single restarted process selected under its new PID
+            if pids.len() == 1
+                && let Some(pid) = restarted
+                && let Some(i) = app.processes.iter().position(|(p, _)| *p == pid)
+            {
+                app.selected_index = i;
+            }
+        }
+        Action::Remove => {
+            for (pid_str, proc) in take_processes(state_file, pids) {
+                events::record(state_file, "removed", &pid_str, &proc.display_name, None);
+                app.log_tails.remove(&proc.log_file);
+                app.log_views.remove(&proc.log_file);
+                trash::discard(log_dir, Path::new(&proc.log_file)).ok();
+            }
+            refresh_processes(app, state_file);
+            if pids.len() == 1 && app.selected_index > 0 {
+                app.selected_index -= 1;
+            }
+        }
+        Action::ClearLogs => {
+            let files: Vec<String> = app.processes.iter().filter(|(pid, _)| pids.contains(pid)).map(|(_, p)| p.log_file.clone()).collect();
+            let mut deleted = 0;
+            for log_file in &files {
+                app.log_tails.remove(log_file);
+                app.log_views.remove(log_file);
+                match trash::discard(log_dir, Path::new(log_file)) {
+                    Ok(_) => deleted += 1,
+                    Err(e) => app.error_message = format!("Error deleting log: {}", e),
                 }
             }
+            app.merged = None;
+            if app.error_message.is_empty() {
+                let verb = if config::settings().trash_retention_secs() > 0 { "Moved to the trash" } else { "Deleted" };
+                app.error_message = match files.as_slice() {
+                    [log_file] => format!("{}: {}", verb, Path::new(log_file).file_name().unwrap().to_string_lossy()),
+                    _ => format!("{}: {} logs", verb, deleted),
+                };
+            }
         }
+        _ => {}
+    }
+    app.marked.clear();
+    refresh_processes(app, state_file);
+}
+
+// Takes the processes out of the state, then stops the running ones once the lock is released
+fn take_processes(state_file: &Path, pids: &[String]) -> Vec<(String, ProcessInfo)> {
+    let mut taken: Vec<(String, ProcessInfo)> = Vec::new();
+    update_state(state_file, |state| {
+        taken = pids.iter().filter_map(|pid| state.remove(pid).map(|p| (pid.clone(), p))).collect();
+    });
+    let running: Vec<(String, ProcessInfo)> = taken.iter().filter(|(_, p)| p.status == "running").cloned().collect();
+    stop_all(&running);
+    taken
+}
+
+// The scroll/search state of the focused log pane together with the lines it shows
+fn log_pane(app: &mut App) -> Option<(&mut LogView, &mut dyn LogLines)> {
+    if app.log_layout == LogLayout::Merged {
+        let merged = app.merged.as_mut()?;
+        return Some((&mut app.merged_view, merged));
+    }
+    let file = focused_log(app)?;
+    let tail = app.log_tails.get_mut(&file)?;
+    Some((app.log_views.entry(file).or_insert_with(LogView::new), tail))
+}
+
+// The pinned logs in the split and merged layouts, otherwise the selected process's
+fn shown_logs(app: &App) -> Vec<String> {
+    if app.log_layout == LogLayout::Single || app.panes.is_empty() {
+        app.processes.get(app.selected_index).map(|(_, p)| p.log_file.clone()).into_iter().collect()
+    } else {
+        app.panes.clone()
+    }
+}
+
+// Log keys act on the selected process's pane when it has one, otherwise on the first pane
+fn focused_log(app: &App) -> Option<String> {
+    let shown = shown_logs(app);
+    let selected = app.processes.get(app.selected_index).map(|(_, p)| &p.log_file);
+    shown.iter().find(|file| Some(*file) == selected).or(shown.first()).cloned()
+}
+
+// The form for editing a job, with the command quoted so it splits back into the same arguments
+fn edit_job_form(job: &ScheduledJob) -> JobForm {
+    let command: Vec<String> = std::iter::once(&job.command).chain(&job.args).cloned().collect();
+    let fields = [job.name.clone(), job.frequency.clone(), format_local(job.start_at, "%Y-%m-%d %H:%M:%S"), launch::join_args(&command)];
+    JobForm { original: Some(job.name.clone()), fields, focus: 1 }
+}
+
+// Validates the form and saves it through the same locked update the CLI uses
+fn save_job_form(scheduled_file: &Path, form: &JobForm) -> Result<(), String> {
+    let [name, every, start_at, command] = &form.fields;
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("A job needs a name".to_string());
+    }
+    if scheduler::parse_frequency(every.trim()).is_none() {
+        return Err(format!("Invalid frequency \"{}\" (e.g. 30s, 5m, 1h, 1d)", every.trim()));
     }
+    let Some(start_at) = scheduler::parse_start_at(start_at.trim()) else {
+        return Err("Start at must be 'now', 'YYYY-MM-DD HH:MM:SS' or 'YYYY-MM-DD'".to_string());
+    };
+    let mut parts = launch::split_args(command)?.into_iter();
+    let Some(cmd) = parts.next() else {
+        return Err("A job needs a command".to_string());
+    };
+    // An edited job keeps the directory it was created in
+    let working_dir = match form.original {
+        Some(_) => String::new(),
+        None => env::current_dir().map_err(|e| format!("Can't read the current directory: {}", e))?.to_string_lossy().to_string(),
+    };
+    let job = ScheduledJob {
+        name: name.to_string(),
+        command: cmd,
+        args: parts.collect(),
+        frequency: every.trim().to_string(),
+        start_at,
+        working_dir,
+        last_run: None,
+        enabled: true,
+        run_now: false,
+    };
+    scheduler::save_job(scheduled_file, form.original.as_deref(), job)
+}
+
+fn format_local(timestamp: u64, format: &str) -> String {
+    chrono::DateTime::from_timestamp(timestamp as i64, 0)
+        .map(|d| d.with_timezone(&Local).format(format).to_string())
+        .unwrap_or_else(|| "?".to_string())
 }
 
 pub fn ui(f: &mut Frame, app: &mut App) {
+    let settings = config::settings();
+    let colors = &settings.tui.colors;
     let size = f.area();
+    let main_area = Rect {
+        x: size.x,
+        y: size.y,
+        width: size.width,
+        height: size.height.saturating_sub(2),
+    };
+
+    match app.tab {
+        Tab::Processes => draw_processes(f, app, main_area),
+        Tab::Jobs => draw_jobs(f, app, main_area),
+    }
+    if let Some(form) = &app.job_form {
+        draw_job_form(f, form, main_area);
+    }
+    if let Some(form) = &app.launch_form {
+        draw_launch_form(f, form, main_area);
+    }
+    if app.mode == Mode::Templates {
+        draw_templates(f, app, main_area);
+    }
+    if let Some((action, pids)) = &app.confirm {
+        draw_confirm(f, app, *action, pids, main_area);
+    }
+
+    let footer_area = Rect {
+        x: size.x,
+        y: size.height.saturating_sub(2),
+        width: size.width,
+        height: 2,
+    };
+
+    let footer_text = if app.mode == Mode::Search {
+        format!("/{}", app.input_buffer)
+    } else if app.mode == Mode::Confirm {
+        "[y] confirm | any other key cancels".to_string()
+    } else if app.mode == Mode::Filter {
+        format!("filter: {}_   (name, status:, tag:, cwd: | [Enter] keep | [Esc] clear)", app.input_buffer)
+    } else if app.is_paused {
+        format!("--- PAUSED (Press '{}' to resume) ---", settings.tui.keys.pause)
+    } else if !app.error_message.is_empty() {
+        app.error_message.clone()
+    } else if app.launch_form.as_ref().is_some_and(|f| f.search.is_some()) {
+        "type to search | [Ctrl-R/Down] older match | [Up] newer match | [Enter] use | [Esc] back".to_string()
+    } else if app.mode == Mode::Launch {
+        "[Tab/Down] next field | [Shift-Tab/Up] previous field | [Up/Down] history (in Command) | [Ctrl-R] search history | [Ctrl-S] save as template | [Enter] launch | [Esc] cancel".to_string()
+    } else if app.mode == Mode::Templates {
+        format!("[1-9] launch | [j/k] select | [Enter] launch selected | [{}] edit before launching | [Esc] close", settings.tui.keys.edit)
+    } else if app.mode == Mode::JobForm {
+        "[Tab/Down] next field | [Shift-Tab/Up] previous field | [Enter] save | [Esc] cancel".to_string()
+    } else if app.tab == Tab::Jobs {
+        let k = &settings.tui.keys;
+        format!(
+            "[{}] add | [{}] edit | [{}] enable/disable | [{}] run now | [{}] delete | [{}/{}] navigate | [Tab] processes | [{}] pause | [{}] quit",
+            k.open, k.edit, k.toggle, k.restart, k.remove, k.down, k.up, k.pause, k.quit
+        )
+    } else {
+        let k = &settings.tui.keys;
+        format!(
+            "[{}] open cmd | [{}] kill | [{}] remove | [{}] clear logs | [{}/{}] navigate | [{}] quit | [{}] restart | [{}] pause | [{}] toggle resources | [{}] tree stats | [{}] events | [Tab] jobs | [PgUp/PgDn/{}/{}] scroll log | [{}] search | [{}/{}] next/prev match | [{}] wrap | [{}] raw/ansi | [{}] pin to panes | [{}] layout | [{}] filter | [{}] sort | [{}] hide inactive | [{}] group | [{}] mark | [{}] mark all",
+            k.open, k.kill, k.remove, k.clear_logs, k.down, k.up, k.quit, k.restart, k.pause, k.resources, k.tree, k.events,
+            k.top, k.bottom, k.search, k.next_match, k.prev_match, k.wrap, k.raw, k.pin, k.layout, k.filter, k.sort, k.hide_inactive, k.group, key_name(k.mark), k.mark_all
+        )
+    };
+
+    let footer_style = if !app.error_message.is_empty() {
+        Style::default().fg(config::color(&colors.error)).add_modifier(Modifier::BOLD)
+    } else {
+        Style::default().bg(config::color(&colors.footer_bg)).fg(config::color(&colors.footer_fg))
+    };
+
+    let footer = Paragraph::new(footer_text).style(footer_style).wrap(Wrap { trim: true });
+    f.render_widget(footer, footer_area);
+}
+
+fn draw_processes(f: &mut Frame, app: &mut App, area: Rect) {
+    let settings = config::settings();
+    let colors = &settings.tui.colors;
     let chunks = Layout::default()
         .direction(Direction::Horizontal)
         .constraints([Constraint::Percentage(33), Constraint::Percentage(67)])
-        .split(Rect {
-            x: size.x,
-            y: size.y,
-            width: size.width,
-            height: size.height.saturating_sub(2),
-        });
+        .split(area);
 
-    let items: Vec<ListItem> = app
-        .processes
-        .iter()
-        .enumerate()
-        .map(|(i, (_, proc))| {
-            let status_color = match proc.status.as_str() {
-                "running" => Color::Green,
-                s if s.starts_with("killed") || s.starts_with("stopped") => Color::Yellow,
-                _ => Color::Red,
-            };
+    // Group headings are rows of their own, so the selected row is tracked separately
+    let mut items: Vec<ListItem> = Vec::new();
+    let mut selected_row = None;
+    let mut group = None;
+    for (i, (pid_str, proc)) in app.processes.iter().enumerate() {
+        if app.list.group != GroupMode::None {
+            let key = app.list.group_key(proc);
+            if i == 0 || key != group {
+                let heading = key.clone().unwrap_or_else(|| if app.list.group == GroupMode::Tag { "(untagged)".to_string() } else { "(unknown)".to_string() });
+                items.push(ListItem::new(format!("── {} ", heading)).style(Style::default().add_modifier(Modifier::BOLD)));
+                group = key;
+            }
+        }
 
-            let display_name = format!("[{}] {}", proc.status.to_uppercase(), proc.display_name);
+        let status_color = match proc.status.as_str() {
+            "running" => config::color(&colors.running),
+            s if s.starts_with("killed") || s.starts_with("stopped") || s == STOPPING => config::color(&colors.stopped),
+            _ => config::color(&colors.failed),
+        };
 
-            let style = if i == app.selected_index {
-                Style::default().bg(Color::Blue).fg(Color::White)
-            } else {
-                Style::default().fg(status_color)
-            };
+        let tags: String = proc.options.tags.iter().map(|tag| format!(" #{}", tag)).collect();
+        let pane = app.panes.iter().position(|file| file == &proc.log_file).map(|i| format!("  [{}]", i + 1)).unwrap_or_default();
+        let mark = if app.marked.contains(pid_str) { "● " } else if app.marked.is_empty() { "" } else { "  " };
+        let display_name = format!("{}[{}] {}{}{}", mark, proc.status.to_uppercase(), proc.display_name, tags, pane);
 
-            ListItem::new(display_name).style(style)
-        })
-        .collect();
+        let style = if i == app.selected_index {
+            selected_row = Some(items.len());
+            Style::default().bg(config::color(&colors.selected_bg)).fg(config::color(&colors.selected_fg))
+        } else {
+            Style::default().fg(status_color)
+        };
+
+        items.push(ListItem::new(display_name).style(style));
+    }
+
+    let mut title = " Processes (j/k)".to_string();
+    if app.list.is_filtering() {
+        title.push_str(&format!(" {}/{}", app.processes.len(), app.total_processes));
+    }
+    title.push_str(&format!(" | sort: {}", app.list.sort.label()));
+    match app.list.group {
+        GroupMode::None => {}
+        GroupMode::Tag => title.push_str(" | by tag"),
+        GroupMode::Cwd => title.push_str(" | by cwd"),
+    }
+    if !app.list.filter.trim().is_empty() {
+        title.push_str(&format!(" | filter: {}", app.list.filter.trim()));
+    }
+    if app.list.hide_inactive {
+        title.push_str(" | running only");
+    }
+    if !app.marked.is_empty() {
+        title.push_str(&format!(" | {} marked", app.marked.len()));
+    }
+    title.push_str(" | [Tab] jobs ");
 
-    let list = List::new(items).block(Block::default().borders(Borders::ALL).title(" Processes (j/k) "));
-    f.render_widget(list, chunks[0]);
+    // Scrolls the list so the selection stays in view
+    let mut state = ListState::default().with_selected(selected_row);
+    let list = List::new(items).block(Block::default().borders(Borders::ALL).title(title));
+    f.render_stateful_widget(list, chunks[0], &mut state);
 
     if app.show_resources {
-        let header_cells = ["PID", "Name", "CPU%", "MxCPU", "RAM", "MxRAM", "Disk", "Thrd"]
+        draw_resources(f, app, chunks[1]);
+    } else if app.show_events {
+        let display_height = (chunks[1].height.saturating_sub(2)) as usize;
+        let lines: Vec<Line> = app.recent_events
             .iter()
-            .map(|h| Cell::from(*h).style(Style::default().fg(Color::Yellow)));
-        let header = Row::new(header_cells).height(1).bottom_margin(1);
-
-        let rows = app.processes.iter().map(|(pid_str, proc)| {
-            let pid: u32 = pid_str.parse().unwrap_or(0);
-            if let Some(stats) = app.monitor.get_stats(pid) {
-                let cpu = format!("{:.1}", stats.cpu_usage);
-                let max_cpu = format!("{:.1}", stats.max_cpu);
-                let mem = format!("{:.1}M", stats.mem_usage as f64 / 1024.0 / 1024.0);
-                let max_mem = format!("{:.1}M", stats.max_mem as f64 / 1024.0 / 1024.0);
-                let disk = format!("{}/{}", stats.disk_read / 1024, stats.disk_written / 1024); // KB
-                let threads = format!("{}", stats.thread_count);
-
-                Row::new(vec![
-                    Cell::from(pid_str.as_str()),
-                    Cell::from(proc.display_name.as_str()),
-                    Cell::from(cpu),
-                    Cell::from(max_cpu),
-                    Cell::from(mem),
-                    Cell::from(max_mem),
-                    Cell::from(disk),
-                    Cell::from(threads),
-                ])
-            } else {
-                 Row::new(vec![
-                    Cell::from(pid_str.as_str()),
-                    Cell::from(proc.display_name.as_str()),
-                    Cell::from("-"),
-                    Cell::from("-"),
-                    Cell::from("-"),
-                    Cell::from("-"),
-                    Cell::from("-"),
-                    Cell::from("-"),
-                ])
-            }
-        });
+            .rev()
+            .take(display_height)
+            .rev()
+            .map(|e| {
+                let color = match e.kind.as_str() {
+                    "spawned" | "revived" => Color::Green,
+                    "killed" | "health-changed" => Color::Red,
+                    "scheduled-fired" => Color::Cyan,
+                    _ => Color::Gray,
+                };
+                Line::styled(e.format_line(), Style::default().fg(color))
+            })
+            .collect();
+
+        let paragraph = Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title(" Events (all processes) "));
+        f.render_widget(paragraph, chunks[1]);
+    } else {
+        draw_logs(f, app, chunks[1]);
+    }
+}
 
-        let table = Table::new(rows, [
-            Constraint::Length(6),
-            Constraint::Percentage(20),
-            Constraint::Length(6),
-            Constraint::Length(6),
-            Constraint::Length(8),
-            Constraint::Length(8),
-            Constraint::Percentage(20),
-            Constraint::Length(5),
+// System gauges, the live/max table with recorded history per process, and charts for the selected one
+fn draw_resources(f: &mut Frame, app: &App, area: Rect) {
+    let settings = config::settings();
+    let window = settings.resource_window_secs();
+    let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs();
+    let since = now.saturating_sub(window);
+    let history = |name: &str| app.stats_history.get(name).map(Vec::as_slice).unwrap_or_default();
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(3), Constraint::Min(5), Constraint::Length(CHART_HEIGHT)])
+        .split(area);
+
+    let system = Block::default().borders(Borders::ALL).title(" System ");
+    let gauges = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+        .spacing(2)
+        .split(system.inner(chunks[0]));
+    f.render_widget(system, chunks[0]);
+    let cpu = app.monitor.system_cpu() as f64;
+    let (used, total) = app.monitor.system_memory();
+    let mem = if total == 0 { 0.0 } else { used as f64 / total as f64 * 100.0 };
+    let gauge = |percent: f64, label: String| {
+        let color = if percent >= 90.0 { Color::Red } else if percent >= 70.0 { Color::Yellow } else { Color::Green };
+        LineGauge::default()
+            .ratio((percent / 100.0).clamp(0.0, 1.0))
+            .label(label)
+            .filled_style(Style::default().fg(color))
+            .unfilled_style(Style::default().fg(Color::DarkGray))
+    };
+    f.render_widget(gauge(cpu, format!("CPU {:>5.1}%", cpu)), gauges[0]);
+    f.render_widget(gauge(mem, format!("RAM {}/{}", format_bytes(used), format_bytes(total))), gauges[1]);
+
+    let header_cells = ["PID", "Name", "CPU%", "MxCPU", "CPU hist", "RAM", "MxRAM", "RAM hist", "Up", "Rst", "Disk", "Thrd", "FDs", "Procs"]
+        .iter()
+        .map(|h| Cell::from(*h).style(Style::default().fg(Color::Yellow)));
+    let header = Row::new(header_cells).height(1).bottom_margin(1);
+
+    // The history columns take the room left over, so work out how wide they end up the way Table does
+    let widths = [
+        Constraint::Length(6),
+        Constraint::Min(8),
+        Constraint::Length(6),
+        Constraint::Length(6),
+        Constraint::Fill(1),
+        Constraint::Length(8),
+        Constraint::Length(8),
+        Constraint::Fill(1),
+        Constraint::Length(6),
+        Constraint::Length(3),
+        Constraint::Length(9),
+        Constraint::Length(5),
+        Constraint::Length(5),
+        Constraint::Length(5),
+    ];
+    let columns = Layout::horizontal(widths).spacing(1).split(Block::default().borders(Borders::ALL).inner(chunks[1]));
+    let (cpu_width, mem_width) = (columns[4].width as usize, columns[7].width as usize);
+
+    let rows = app.processes.iter().map(|(pid_str, proc)| {
+        let pid: u32 = pid_str.parse().unwrap_or(0);
+        let stats = if app.resource_tree_view {
+            app.monitor.get_tree_stats(pid)
+        } else {
+            app.monitor.get_stats(pid)
+        };
+        let samples = history(&proc.display_name);
+        let cpu_hist = sparkline(&bucket(samples, since, window, cpu_width, |s| s.cpu as f64));
+        let mem_hist = sparkline(&bucket(samples, since, window, mem_width, |s| s.mem as f64));
+        let uptime = if proc.status == "running" {
+            format_uptime(now.saturating_sub(proc.start_time as u64))
+        } else {
+            "-".to_string()
+        };
+        let restarts = proc.restarts.to_string();
+
+        let live = match stats {
+            Some(stats) => [
+                format!("{:.1}", stats.cpu_usage),
+                format!("{:.1}", stats.max_cpu),
+                format!("{:.1}M", stats.mem_usage as f64 / 1024.0 / 1024.0),
+                format!("{:.1}M", stats.max_mem as f64 / 1024.0 / 1024.0),
+                format!("{}/{}", stats.disk_read / 1024, stats.disk_written / 1024), // KB
+                format!("{}", stats.thread_count),
+                format!("{}", stats.fd_count),
+                format!("{}", stats.process_count),
+            ],
+            None => std::array::from_fn(|_| "-".to_string()),
+        };
+        let [cpu, max_cpu, mem, max_mem, disk, threads, fds, procs] = live;
+
+        Row::new(vec![
+            Cell::from(pid_str.as_str()),
+            Cell::from(proc.display_name.as_str()),
+            Cell::from(cpu),
+            Cell::from(max_cpu),
+            Cell::from(cpu_hist).style(Style::default().fg(Color::Cyan)),
+            Cell::from(mem),
+            Cell::from(max_mem),
+            Cell::from(mem_hist).style(Style::default().fg(Color::Magenta)),
+            Cell::from(uptime),
+            Cell::from(restarts),
+            Cell::from(disk),
+            Cell::from(threads),
+            Cell::from(fds),
+            Cell::from(procs),
         ])
-        .header(header)
-        .block(Block::default().borders(Borders::ALL).title(" Resource Usage (Live/Max) "));
-        f.render_widget(table, chunks[1]);
-
-    } else if app.selected_index < app.processes.len() {
-        let (_, proc) = &app.processes[app.selected_index];
-        let log_file = proc.log_file.clone();
-
-        if !app.log_readers.contains_key(&log_file) {
-            if let Ok(file) = File::open(&log_file) {
-                app.log_readers.insert(log_file.clone(), io::BufReader::new(file));
-                app.log_cache.insert(log_file.clone(), VecDeque::with_capacity(1000));
+    });
+
+    let table = Table::new(rows, widths)
+    .header(header)
+    .block(Block::default().borders(Borders::ALL).title(format!(
+        " Resource Usage (Live/Max, history over {}) | {} [t] ",
+        settings.tui.resource_window,
+        if app.resource_tree_view { "Whole Tree" } else { "Main Process" },
+    )));
+    f.render_widget(table, chunks[1]);
+
+    let charts = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+        .split(chunks[2]);
+    let Some((_, proc)) = app.processes.get(app.selected_index) else {
+        return;
+    };
+    let samples = history(&proc.display_name);
+    if samples.is_empty() {
+        let note = Paragraph::new(format!(
+            "No history for {} in the last {}. The scheduler daemon records a sample every {}s while the process runs.",
+            proc.display_name, settings.tui.resource_window, stats::SAMPLE_INTERVAL_SECS,
+        ))
+        .wrap(Wrap { trim: true })
+        .block(Block::default().borders(Borders::ALL).title(format!(" {} ", proc.display_name)));
+        f.render_widget(note, chunks[2]);
+        return;
+    }
+
+    // Minutes before now on the x axis
+    let point = |s: &StatSample, value: f64| ((s.ts as f64 - now as f64) / 60.0, value);
+    let cpu_data: Vec<(f64, f64)> = samples.iter().map(|s| point(s, s.cpu as f64)).collect();
+    let mem_data: Vec<(f64, f64)> = samples.iter().map(|s| point(s, s.mem as f64 / 1024.0 / 1024.0)).collect();
+    let last = samples.last().unwrap();
+    let cpu_peak = samples.iter().map(|s| s.cpu).fold(0.0, f32::max);
+    let mem_peak = samples.iter().map(|s| s.mem).max().unwrap_or(0);
+
+    f.render_widget(
+        history_chart(&cpu_data, window, Color::Cyan, format!(" CPU {} ({:.1}%, peak {:.1}%) ", proc.display_name, last.cpu, cpu_peak), cpu_peak as f64, "%"),
+        charts[0],
+    );
+    f.render_widget(
+        history_chart(
+            &mem_data,
+            window,
+            Color::Magenta,
+            format!(" RAM {} ({}, peak {}) ", proc.display_name, format_bytes(last.mem), format_bytes(mem_peak)),
+            mem_peak as f64 / 1024.0 / 1024.0,
+            "M",
+        ),
+        charts[1],
+    );
+}
+
+// A line chart of (minutes before now, value) points, with room above the peak
+fn history_chart<'a>(data: &'a [(f64, f64)], window: u64, color: Color, title: String, peak: f64, unit: &str) -> Chart<'a> {
+    let top = peak.max(1.0) * 1.2;
+    let dataset = Dataset::default().marker(Marker::Braille).graph_type(GraphType::Line).style(Style::default().fg(color)).data(data);
+    Chart::new(vec![dataset])
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .x_axis(
+            Axis::default()
+                .bounds([-(window as f64) / 60.0, 0.0])
+                .labels([format!("-{}", config::settings().tui.resource_window), "now".to_string()])
+                .style(Style::default().fg(Color::DarkGray)),
+        )
+        .y_axis(
+            Axis::default()
+                .bounds([0.0, top])
+                .labels([format!("0{}", unit), format!("{:.0}{}", top, unit)])
+                .style(Style::default().fg(Color::DarkGray)),
+        )
+}
+
+// Averages samples into `width` equal slices of the window ending now. Slices without samples stay
+// empty, so time the process wasn't running (or the daemon wasn't sampling) shows as a gap.
+fn bucket(samples: &[StatSample], since: u64, window: u64, width: usize, value: impl Fn(&StatSample) -> f64) -> Vec<Option<f64>> {
+    let mut sums = vec![(0.0, 0); width];
+    if width == 0 {
+        return Vec::new();
+    }
+    for s in samples {
+        let slot = ((s.ts.saturating_sub(since) as u128 * width as u128) / window.max(1) as u128) as usize;
+        let (sum, n) = &mut sums[slot.min(width - 1)];
+        *sum += value(s);
+        *n += 1;
+    }
+    sums.into_iter().map(|(sum, n)| (n > 0).then(|| sum / n as f64)).collect()
+}
+
+// Scaled to the series' own peak, so the shape shows even for small values
+fn sparkline(values: &[Option<f64>]) -> String {
+    const LEVELS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+    let peak = values.iter().flatten().fold(0.0, |a: f64, b| a.max(*b));
+    values
+        .iter()
+        .map(|v| match v {
+            None => ' ',
+            Some(_) if peak <= 0.0 => LEVELS[0],
+            Some(v) => LEVELS[((v / peak) * (LEVELS.len() - 1) as f64).round() as usize],
+        })
+        .collect()
+}
+
+fn format_bytes(bytes: u64) -> String {
+    let mb = bytes as f64 / 1024.0 / 1024.0;
+    if mb >= 1024.0 { format!("{:.1}G", mb / 1024.0) } else { format!("{:.1}M", mb) }
+}
+
+// 45s, 12m30s, 3h05m, 2d04h
+fn format_uptime(secs: u64) -> String {
+    match secs {
+        0..60 => format!("{}s", secs),
+        60..3600 => format!("{}m{:02}s", secs / 60, secs % 60),
+        3600..86400 => format!("{}h{:02}m", secs / 3600, secs % 3600 / 60),
+        _ => format!("{}d{:02}h", secs / 86400, secs % 86400 / 3600),
+    }
+}
+
+// The log pane in the current layout. Every shown log is polled here, once per frame.
+fn draw_logs(f: &mut Frame, app: &mut App, area: Rect) {
+    let settings = config::settings();
+    let cache_lines = settings.tui.log_cache_lines;
+    let shown = shown_logs(app);
+    for file in &shown {
+        if !app.log_tails.contains_key(file)
+            && let Ok(tail) = LogTail::open(Path::new(file), cache_lines) {
+            app.log_tails.insert(file.clone(), tail);
+        }
+    }
+    let name_of = |file: &String| {
+        app.processes.iter().find(|(_, p)| &p.log_file == file).map(|(_, p)| p.display_name.clone()).unwrap_or_default()
+    };
+
+    if app.log_layout == LogLayout::Merged {
+        if app.merged.as_ref().is_none_or(|m| m.files != shown) {
+            app.merged = Some(MergedLog::new(shown.clone(), &app.log_tails, cache_lines));
+            app.merged_view = LogView::new();
+        }
+        let Some(merged) = app.merged.as_mut() else { return };
+        let mut added = 0;
+        for (i, file) in shown.iter().enumerate() {
+            let Some(tail) = app.log_tails.get_mut(file) else { continue };
+            let polled = tail.poll(Path::new(file));
+            // Keep the per-process views in step for when the layout changes back
+            if let Some(view) = app.log_views.get_mut(file) {
+                view.appended(polled, tail.lines.len());
+            }
+            // Unfinished lines are left out until they are complete
+            for line in tail.just_finished() {
+                merged.push(i, line.clone());
+                added += 1;
             }
         }
+        app.merged_view.appended(added, merged.lines.len());
 
-        if let Some(reader) = app.log_readers.get_mut(&log_file) {
-            let cache = app.log_cache.entry(log_file.clone()).or_insert_with(|| VecDeque::with_capacity(1000));
+        let names: Vec<String> = shown.iter().map(name_of).collect();
+        let width = names.iter().map(|n| n.chars().count()).max().unwrap_or(0);
+        let title = format!("{} ", log_title(&app.merged_view, merged, &format!("Merged: {}", names.join(", "))));
+        let block = Block::default().borders(Borders::ALL).title(title);
+        let sources = &merged.sources;
+        draw_log(f, area, block, &mut app.merged_view, merged, |i| {
+            Some(Span::styled(format!("{:<width$} | ", names[sources[i]]), Style::default().fg(MERGED_COLORS[sources[i] % MERGED_COLORS.len()])))
+        });
+        return;
+    }
 
-            let mut line = String::new();
-            while let Ok(n) = reader.read_line(&mut line) {
-                if n == 0 {
-                    break;
-                }
-                cache.push_back(line.trim_end().to_string());
-                if cache.len() > 1000 {
-                    cache.pop_front();
-                }
-                line.clear();
+    let focused = focused_log(app);
+    for (file, rect) in shown.iter().zip(pane_rects(area, shown.len())) {
+        let Some(tail) = app.log_tails.get_mut(file) else { continue };
+        let added = tail.poll(Path::new(file));
+        let view = app.log_views.entry(file.clone()).or_insert_with(LogView::new);
+        view.appended(added, tail.lines.len());
+
+        let mut block = Block::default().borders(Borders::ALL);
+        let title = if app.log_layout == LogLayout::Single {
+            let cwd = app.processes.get(app.selected_index).map(|(_, p)| p.working_dir.as_str()).unwrap_or_default();
+            format!("{} | CWD: {} ", log_title(view, tail, "STDOUT Log"), cwd)
+        } else {
+            if shown.len() > 1 && focused.as_ref() == Some(file) {
+                block = block.border_style(Style::default().fg(config::color(&settings.tui.colors.selected_bg)));
             }
+            format!("{} ", log_title(view, tail, &name_of(file)))
+        };
+        draw_log(f, rect, block.title(title), view, tail, |_| None);
+    }
+}
 
-            let display_height = (chunks[1].height.saturating_sub(2)) as usize;
-            let lines: Vec<Line> = cache
-                .iter()
-                .rev()
-                .take(display_height)
-                .rev()
-                .map(|l| Line::from(l.clone()))
-                .collect();
+// Fills a log pane bottom-up from the view's scroll position, with search matches highlighted
+// and an optional styled prefix per line
+fn draw_log(f: &mut Frame, area: Rect, block: Block, view: &mut LogView, log: &dyn LogLines, prefix: impl Fn(usize) -> Option<Span<'static>>) {
+    let lines_in = log.lines();
+    let height = area.height.saturating_sub(2) as usize;
+    let width = area.width.saturating_sub(2).max(1) as usize;
+    view.height = height;
 
-            let title = format!(" STDOUT Log (tail -f) | CWD: {} ", proc.working_dir);
-            let paragraph = Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title(title));
-            f.render_widget(paragraph, chunks[1]);
+    // Walk up from the bottom of the view until the pane is full
+    let end = lines_in.len() - view.scroll.min(lines_in.len());
+    let current = view.current_match.map(|m| lines_in.len() - 1 - m);
+    let mut lines = Vec::new();
+    let mut rows = 0;
+    for i in (0..end).rev() {
+        let line = if view.raw { Line::from(ansi::escape(&lines_in[i]).into_owned()) } else { ansi::parse(&lines_in[i]) };
+        let mut line = highlight(line, view.search.as_ref(), current == Some(i));
+        if let Some(span) = prefix(i) {
+            line.spans.insert(0, span);
         }
+        let line_rows = if view.wrap { line.width().div_ceil(width).max(1) } else { 1 };
+        if rows + line_rows > height {
+            break;
+        }
+        rows += line_rows;
+        lines.push(line);
     }
+    lines.reverse();
 
-    let footer_area = Rect {
-        x: size.x,
-        y: size.height.saturating_sub(2),
-        width: size.width,
-        height: 2,
-    };
+    let mut paragraph = Paragraph::new(lines).block(block);
+    if view.wrap {
+        paragraph = paragraph.wrap(Wrap { trim: false });
+    }
+    f.render_widget(paragraph, area);
+}
 
-    let footer_text = if app.name_input_mode {
-        format!("Enter process name (or press Enter for default): {}", app.input_buffer)
-    } else if app.mode == Mode::Input {
-        format!("cmd: {}", app.input_buffer)
-    } else if app.is_paused {
-        "--- PAUSED (Press 'p' to resume) ---".to_string()
-    } else if !app.error_message.is_empty() {
-        app.error_message.clone()
+// "<label> (tail -f)" or the frozen position, then the search if there is one
+fn log_title(view: &LogView, log: &dyn LogLines, label: &str) -> String {
+    let mut title = if view.scroll == 0 {
+        format!(" {} (tail -f)", label)
     } else {
-        "[o]pen cmd | [x]kill | [X]remove | [c]lear logs | [j/k]navigate | [q]uit | [R]restart | [p]pause | [s]toggle resources".to_string()
+        format!(" {} (frozen, {} lines below, [{}] to follow)", label, view.scroll, config::settings().tui.keys.bottom)
     };
+    if view.raw {
+        title.push_str(" | raw");
+    }
+    if let Some(re) = &view.search {
+        title.push_str(&format!(" | /{}/ {} matches", re.as_str(), view.match_count(log)));
+    }
+    title
+}
 
-    let footer_style = if !app.error_message.is_empty() {
-        Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)
+// Two or three panes are stacked, four make a grid
+fn pane_rects(area: Rect, count: usize) -> Vec<Rect> {
+    if count == 4 {
+        let rows = Layout::default().direction(Direction::Vertical).constraints([Constraint::Percentage(50); 2]).split(area);
+        rows.iter()
+            .flat_map(|row| Layout::default().direction(Direction::Horizontal).constraints([Constraint::Percentage(50); 2]).split(*row).to_vec())
+            .collect()
+    } else {
+        let count = count.max(1);
+        Layout::default().direction(Direction::Vertical).constraints(vec![Constraint::Ratio(1, count as u32); count]).split(area).to_vec()
+    }
+}
+
+// Splits the spans of a log line so search matches stand out over the line's own colours
+fn highlight(line: Line<'static>, search: Option<&Regex>, current: bool) -> Line<'static> {
+    let Some(re) = search else { return line };
+    let text: String = line.spans.iter().map(|s| s.content.as_ref()).collect();
+    let matches: Vec<_> = re.find_iter(&text).filter(|m| !m.is_empty()).map(|m| m.range()).collect();
+    if matches.is_empty() {
+        return line;
+    }
+    let colors = &config::settings().tui.colors;
+    let found = Style::default().bg(config::color(if current { &colors.search_current } else { &colors.search_match })).fg(Color::Black);
+
+    let mut spans = Vec::new();
+    let mut offset = 0;
+    for span in line.spans {
+        let span_end = offset + span.content.len();
+        let mut pos = offset;
+        // Cut the span at every match boundary that falls inside it
+        for m in matches.iter().filter(|m| m.start < span_end && m.end > offset) {
+            if m.start > pos {
+                spans.push(Span::styled(text[pos..m.start].to_string(), span.style));
+                pos = m.start;
+            }
+            let cut = m.end.min(span_end);
+            spans.push(Span::styled(text[pos..cut].to_string(), span.style.patch(found)));
+            pos = cut;
+        }
+        if pos < span_end {
+            spans.push(Span::styled(text[pos..span_end].to_string(), span.style));
+        }
+        offset = span_end;
+    }
+    Line::from(spans)
+}
+
+fn draw_jobs(f: &mut Frame, app: &App, area: Rect) {
+    let settings = config::settings();
+    let colors = &settings.tui.colors;
+    let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs();
+
+    let header_cells = ["Name", "Every", "Next Run", "Last Run", "Last Result", "Enabled", "Command"]
+        .iter()
+        .map(|h| Cell::from(*h).style(Style::default().fg(Color::Yellow)));
+    let header = Row::new(header_cells).height(1).bottom_margin(1);
+
+    let rows = app.jobs.iter().enumerate().map(|(i, job)| {
+        let next_run = if job.run_now {
+            "now".to_string()
+        } else {
+            match scheduler::next_run(job, settings.default_frequency_secs()) {
+                None => "-".to_string(),
+                Some(t) if t <= now => "due".to_string(),
+                Some(t) => format_local(t, "%m-%d %H:%M:%S"),
+            }
+        };
+        let last_run = job.last_run.map(|t| format_local(t, "%m-%d %H:%M:%S")).unwrap_or_else(|| "-".to_string());
+        let command = launch::join_args(&std::iter::once(&job.command).chain(&job.args).cloned().collect::<Vec<_>>());
+
+        let style = if i == app.job_index {
+            Style::default().bg(config::color(&colors.selected_bg)).fg(config::color(&colors.selected_fg))
+        } else if !job.enabled {
+            Style::default().fg(config::color(&colors.stopped))
+        } else {
+            Style::default()
+        };
+
+        Row::new(vec![
+            Cell::from(job.name.clone()),
+            Cell::from(job.frequency.clone()),
+            Cell::from(next_run),
+            Cell::from(last_run),
+            Cell::from(last_result(app, job)),
+            Cell::from(if job.enabled { "yes" } else { "no" }),
+            Cell::from(command),
+        ])
+        .style(style)
+    });
+
+    let table = Table::new(rows, [
+        Constraint::Percentage(15),
+        Constraint::Length(6),
+        Constraint::Length(15),
+        Constraint::Length(15),
+        Constraint::Percentage(20),
+        Constraint::Length(7),
+        Constraint::Percentage(35),
+    ])
+    .header(header)
+    .block(Block::default().borders(Borders::ALL).title(" Scheduled Jobs (j/k) | [Tab] processes "));
+    f.render_widget(table, area);
+}
+
+// Status of the job's most recent run, as recorded for its S:<name> process
+fn last_result(app: &App, job: &ScheduledJob) -> String {
+    let target = format!("S:{}", job.name);
+    app.processes
+        .iter()
+        .filter(|(_, p)| p.display_name == target)
+        .max_by(|a, b| a.1.start_time.total_cmp(&b.1.start_time))
+        .map(|(_, p)| match p.exit_code {
+            Some(code) => format!("{} (exit {})", p.status, code),
+            None => p.status.clone(),
+        })
+        .unwrap_or_else(|| "-".to_string())
+}
+
+// Names what a destructive action is about to touch, and whether deleted logs can be restored
+fn draw_confirm(f: &mut Frame, app: &App, action: Action, targets: &[String], area: Rect) {
+    let mut lines = Vec::new();
+    if app.tab == Tab::Jobs {
+        lines.push(Line::from(format!("Remove the scheduled job '{}'?", targets.join("', '"))));
     } else {
-        Style::default().bg(Color::White).fg(Color::Black)
+        let verb = match action {
+            Action::Kill => "Stop",
+            Action::Restart => "Restart",
+            Action::Remove => "Remove (and delete the logs of)",
+            _ => "Delete the logs of",
+        };
+        let describe = |pid: &String| {
+            app.processes.iter().find(|(p, _)| p == pid).map(|(_, proc)| format!("{} (PID {}, {})", proc.display_name, pid, proc.status))
+        };
+        match targets {
+            [pid] => lines.push(Line::from(format!("{} {}?", verb, describe(pid).unwrap_or_else(|| format!("PID {}", pid))))),
+            _ => {
+                lines.push(Line::from(format!("{} {} processes?", verb, targets.len())));
+                lines.push(Line::from(""));
+                lines.extend(targets.iter().filter_map(describe).map(|d| Line::from(format!("  {}", d))));
+            }
+        }
+        if matches!(action, Action::Remove | Action::ClearLogs) {
+            let settings = config::settings();
+            lines.push(Line::from(""));
+            lines.push(Line::from(if settings.trash_retention_secs() > 0 {
+                format!("Logs go to the trash for {} (`ksai_proc trash restore`).", settings.logs.trash_retention)
+            } else {
+                "Logs are deleted permanently.".to_string()
+            }));
+        }
+    }
+    lines.push(Line::from(""));
+    lines.push(Line::from("[y] yes | any other key cancels"));
+
+    let width = area.width.min(70);
+    let height = (lines.len() as u16 + 2).min(area.height);
+    let popup = Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
     };
+    f.render_widget(Clear, popup);
+    f.render_widget(Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title(" Confirm ")), popup);
+}
 
-    let footer = Paragraph::new(footer_text).style(footer_style);
-    f.render_widget(footer, footer_area);
-}
\ No newline at end of file
+// How a key binding reads in the footer
+fn key_name(c: char) -> String {
+    if c == ' ' { "space".to_string() } else { c.to_string() }
+}
+
+fn draw_job_form(f: &mut Frame, form: &JobForm, area: Rect) {
+    let colors = &config::settings().tui.colors;
+    let width = area.width.min(70);
+    let height = (form.fields.len() as u16 + 2).min(area.height);
+    let popup = Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    };
+
+    let lines: Vec<Line> = JobForm::LABELS
+        .iter()
+        .zip(&form.fields)
+        .enumerate()
+        .map(|(i, (label, value))| {
+            if i == form.focus {
+                let style = Style::default().bg(config::color(&colors.selected_bg)).fg(config::color(&colors.selected_fg));
+                Line::styled(format!("{:<10}{}_", format!("{}:", label), value), style)
+            } else {
+                Line::from(format!("{:<10}{}", format!("{}:", label), value))
+            }
+        })
+        .collect();
+
+    let title = match &form.original {
+        Some(name) => format!(" Edit Job '{}' ", name),
+        None => " Add Job ".to_string(),
+    };
+    f.render_widget(Clear, popup);
+    f.render_widget(Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title(title)), popup);
+}
+
+fn draw_launch_form(f: &mut Frame, form: &LaunchForm, area: Rect) {
+    let colors = &config::settings().tui.colors;
+    let width = area.width.min(80);
+    // The fields, then a blank line and the error, the directory choices or a hint.
+    // A history search adds its query and the best matches.
+    let search_rows = if form.search.is_some() { SEARCH_ROWS as u16 + 1 } else { 0 };
+    let height = (form.fields.len() as u16 + 4 + search_rows).min(area.height);
+    let popup = Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    };
+
+    let error_style = Style::default().fg(config::color(&colors.error)).add_modifier(Modifier::BOLD);
+    let mut lines: Vec<Line> = LaunchForm::LABELS
+        .iter()
+        .zip(&form.fields)
+        .enumerate()
+        .map(|(i, (label, value))| {
+            let label = Span::styled(
+                format!("{:<11}", format!("{}:", label)),
+                if form.error.as_ref().is_some_and(|(field, _)| *field == i) { error_style } else { Style::default() },
+            );
+            if i == form.focus {
+                // Long values scroll so the end being typed stays visible
+                let room = (width as usize).saturating_sub(14);
+                let skip = value.chars().count().saturating_sub(room);
+                let style = Style::default().bg(config::color(&colors.selected_bg)).fg(config::color(&colors.selected_fg));
+                Line::from(vec![label, Span::styled(format!("{}_", value.chars().skip(skip).collect::<String>()), style)])
+            } else {
+                Line::from(vec![label, Span::raw(value.clone())])
+            }
+        })
+        .collect();
+    lines.push(Line::from(""));
+    lines.push(match &form.error {
+        Some((_, message)) => Line::styled(message.clone(), error_style),
+        None if !form.completions.is_empty() => Line::from(form.completions.join("  ")),
+        None => Line::styled(LaunchForm::HINTS[form.focus], Style::default().add_modifier(Modifier::DIM)),
+    });
+    if let Some(search) = &form.search {
+        let matches = form.search_matches();
+        let selected = search.selected.min(matches.len().saturating_sub(1));
+        lines.push(Line::from(format!("history search: {}_  ({} of {})", search.query, matches.len(), form.history.len())));
+        // Scrolls so the selected match stays in view
+        let first = selected.saturating_sub(SEARCH_ROWS - 1);
+        for (row, i) in matches.iter().enumerate().skip(first).take(SEARCH_ROWS) {
+            let text = format!("  {}", form.history[*i].summary());
+            if row == selected {
+                lines.push(Line::styled(text, Style::default().bg(config::color(&colors.selected_bg)).fg(config::color(&colors.selected_fg))));
+            } else {
+                lines.push(Line::from(text));
+            }
+        }
+    }
+
+    f.render_widget(Clear, popup);
+    f.render_widget(Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title(" Launch Process ")), popup);
+}
+
+// Saved launch templates; the first nine launch with their number
+fn draw_templates(f: &mut Frame, app: &App, area: Rect) {
+    let colors = &config::settings().tui.colors;
+    let width = area.width.min(90);
+    let height = (app.templates.len() as u16 + 2).min(area.height);
+    let popup = Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    };
+
+    let items: Vec<ListItem> = app.templates
+        .iter()
+        .enumerate()
+        .map(|(i, (name, spec))| {
+            let number = if i < 9 { format!("{}", i + 1) } else { " ".to_string() };
+            ListItem::new(format!("{} {:<16} {}", number, name, launch::join_args(&spec.command)))
+        })
+        .collect();
+    let mut state = ListState::default().with_selected(Some(app.template_index));
+    let list = List::new(items)
+        .block(Block::default().borders(Borders::ALL).title(" Launch Template "))
+        .highlight_style(Style::default().bg(config::color(&colors.selected_bg)).fg(config::color(&colors.selected_fg)));
+    f.render_widget(Clear, popup);
+    f.render_stateful_widget(list, popup, &mut state);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn proc(name: &str, log_file: &str) -> ProcessInfo {
+        serde_json::from_value(serde_json::json!({
+            "cmd_str": format!("/bin/sleep {}", name),
+            "log_file": log_file,
+            "status": "running",
+            "start_time": 0.0,
+            "display_name": name,
+        }))
+        .unwrap()
+    }
+
+    // A state directory unique to the test, removed again when dropped
+    struct TempState(std::path::PathBuf);
+
+    impl TempState {
+        fn new(tag: &str) -> Self {
+            let dir = env::temp_dir().join(format!("ksai_proc_ui_{}_{}", std::process::id(), tag));
+            std::fs::create_dir_all(&dir).unwrap();
+            Self(dir)
+        }
+
+        fn state_file(&self) -> std::path::PathBuf {
+            self.0.join("runningscripts.json")
+        }
+    }
+
+    impl Drop for TempState {
+        fn drop(&mut self) {
+            std::fs::remove_dir_all(&self.0).ok();
+        }
+    }
+
+    // Starts a sleep in its own process group, the way managed processes run, and registers it
+    fn spawn_sleep(state_file: &Path, name: &str) -> (String, std::process::Child) {
+        use std::os::unix::process::CommandExt;
+        let log_file = state_file.with_file_name(format!("{}.log", name));
+        std::fs::write(&log_file, "started\n").unwrap();
+        let child = std::process::Command::new("/bin/sleep").arg("1000").process_group(0).spawn().unwrap();
+        register_process(state_file, child.id(), "/bin/sleep 1000", &[], None, &log_file, "sleep", "/", name, &ProcessOptions::default(), 0);
+        (child.id().to_string(), child)
+    }
+
+    fn statuses(state_file: &Path) -> std::collections::HashMap<String, String> {
+        read_state(state_file).into_values().map(|p| (p.display_name, p.status)).collect()
+    }
+
+    fn app_with(names: &[&str]) -> App {
+        App::new(names.iter().enumerate().map(|(i, name)| ((100 + i).to_string(), proc(name, &format!("/logs/{}.log", name)))).collect())
+    }
+
+    #[test]
+    fn single_layout_shows_the_selected_log() {
+        let mut app = app_with(&["a", "b", "c"]);
+        app.selected_index = 1;
+        app.panes = vec!["/logs/c.log".to_string()];
+        assert_eq!(shown_logs(&app), ["/logs/b.log"]);
+        assert_eq!(focused_log(&app).as_deref(), Some("/logs/b.log"));
+    }
+
+    #[test]
+    fn split_layout_focuses_the_selected_pane() {
+        let mut app = app_with(&["a", "b", "c"]);
+        app.log_layout = LogLayout::Split;
+        // Without pins it falls back to the selected process
+        assert_eq!(shown_logs(&app), ["/logs/a.log"]);
+
+        app.panes = vec!["/logs/c.log".to_string(), "/logs/b.log".to_string()];
+        assert_eq!(shown_logs(&app), ["/logs/c.log", "/logs/b.log"]);
+        // The selected process isn't pinned, so keys go to the first pane
+        assert_eq!(focused_log(&app).as_deref(), Some("/logs/c.log"));
+        app.selected_index = 1;
+        assert_eq!(focused_log(&app).as_deref(), Some("/logs/b.log"));
+    }
+
+    #[test]
+    fn panes_stack_and_four_make_a_grid() {
+        let area = Rect::new(0, 0, 100, 40);
+        let heights: Vec<u16> = pane_rects(area, 2).iter().map(|r| r.height).collect();
+        assert_eq!(heights, [20, 20]);
+        assert_eq!(pane_rects(area, 3).len(), 3);
+        assert!(pane_rects(area, 3).iter().all(|r| r.width == 100));
+        let grid = pane_rects(area, 4);
+        assert_eq!(grid.len(), 4);
+        assert!(grid.iter().all(|r| r.width == 50 && r.height == 20));
+        assert_eq!(pane_rects(area, 0), [area]);
+    }
+
+    #[test]
+    fn list_actions_lose_no_concurrent_updates() {
+        let dir = TempState::new("concurrent");
+        let state_file = dir.state_file();
+        let (pids, mut children): (Vec<String>, Vec<_>) = (0..5).map(|i| spawn_sleep(&state_file, &format!("t{}", i))).unzip();
+        let mut app = App::new(Vec::new());
+        refresh_processes(&mut app, &state_file);
+
+        // Other writers add entries while the TUI acts on a list that is already stale
+        let writer = {
+            let state_file = state_file.clone();
+            std::thread::spawn(move || {
+                for i in 0..50 {
+                    update_state(&state_file, |state| {
+                        let mut entry = proc(&format!("cli{}", i), "/logs/cli.log");
+                        entry.status = "completed".to_string();
+                        state.insert(format!("cli{}", i), entry);
+                    });
+                }
+            })
+        };
+        apply_action(&mut app, &state_file, &dir.0, Action::Kill, &pids[0..2]);
+        apply_action(&mut app, &state_file, &dir.0, Action::Remove, &pids[2..4]);
+        apply_action(&mut app, &state_file, &dir.0, Action::ClearLogs, &pids[4..5]);
+        writer.join().unwrap();
+
+        let statuses = statuses(&state_file);
+        assert!((0..50).all(|i| statuses.contains_key(&format!("cli{}", i))));
+        assert_eq!(statuses["t0"], "killed (manual)");
+        assert_eq!(statuses["t1"], "killed (manual)");
+        assert!(!statuses.contains_key("t2") && !statuses.contains_key("t3"));
+        assert_eq!(statuses["t4"], "running");
+        assert!(!dir.0.join("t4.log").exists());
+
+        for child in &mut children {
+            child.kill().ok();
+            child.wait().ok();
+        }
+    }
+
+    #[test]
+    fn actions_target_the_marked_processes_in_list_order() {
+        let mut app = app_with(&["a", "b", "c"]);
+        app.selected_index = 2;
+        assert_eq!(action_targets(&app), ["102"]);
+        app.marked = ["102".to_string(), "100".to_string()].into();
+        assert_eq!(action_targets(&app), ["100", "102"]);
+        app.processes.clear();
+        app.marked.clear();
+        assert!(action_targets(&app).is_empty());
+    }
+
+    #[test]
+    fn bulk_kill_stops_every_marked_process_and_clears_the_marks() {
+        let dir = TempState::new("bulk");
+        let state_file = dir.state_file();
+        let (pids, mut children): (Vec<String>, Vec<_>) = (0..3).map(|i| spawn_sleep(&state_file, &format!("b{}", i))).unzip();
+        let mut app = App::new(Vec::new());
+        refresh_processes(&mut app, &state_file);
+        app.marked = [pids[0].clone(), pids[2].clone()].into();
+
+        let targets = action_targets(&app);
+        assert_eq!(targets.len(), 2);
+        apply_action(&mut app, &state_file, &dir.0, Action::Kill, &targets);
+        assert!(app.marked.is_empty());
+        let statuses = statuses(&state_file);
+        assert_eq!(statuses["b0"], "killed (manual)");
+        assert_eq!(statuses["b1"], "running");
+        assert_eq!(statuses["b2"], "killed (manual)");
+
+        // Marks of processes that left the list are dropped on refresh
+        apply_action(&mut app, &state_file, &dir.0, Action::Remove, &targets);
+        app.marked = [pids[0].clone(), pids[1].clone()].into();
+        refresh_processes(&mut app, &state_file);
+        assert_eq!(app.marked, [pids[1].clone()].into());
+
+        for child in &mut children {
+            child.kill().ok();
+            child.wait().ok();
+        }
+    }
+
+    #[test]
+    fn editing_a_job_keeps_quoted_arguments() {
+        let dir = TempState::new("jobs");
+        let scheduled_file = dir.0.join("scheduledscripts.json");
+        let mut form = JobForm { original: None, fields: ["echo".to_string(), "1m".to_string(), "now".to_string(), "sh -c 'echo a b'".to_string()], focus: 0 };
+        save_job_form(&scheduled_file, &form).unwrap();
+        let job = read_scheduled_jobs(&scheduled_file).remove(0);
+        assert_eq!((job.command.as_str(), job.args.as_slice()), ("sh", ["-c".to_string(), "echo a b".to_string()].as_slice()));
+
+        // Changing only the frequency leaves the command and directory alone
+        form = edit_job_form(&job);
+        assert_eq!(form.fields[3], "sh -c 'echo a b'");
+        form.fields[1] = "5m".to_string();
+        save_job_form(&scheduled_file, &form).unwrap();
+        let edited = read_scheduled_jobs(&scheduled_file).remove(0);
+        assert_eq!(edited.args, job.args);
+        assert_eq!(edited.frequency, "5m");
+        assert_eq!(edited.working_dir, job.working_dir);
+
+        form.fields[3] = "sh -c 'oops".to_string();
+        assert_eq!(save_job_form(&scheduled_file, &form), Err("Unterminated ' quote".to_string()));
+    }
+}
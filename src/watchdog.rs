@@ -0,0 +1,179 @@
+use std::{collections::{HashMap, HashSet}, fs::OpenOptions, io::Write, path::Path, time::{SystemTime, UNIX_EPOCH}};
+use chrono::Local;
+use crate::{events, hooks::{self, HookEvent}, monitor::Monitor, process::{finish_stopping, mark_stopping, respawn_process, stop_all}, state::{read_state, update_state}, types::{ProcessInfo, WatchAction}};
+
+// How long --watch-cpu must be exceeded when no duration was given
+const DEFAULT_CPU_SECS: u64 = 30;
+
+pub struct Violation {
+    pub pid: String,
+    pub reason: String,
+    pub action: WatchAction,
+}
+
+// Soft limits checked by the scheduler daemon against Monitor samples
+pub struct Watchdog {
+    cpu_exceeded_since: HashMap<u32, u64>,
+    // Processes already alerted about; they are alerted again only after recovering in between
+    alerted: HashSet<u32>,
+}
+
+impl Watchdog {
+    pub fn new() -> Self {
+        Self { cpu_exceeded_since: HashMap::new(), alerted: HashSet::new() }
+    }
+
+    pub fn check(&mut self, monitor: &Monitor, processes: &HashMap<String, ProcessInfo>) -> Vec<Violation> {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+        let mut violations = Vec::new();
+
+        // Forget processes that are gone so a reused PID starts with a clean slate
+        let tracked: HashSet<u32> = processes.iter()
+            .filter(|(_, p)| p.status == "running")
+            .filter_map(|(pid_str, _)| pid_str.parse().ok())
+            .collect();
+        self.cpu_exceeded_since.retain(|pid, _| tracked.contains(pid));
+        self.alerted.retain(|pid| tracked.contains(pid));
+
+        for (pid_str, proc) in processes {
+            let limits = &proc.options.limits;
+            if proc.status != "running" || (limits.watch_rss.is_none() && limits.watch_cpu.is_none()) {
+                continue;
+            }
+            let Ok(pid) = pid_str.parse::<u32>() else { continue };
+            // Thresholds apply to the whole tree since that is what gets killed
+            let Some(stats) = monitor.get_tree_stats(pid) else { continue };
+
+            let over_rss = limits.watch_rss.is_some_and(|max_rss| stats.mem_usage > max_rss);
+            let over_cpu = limits.watch_cpu.is_some_and(|max_cpu| stats.cpu_usage > max_cpu);
+            if !over_rss && !over_cpu {
+                self.alerted.remove(&pid);
+            }
+            if limits.watch_action == WatchAction::Alert && self.alerted.contains(&pid) {
+                continue;
+            }
+
+            if let Some(max_rss) = limits.watch_rss
+                && over_rss
+            {
+                violations.push(Violation {
+                    pid: pid_str.clone(),
+                    reason: format!("memory {:.1}M > {:.1}M", stats.mem_usage as f64 / 1024.0 / 1024.0, max_rss as f64 / 1024.0 / 1024.0),
+                    action: limits.watch_action,
+                });
+                continue;
+            }
+
+            if let Some(max_cpu) = limits.watch_cpu {
+                if over_cpu {
+                    let since = *self.cpu_exceeded_since.entry(pid).or_insert(now);
+                    let window = limits.watch_cpu_secs.unwrap_or(DEFAULT_CPU_SECS);
+                    if now - since >= window {
+                        violations.push(Violation {
+                            pid: pid_str.clone(),
+                            reason: format!("cpu above {:.0}% for {}s", max_cpu, now - since),
+                            action: limits.watch_action,
+                        });
+                    }
+                } else {
+                    self.cpu_exceeded_since.remove(&pid);
+                }
+            }
+        }
+
+        for v in &violations {
+            if let Ok(pid) = v.pid.parse::<u32>() {
+                self.cpu_exceeded_since.remove(&pid);
+                if v.action == WatchAction::Alert {
+                    self.alerted.insert(pid);
+                }
+            }
+        }
+        violations
+    }
+}
+
+pub fn enforce(state_file: &Path, violations: Vec<Violation>) {
+    let mut violations: HashMap<String, Violation> = violations.into_iter()
+        .filter_map(|v| {
+            if v.action == WatchAction::Alert {
+                alert(state_file, &v);
+                return None;
+            }
+            Some((v.pid.clone(), v))
+        })
+        .collect();
+    if violations.is_empty() {
+        return;
+    }
+
+    // Stopped with the lock released, like any other stop, so other writers aren't held up
+    let pids: Vec<String> = violations.keys().cloned().collect();
+    let mut stopping = Vec::new();
+    update_state(state_file, |state| stopping = mark_stopping(state, &pids));
+    stop_all(&stopping);
+
+    for entry in &stopping {
+        let Some(v) = violations.remove(&entry.0) else { continue };
+        let status = match v.action {
+            WatchAction::Restart => format!("restarted (watchdog: {})", v.reason),
+            _ => format!("killed (watchdog: {})", v.reason),
+        };
+        let mut proc = entry.1.clone();
+        events::record(state_file, "health-changed", &v.pid, &proc.display_name, Some(format!("unhealthy: {}", v.reason)));
+        finish_stopping(state_file, std::slice::from_ref(entry), &status, &format!("watchdog: {}", v.reason));
+        proc.status = status;
+
+        hooks::fire(state_file, vec![HookEvent::new("unhealthy", &v.pid, &proc, Some(v.reason.clone()))]);
+        println!("Watchdog: PID {} ({}) {}", v.pid, proc.display_name, proc.status);
+        if let Ok(mut f) = OpenOptions::new().append(true).open(&proc.log_file) {
+            writeln!(f, "\n--- ⚠️ WATCHDOG: {} (PID {}) @ {} ---", v.reason, v.pid, Local::now()).ok();
+        }
+
+        if v.action == WatchAction::Restart {
+            match respawn_process(state_file, &proc) {
+                Ok(new_pid) => println!("Watchdog: restarted '{}' with new PID {}", proc.display_name, new_pid),
+                Err(e) => eprintln!("Watchdog: failed to restart '{}': {}", proc.display_name, e),
+            }
+        }
+    }
+}
+
+// Tells about the violation without touching the process
+fn alert(state_file: &Path, v: &Violation) {
+    let Some(proc) = read_state(state_file).remove(&v.pid).filter(|p| p.status == "running") else {
+        return;
+    };
+    events::record(state_file, "health-changed", &v.pid, &proc.display_name, Some(format!("unhealthy: {}", v.reason)));
+    hooks::fire(state_file, vec![HookEvent::new("unhealthy", &v.pid, &proc, Some(v.reason.clone()))]);
+    println!("Watchdog: PID {} ({}) alert: {}", v.pid, proc.display_name, v.reason);
+    if let Ok(mut f) = OpenOptions::new().append(true).open(&proc.log_file) {
+        writeln!(f, "\n--- ⚠️ WATCHDOG ALERT: {} (PID {}) @ {} ---", v.reason, v.pid, Local::now()).ok();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn forgets_processes_that_are_no_longer_running() {
+        let mut watchdog = Watchdog::new();
+        watchdog.cpu_exceeded_since.insert(100, 0);
+        watchdog.alerted.insert(100);
+        watchdog.cpu_exceeded_since.insert(101, 0);
+        watchdog.alerted.insert(101);
+
+        let running: ProcessInfo = serde_json::from_value(serde_json::json!({
+            "cmd_str": "sleep 1000", "log_file": "/dev/null", "status": "running", "start_time": 0.0,
+        })).unwrap();
+        let mut done = running.clone();
+        done.status = "completed".to_string();
+        let processes = HashMap::from([("100".to_string(), running), ("101".to_string(), done)]);
+
+        assert!(watchdog.check(&Monitor::new(), &processes).is_empty());
+        // 100 has no watch limits, so it is skipped but kept
+        assert!(watchdog.alerted.contains(&100) && watchdog.cpu_exceeded_since.contains_key(&100));
+        assert!(!watchdog.alerted.contains(&101) && !watchdog.cpu_exceeded_since.contains_key(&101));
+    }
+}
@@ -52,6 +52,18 @@ def test_env(request):
     # However, `metrics` or `scheduler` might be running.
     # Best effort cleanup of processes tracked in the state file.
     
+    # The daemon isn't in the state file; its PID is in the pidfile next to it.
+    # Killed first so it can't start scheduled jobs again while the rest is cleaned up,
+    # and again at the end since any CLI call below starts a new one.
+    def kill_daemon():
+        try:
+            with open(os.path.join(logs_dir, "daemon.pid"), 'r') as f:
+                os.kill(int(f.read().strip()), 9)
+        except:
+            pass
+
+    kill_daemon()
+
     try:
         if os.path.exists(state_file):
              # We can use our own binary to stop them?
@@ -59,7 +71,7 @@ def test_env(request):
              # Or just kill by reading state file
              import json
              with open(state_file, 'r') as f:
-                 state = json.load(f)
+                 state = json.load(f)["processes"]
                  for pid, proc in state.items():
                      try:
                          os.kill(int(pid), 9)
@@ -68,5 +80,47 @@ def test_env(request):
     except:
         pass
 
+    # Same for tests that switched to the SQLite backend
+    try:
+        db_file = os.path.join(logs_dir, "ksai_proc.db")
+        if os.path.exists(db_file):
+            import sqlite3
+            conn = sqlite3.connect(db_file)
+            for (pid,) in conn.execute("SELECT pid FROM processes"):
+                try:
+                    os.kill(int(pid), 9)
+                except:
+                    pass
+            conn.close()
+    except:
+        pass
+
+    # Each killed process has a supervisor that writes its exit status to the
+    # state file; let them finish before the directory is removed.
+    def supervisors():
+        pids = []
+        for entry in os.listdir("/proc"):
+            if not entry.isdigit():
+                continue
+            try:
+                with open(f"/proc/{entry}/cmdline", 'rb') as f:
+                    cmdline = f.read().decode(errors="replace")
+            except OSError:
+                continue
+            if "internal-supervise" in cmdline and test_dir in cmdline:
+                pids.append(int(entry))
+        return pids
+
+    deadline = time.time() + 5
+    while supervisors() and time.time() < deadline:
+        time.sleep(0.1)
+    for pid in supervisors():
+        try:
+            os.kill(pid, 9)
+        except OSError:
+            pass
+
+    kill_daemon()
+
     if os.path.exists(test_dir):
         shutil.rmtree(test_dir)
@@ -36,7 +36,7 @@ def test_run_with_timeout(test_env):
     # It might still be in the list but status killed/completed
     # We need to parse the list output or check state file directly
     with open(test_env["state_file"], 'r') as f:
-        state = json.load(f)
+        state = json.load(f)["processes"]
         found = False
         for pid, proc in state.items():
             if proc["display_name"] == "timeout_test":
@@ -55,7 +55,7 @@ def test_stop_by_name(test_env):
     
     # Verify status
     with open(test_env["state_file"], 'r') as f:
-        state = json.load(f)
+        state = json.load(f)["processes"]
         for pid, proc in state.items():
             if proc["display_name"] == "to_stop":
                 assert "killed" in proc["status"]
@@ -66,21 +66,105 @@ def test_restart_process(test_env):
     time.sleep(1)
     
     with open(test_env["state_file"], 'r') as f:
-        state = json.load(f)
+        state = json.load(f)["processes"]
         pid_1 = [k for k, v in state.items() if v["display_name"] == "to_restart"][0]
+        assert state[pid_1]["restarts"] == 0
 
     res = test_env["run"](["restart", pid_1])
     assert "restarted with new PID" in res.stdout
     
     # Verify new PID
     with open(test_env["state_file"], 'r') as f:
-        state = json.load(f)
+        state = json.load(f)["processes"]
         # Should still have 1 entry (or 2 if old one wasn't cleaned immediately? restart removes old)
         assert len(state) >= 1
         # Find the one with display_name
         pids = [p for p, v in state.items() if v["display_name"] == "to_restart"]
         assert len(pids) == 1
         assert pids[0] != pid_1
+        assert state[pids[0]]["restarts"] == 1
+
+def test_run_with_tags(test_env):
+    """Test that --tag is stored with the process and kept across a restart."""
+    res = test_env["run"](["run", "--name", "tagged", "--tag", "web", "--tag", "api", "--no-tui", "/bin/sleep", "100"])
+    assert res.returncode == 0
+
+    with open(test_env["state_file"], 'r') as f:
+        state = json.load(f)["processes"]
+    pid, proc = [(k, v) for k, v in state.items() if v["display_name"] == "tagged"][0]
+    assert proc["options"]["tags"] == ["web", "api"]
+
+    test_env["run"](["restart", pid])
+    with open(test_env["state_file"], 'r') as f:
+        state = json.load(f)["processes"]
+    procs = [v for v in state.values() if v["display_name"] == "tagged"]
+    assert len(procs) == 1
+    assert procs[0]["options"]["tags"] == ["web", "api"]
+
+def test_run_with_env_survives_restart(test_env):
+    """Test that --env reaches the process and that a restart keeps it and arguments containing spaces."""
+    script = "import os, time; print('value=' + os.environ['GREETING']); time.sleep(100)"
+    res = test_env["run"](["run", "--name", "env_test", "--env", "GREETING=hello there", "--no-tui", "python3", "-u", "-c", script])
+    assert res.returncode == 0
+
+    res = test_env["run"](["run", "--name", "bad_env", "--env", "1BAD", "--no-tui", "/bin/sleep", "1"])
+    assert res.returncode != 0
+
+    with open(test_env["state_file"], 'r') as f:
+        state = json.load(f)["processes"]
+    pid, proc = [(k, v) for k, v in state.items() if v["display_name"] == "env_test"][0]
+    assert proc["options"]["env"] == {"GREETING": "hello there"}
+    assert proc["argv"] == ["python3", "-u", "-c", script]
+
+    time.sleep(1)
+    res = test_env["run"](["restart", pid])
+    assert "restarted with new PID" in res.stdout
+    time.sleep(1)
+    with open(proc["log_file"]) as f:
+        assert f.read().count("value=hello there") == 2
+
+def test_run_template(test_env):
+    """Test launching a saved template with `run --template`, with options overriding it."""
+    workdir = os.path.join(test_env["test_dir"], "work")
+    os.makedirs(workdir)
+    templates = {"schema_version": 2, "templates": {"web": {
+        "command": ["python3", "-u", "-c", "import os, time; print(os.getcwd(), os.environ['PORT']); time.sleep(100)"],
+        "name": "web",
+        "working_dir": workdir,
+        "restart_policy": "on-failure",
+        "env": {"PORT": "8080"},
+    }}}
+    with open(os.path.join(test_env["logs_dir"], "templates.json"), 'w') as f:
+        json.dump(templates, f)
+
+    res = test_env["run"](["templates", "list"])
+    assert "web" in res.stdout
+    assert "PORT=8080" in res.stdout
+
+    res = test_env["run"](["run", "--template", "web", "--no-tui"])
+    assert res.returncode == 0
+    # The same command in the same directory is refused while it runs
+    time.sleep(1)
+    test_env["run"](["stop", "--name", "web"])
+    res = test_env["run"](["run", "--template", "web", "--name", "web2", "--env", "PORT=9090", "--no-tui"])
+    assert res.returncode == 0
+    time.sleep(1)
+
+    with open(test_env["state_file"], 'r') as f:
+        state = json.load(f)["processes"]
+    procs = {v["display_name"]: v for v in state.values()}
+    assert procs["web"]["working_dir"] == workdir
+    assert procs["web"]["options"]["restart_policy"] == "on-failure"
+    assert procs["web2"]["options"]["env"] == {"PORT": "9090"}
+    with open(procs["web2"]["log_file"]) as f:
+        assert workdir + " 9090" in f.read()
+
+    res = test_env["run"](["run", "--template", "missing", "--no-tui"])
+    assert res.returncode != 0
+    assert "No template named 'missing'" in res.stderr
+
+    assert "removed" in test_env["run"](["templates", "remove", "web"]).stdout
+    assert "No templates" in test_env["run"](["templates", "list"]).stdout
 
 def test_remove_process(test_env):
     """Test removing a process."""
@@ -89,7 +173,7 @@ def test_remove_process(test_env):
     
     # Get PID
     with open(test_env["state_file"], 'r') as f:
-        state = json.load(f)
+        state = json.load(f)["processes"]
         pid = [k for k, v in state.items() if v["display_name"] == "to_remove"][0]
         
     res = test_env["run"](["remove", pid])
@@ -97,7 +181,7 @@ def test_remove_process(test_env):
     
     # Verify it's gone
     with open(test_env["state_file"], 'r') as f:
-        state = json.load(f)
+        state = json.load(f)["processes"]
         # Ensure "to_remove" is not in any display_name
         found = any("to_remove" in v["display_name"] for v in state.values())
         assert not found
@@ -111,7 +195,7 @@ def test_logs_creation(test_env):
     
     # Get PID
     with open(test_env["state_file"], 'r') as f:
-        state = json.load(f)
+        state = json.load(f)["processes"]
         pid = [k for k, v in state.items() if v["display_name"] == "log_test"][0]
     
     # Check logs command
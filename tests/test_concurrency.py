@@ -0,0 +1,77 @@
+import pytest
+import json
+import threading
+import time
+import os
+
+def test_concurrent_mutations_lose_no_updates(test_env):
+    """Hammer the state from many CLIs at once (run, stop, restart, remove, list) and check every update landed.
+
+    The TUI's side of the same race is covered by the unit tests in src/ui.rs."""
+    workers = 8
+    rounds = 3
+
+    def read_processes():
+        with open(test_env["state_file"], 'r') as f:
+            return json.load(f)["processes"]
+
+    def pid_of(name):
+        return [k for k, v in read_processes().items() if v["display_name"] == name][0]
+
+    def worker(w):
+        for r in range(rounds):
+            name = f"c_{w}_{r}"
+            # Distinct arguments, since identical commands in the same directory are refused
+            test_env["run"](["run", "--name", name, "--no-tui", "/bin/sleep", str(1000 + w * 10 + r)])
+            test_env["run"](["list"])
+            # Round 0 is stopped, round 1 restarted by odd workers, round 2 removed while running
+            if r == 0:
+                test_env["run"](["stop", "--name", name])
+            if r == 1 and w % 2 == 1:
+                test_env["run"](["restart", pid_of(name)])
+            if r == 2:
+                test_env["run"](["remove", pid_of(name)])
+
+    threads = [threading.Thread(target=worker, args=(w,)) for w in range(workers)]
+    for t in threads:
+        t.start()
+    for t in threads:
+        t.join()
+
+    state = read_processes()
+    by_name = {}
+    for v in state.values():
+        by_name.setdefault(v["display_name"], []).append(v)
+    for w in range(workers):
+        assert [p["status"] for p in by_name[f"c_{w}_0"]] == ["killed (manual)"]
+        assert [p["status"] for p in by_name[f"c_{w}_1"]] == ["running"]
+        assert by_name[f"c_{w}_1"][0]["restarts"] == (1 if w % 2 == 1 else 0)
+        assert f"c_{w}_2" not in by_name
+
+def test_slow_stop_does_not_block_other_commands(test_env):
+    """A stop waiting out stop_timeout must not hold the state lock while it waits."""
+    config = os.path.join(test_env["test_dir"], "config.toml")
+    with open(config, "w") as f:
+        f.write('[process]\nstop_signal = "SIGTERM"\nstop_timeout = "4s"\n')
+    test_env["env"]["KSAI_PROC_CONFIG"] = config
+
+    # Ignores SIGTERM, so the stop only finishes when it escalates to SIGKILL
+    test_env["run"](["run", "--name", "stubborn", "--no-tui", "/bin/sh", "-c", "trap '' TERM; while true; do sleep 0.1; done"])
+    time.sleep(0.5)
+
+    stopper = threading.Thread(target=test_env["run"], args=(["stop", "--name", "stubborn"],))
+    stopper.start()
+    time.sleep(1)
+
+    started = time.time()
+    test_env["run"](["run", "--name", "other", "--no-tui", "/bin/sleep", "1000"])
+    assert time.time() - started < 2.5, "run waited for the stop to finish"
+    with open(test_env["state_file"], 'r') as f:
+        statuses = {p["display_name"]: p["status"] for p in json.load(f)["processes"].values()}
+    assert statuses["stubborn"] == "stopping"
+    assert statuses["other"] == "running"
+
+    stopper.join()
+    with open(test_env["state_file"], 'r') as f:
+        statuses = {p["display_name"]: p["status"] for p in json.load(f)["processes"].values()}
+    assert statuses["stubborn"] == "killed (manual)"
@@ -0,0 +1,167 @@
+import pytest
+import os
+import time
+import json
+
+def write_config(test_env, text):
+    """Point ksai_proc at a config file in the test dir (must happen before the daemon starts)."""
+    path = os.path.join(test_env["test_dir"], "config.toml")
+    with open(path, "w") as f:
+        f.write(text)
+    test_env["env"]["KSAI_PROC_CONFIG"] = path
+    return path
+
+def entries(test_env, name):
+    with open(test_env["state_file"], 'r') as f:
+        state = json.load(f)["processes"]
+    return {pid: p for pid, p in state.items() if p["display_name"] == name}
+
+def test_config_show(test_env):
+    """Test that `config show` prints the defaults merged with the config file."""
+    write_config(test_env, '[scheduler]\ndefault_frequency = "5m"\n\n[tui.keys]\nquit = "Q"\n')
+    res = test_env["run"](["config", "show"])
+    assert res.returncode == 0
+    assert 'default_frequency = "5m"' in res.stdout
+    assert 'quit = "Q"' in res.stdout
+    # Untouched settings keep their defaults
+    assert "log_cache_lines = 1000" in res.stdout
+    assert 'stop_signal = "SIGKILL"' in res.stdout
+
+def test_invalid_config_is_rejected(test_env):
+    """Test that invalid values are reported and the defaults used instead."""
+    write_config(test_env, '[process]\nstop_signal = "SIGFOO"\n')
+    res = test_env["run"](["config", "show"])
+    assert res.returncode != 0
+    assert 'unknown signal "SIGFOO"' in res.stderr
+    assert 'stop_signal = "SIGKILL"' in res.stdout
+
+    write_config(test_env, '[tui.keys]\nquit = "x"\n')
+    res = test_env["run"](["config", "show"])
+    assert res.returncode != 0
+    assert "both bound to 'x'" in res.stderr
+
+    write_config(test_env, '[tui]\nresource_window = "0m"\n')
+    res = test_env["run"](["config", "show"])
+    assert res.returncode != 0
+    assert 'tui.resource_window: invalid duration "0m"' in res.stderr
+    assert 'resource_window = "10m"' in res.stdout
+
+def test_stop_signal_from_config(test_env):
+    """Test that stop sends the configured signal so the process can shut down cleanly."""
+    write_config(test_env, '[process]\nstop_signal = "SIGTERM"\nstop_timeout = "3s"\n')
+    test_env["run"](["run", "--name", "graceful", "--no-tui", "/bin/sh", "-c",
+                     "trap 'echo got-term; exit 0' TERM; while true; do sleep 0.1; done"])
+    time.sleep(1)
+
+    res = test_env["run"](["stop", "--name", "graceful"])
+    assert "stopped" in res.stdout
+
+    log_file = list(entries(test_env, "graceful").values())[0]["log_file"]
+    with open(log_file, 'r') as f:
+        assert "got-term" in f.read()
+
+def test_restart_policy_always(test_env):
+    """Test that the daemon restarts a process that exits when its policy is 'always'."""
+    write_config(test_env, '[scheduler]\ntick_ms = 200\n')
+    test_env["run"](["run", "--name", "flaky", "--no-tui", "--restart", "always", "/bin/sleep", "1"])
+
+    restarted = False
+    for _ in range(30):
+        time.sleep(0.5)
+        procs = entries(test_env, "flaky")
+        if any(p["status"] == "restarted (policy)" for p in procs.values()):
+            restarted = True
+            break
+    assert restarted, "Process was not restarted by its restart policy"
+    assert all(p["options"]["restart_policy"] == "always" for p in procs.values())
+
+def test_restart_policy_backs_off(test_env):
+    """Test that a process that keeps exiting right away is restarted with growing delays."""
+    write_config(test_env, '[scheduler]\ntick_ms = 200\n')
+    test_env["run"](["run", "--name", "crashloop", "--no-tui", "--restart", "always", "/bin/true"])
+    time.sleep(5)
+
+    starts = sorted(p["start_time"] for p in entries(test_env, "crashloop").values())
+    # Without backoff it would be restarted on every 200ms tick
+    assert 2 <= len(starts) <= 4, starts
+    gaps = [b - a for a, b in zip(starts, starts[1:])]
+    assert gaps[0] >= 1.0
+    if len(gaps) > 1:
+        assert gaps[1] >= 2.0
+
+def test_restart_policy_default_is_never(test_env):
+    """Test that processes without a policy are left alone after they exit."""
+    write_config(test_env, '[scheduler]\ntick_ms = 200\n')
+    test_env["run"](["run", "--name", "oneshot", "--no-tui", "/bin/sleep", "0.5"])
+    time.sleep(3)
+    procs = entries(test_env, "oneshot")
+    assert len(procs) == 1
+    assert list(procs.values())[0]["status"] == "completed"
+
+def test_log_rotation(test_env):
+    """Test that the daemon rotates logs that grow past logs.max_size."""
+    write_config(test_env, '[scheduler]\ntick_ms = 200\n\n[logs]\nmax_size = "2K"\nkeep = 2\n')
+    test_env["run"](["run", "--name", "chatty", "--no-tui", "python3", "-u", "-c",
+                     "import time\nwhile True:\n    print('x' * 100, flush=True)\n    time.sleep(0.01)"])
+
+    log_file = None
+    for _ in range(30):
+        time.sleep(0.5)
+        procs = entries(test_env, "chatty")
+        if procs:
+            log_file = list(procs.values())[0]["log_file"]
+            if os.path.exists(log_file + ".2"):
+                break
+
+    assert log_file is not None
+    assert os.path.exists(log_file + ".1")
+    assert os.path.exists(log_file + ".2")
+    assert not os.path.exists(log_file + ".3")
+    # The live log was truncated, so it stays around the limit
+    assert os.path.getsize(log_file) < 64 * 1024
+
+def test_removed_logs_go_to_trash(test_env):
+    """Test that `remove` moves the log to the trash and `trash restore` brings it back."""
+    test_env["run"](["run", "--name", "trashed", "--no-tui", "python3", "-u", "-c", "print('keep me')"])
+    time.sleep(1)
+    pid, proc = list(entries(test_env, "trashed").items())[0]
+    log_file = proc["log_file"]
+    log_name = os.path.basename(log_file)
+
+    res = test_env["run"](["remove", pid])
+    assert "moved to the trash" in res.stdout
+    assert not os.path.exists(log_file)
+
+    res = test_env["run"](["trash", "list"])
+    assert log_name in res.stdout
+
+    res = test_env["run"](["trash", "restore", log_name])
+    assert res.returncode == 0
+    with open(log_file) as f:
+        assert "keep me" in f.read()
+    assert "The trash is empty" in test_env["run"](["trash", "list"]).stdout
+
+    # Nothing left to restore
+    res = test_env["run"](["trash", "restore", log_name])
+    assert res.returncode != 0
+    assert "not in the trash" in res.stderr
+
+def test_trash_retention(test_env):
+    """Test that a zero retention deletes logs outright, and that invalid settings are rejected."""
+    write_config(test_env, '[logs]\ntrash_retention = "0s"\n')
+    test_env["run"](["run", "--name", "gone", "--no-tui", "/bin/sleep", "100"])
+    time.sleep(1)
+    pid = list(entries(test_env, "gone"))[0]
+    res = test_env["run"](["remove", pid])
+    assert "logs deleted" in res.stdout
+    assert "The trash is empty" in test_env["run"](["trash", "list"]).stdout
+
+    write_config(test_env, '[logs]\ntrash_retention = "forever"\n')
+    res = test_env["run"](["config", "show"])
+    assert res.returncode != 0
+    assert 'invalid duration "forever"' in res.stderr
+
+    write_config(test_env, '[tui]\nconfirm = ["kill", "quit"]\n')
+    res = test_env["run"](["config", "show"])
+    assert res.returncode != 0
+    assert '"quit" is not one of' in res.stderr
@@ -0,0 +1,231 @@
+import pytest
+import os
+import json
+import time
+import signal
+import subprocess
+from conftest import KSAI_PROC_BIN
+
+FAKE_SYSTEMCTL = """#!/bin/sh
+echo "$@" >> "$FAKE_DIR/systemctl.log"
+case "$2" in
+    is-enabled) echo enabled ;;
+    is-active) echo inactive ;;
+esac
+exit ${FAKE_SYSTEMCTL_RC:-0}
+"""
+
+FAKE_CRONTAB = """#!/bin/sh
+if [ "$1" = "-l" ]; then
+    [ -f "$FAKE_DIR/crontab.txt" ] || { echo "no crontab for user" >&2; exit 1; }
+    cat "$FAKE_DIR/crontab.txt"
+else
+    cat > "$FAKE_DIR/crontab.txt"
+fi
+"""
+
+def fake_tools(test_env):
+    """Put fake systemctl and crontab first on PATH so nothing touches the real user session."""
+    fake_dir = os.path.join(test_env["test_dir"], "fakebin")
+    os.makedirs(fake_dir)
+    for name, script in [("systemctl", FAKE_SYSTEMCTL), ("crontab", FAKE_CRONTAB)]:
+        path = os.path.join(fake_dir, name)
+        with open(path, "w") as f:
+            f.write(script)
+        os.chmod(path, 0o755)
+    env = test_env["env"]
+    env["PATH"] = fake_dir + ":" + env["PATH"]
+    env["FAKE_DIR"] = fake_dir
+    env["XDG_CONFIG_HOME"] = os.path.join(test_env["test_dir"], "config")
+    return fake_dir
+
+def read(path):
+    with open(path, "r") as f:
+        return f.read()
+
+def test_install_systemd_unit(test_env):
+    """Test that install writes and enables a user unit that survives stopping the service."""
+    fake_dir = fake_tools(test_env)
+    res = test_env["run"](["daemon", "install"])
+    assert res.returncode == 0, res.stderr
+
+    unit_path = os.path.join(test_env["env"]["XDG_CONFIG_HOME"], "systemd", "user", "ksai_proc.service")
+    unit = read(unit_path)
+    assert f'ExecStart="{KSAI_PROC_BIN}" internal-scheduler' in unit
+    assert f'Environment="KSAI_PROC_LOG_JSON={test_env["state_file"]}"' in unit
+    assert "KillMode=process" in unit
+    assert "ExecReload=/bin/kill -HUP $MAINPID" in unit
+    assert "WantedBy=default.target" in unit
+
+    calls = read(os.path.join(fake_dir, "systemctl.log"))
+    assert "--user daemon-reload" in calls
+    assert "--user enable ksai_proc.service" in calls
+
+    status = test_env["run"](["daemon", "status"]).stdout
+    assert "ksai_proc.service (enabled, inactive)" in status
+
+    res = test_env["run"](["daemon", "uninstall"])
+    assert "Removed systemd unit" in res.stdout
+    assert not os.path.exists(unit_path)
+    assert "--user disable ksai_proc.service" in read(os.path.join(fake_dir, "systemctl.log"))
+
+def test_install_escapes_unit_values(test_env):
+    """Test that spaces, quotes and % in paths survive the escaping rules of systemd units and crontabs."""
+    fake_tools(test_env)
+    odd_dir = os.path.join(test_env["test_dir"], 'my 100% "logs"')
+    os.makedirs(odd_dir)
+    test_env["env"]["KSAI_PROC_LOG_DIR"] = odd_dir
+    res = test_env["run"](["daemon", "install"])
+    assert res.returncode == 0, res.stderr
+
+    unit = read(os.path.join(test_env["env"]["XDG_CONFIG_HOME"], "systemd", "user", "ksai_proc.service"))
+    escaped = odd_dir.replace("%", "%%")
+    assert 'Environment="KSAI_PROC_LOG_DIR=' + escaped.replace('"', '\\"') + '"' in unit
+    assert "StandardOutput=append:" + escaped + "/scheduler.log" in unit
+    test_env["run"](["daemon", "uninstall"])
+
+    # cron needs its own escaping of %
+    test_env["run"](["daemon", "install", "--cron"])
+    reboot = [l for l in read(os.path.join(test_env["env"]["FAKE_DIR"], "crontab.txt")).splitlines() if l.startswith("@reboot")][0]
+    assert "KSAI_PROC_LOG_DIR='" + odd_dir.replace("%", "\\%") + "'" in reboot
+    assert "%" not in reboot.replace("\\%", "")
+    test_env["run"](["daemon", "uninstall"])
+
+def test_install_falls_back_to_cron(test_env):
+    """Test that a failing systemctl leads to an @reboot crontab entry, and that other entries are kept."""
+    fake_dir = fake_tools(test_env)
+    test_env["env"]["FAKE_SYSTEMCTL_RC"] = "1"
+    with open(os.path.join(fake_dir, "crontab.txt"), "w") as f:
+        f.write("0 * * * * /usr/bin/true\n")
+
+    res = test_env["run"](["daemon", "install"])
+    assert res.returncode == 0, res.stderr
+    assert "falling back" in res.stderr
+    assert not os.path.exists(os.path.join(test_env["env"]["XDG_CONFIG_HOME"], "systemd", "user", "ksai_proc.service"))
+
+    crontab = read(os.path.join(fake_dir, "crontab.txt"))
+    assert "0 * * * * /usr/bin/true" in crontab
+    reboot = [l for l in crontab.splitlines() if l.startswith("@reboot")]
+    assert len(reboot) == 1
+    assert "internal-scheduler" in reboot[0]
+    assert test_env["state_file"] in reboot[0]
+
+    # Installing again replaces the entry instead of adding a second one
+    test_env["run"](["daemon", "install", "--cron"])
+    assert len([l for l in read(os.path.join(fake_dir, "crontab.txt")).splitlines() if l.startswith("@reboot")]) == 1
+
+    assert "@reboot entry installed" in test_env["run"](["daemon", "status"]).stdout
+
+    test_env["run"](["daemon", "uninstall"])
+    assert read(os.path.join(fake_dir, "crontab.txt")) == "0 * * * * /usr/bin/true\n"
+
+def test_daemon_revives_saved_processes_on_startup(test_env):
+    """Test that a daemon started at boot brings back processes that were running before."""
+    dead = subprocess.Popen(["/bin/true"])
+    dead.wait()
+    previous_boot = "00000000-0000-0000-0000-000000000000"
+    entry = {
+        "cmd_str": "/bin/sleep 103",
+        "log_file": os.path.join(test_env["logs_dir"], "saved.log"),
+        "status": "running",
+        "start_time": time.time() - 100,
+        "timeout_sec": None,
+        "script_name": "sleep",
+        "working_dir": test_env["test_dir"],
+        "display_name": "saved",
+        "boot_id": previous_boot,
+    }
+    old_daemon = dict(entry, cmd_str="ksai_proc internal-scheduler", display_name="ksai_scheduler_daemon")
+    with open(test_env["state_file"], "w") as f:
+        json.dump({"schema_version": 2, "processes": {str(dead.pid): entry, "999999": old_daemon}}, f)
+
+    # Started the way systemd or cron would, without a CLI reaping first
+    daemon = subprocess.Popen([KSAI_PROC_BIN, "internal-scheduler"], env=test_env["env"], cwd=test_env["test_dir"],
+                              stdout=subprocess.DEVNULL, stderr=subprocess.DEVNULL)
+    try:
+        time.sleep(2)
+        with open(test_env["state_file"], "r") as f:
+            state = json.load(f)["processes"]
+
+        saved = [p for pid, p in state.items() if p["display_name"] == "saved"]
+        assert len(saved) == 1
+        assert saved[0]["status"] == "running"
+        assert saved[0]["cmd_str"] == "/bin/sleep 103"
+
+        # The stale daemon entry from an older version is dropped, not respawned
+        assert not any(p["display_name"] == "ksai_scheduler_daemon" for p in state.values())
+        assert read(os.path.join(test_env["logs_dir"], "daemon.pid")).strip() == str(daemon.pid)
+    finally:
+        daemon.send_signal(signal.SIGKILL)
+        daemon.wait()
+
+def daemon_processes(state_file):
+    """PIDs of daemons pinned to this state file, found through their environment."""
+    pids = []
+    for entry in os.listdir("/proc"):
+        if not entry.isdigit():
+            continue
+        try:
+            with open(f"/proc/{entry}/cmdline", "rb") as f:
+                if b"internal-scheduler" not in f.read():
+                    continue
+            with open(f"/proc/{entry}/environ", "rb") as f:
+                if f"KSAI_PROC_LOG_JSON={state_file}".encode() in f.read().split(b"\0"):
+                    with open(f"/proc/{entry}/stat", "r") as s:
+                        if s.read().rsplit(")", 1)[1].split()[0] != "Z":
+                            pids.append(int(entry))
+        except OSError:
+            pass
+    return pids
+
+def test_daemon_start_stop_restart(test_env):
+    """Test the daemon control commands and that the daemon stays out of the process list."""
+    pid_file = os.path.join(test_env["logs_dir"], "daemon.pid")
+    res = test_env["run"](["daemon", "start"])
+    assert "Scheduler daemon started" in res.stdout, res.stderr
+    pid = int(read(pid_file).strip())
+    assert daemon_processes(test_env["state_file"]) == [pid]
+
+    assert "already running" in test_env["run"](["daemon", "start"]).stdout
+    assert f"running (PID {pid}" in test_env["run"](["daemon", "status"]).stdout
+
+    # Detached: own session, no stdin, no hold on the caller's directory
+    assert os.getsid(pid) == pid
+    assert os.readlink(f"/proc/{pid}/fd/0") == "/dev/null"
+    assert os.readlink(f"/proc/{pid}/cwd") == "/"
+
+    list_out = test_env["run"](["list"]).stdout
+    assert "ksai_scheduler_daemon" not in list_out and "internal-scheduler" not in list_out
+
+    res = test_env["run"](["daemon", "restart"])
+    assert f"(PID {pid}) stopped" in res.stdout
+    new_pid = int(read(pid_file).strip())
+    assert new_pid != pid
+    assert daemon_processes(test_env["state_file"]) == [new_pid]
+
+    res = test_env["run"](["daemon", "stop"])
+    assert f"(PID {new_pid}) stopped" in res.stdout
+    assert daemon_processes(test_env["state_file"]) == []
+    assert "daemon:   not running" in test_env["run"](["daemon", "status"]).stdout
+
+def test_concurrent_clis_start_one_daemon(test_env):
+    """Test that CLIs racing to start the daemon end up with exactly one."""
+    import threading
+    threads = [threading.Thread(target=test_env["run"], args=(["list"],)) for _ in range(8)]
+    for t in threads:
+        t.start()
+    for t in threads:
+        t.join()
+    time.sleep(1)
+    pids = daemon_processes(test_env["state_file"])
+    assert len(pids) == 1
+    assert read(os.path.join(test_env["logs_dir"], "daemon.pid")).strip() == str(pids[0])
+
+def test_daemon_exits_when_pidfile_removed(test_env):
+    """Test that a daemon whose pidfile is gone steps aside instead of running unsupervised."""
+    test_env["run"](["daemon", "start"])
+    pid_file = os.path.join(test_env["logs_dir"], "daemon.pid")
+    pid = int(read(pid_file).strip())
+    os.remove(pid_file)
+    time.sleep(2)
+    assert pid not in daemon_processes(test_env["state_file"])
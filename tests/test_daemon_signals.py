@@ -0,0 +1,117 @@
+import pytest
+import os
+import json
+import time
+import signal
+
+def write_config(test_env, text):
+    path = os.path.join(test_env["test_dir"], "config.toml")
+    with open(path, "w") as f:
+        f.write(text)
+    return path
+
+def use_config(test_env):
+    """Point ksai_proc at config.toml in the test dir; it may be written later."""
+    test_env["env"]["KSAI_PROC_CONFIG"] = os.path.join(test_env["test_dir"], "config.toml")
+
+def entries(test_env, name):
+    with open(test_env["state_file"], 'r') as f:
+        state = json.load(f)["processes"]
+    return {pid: p for pid, p in state.items() if p["display_name"] == name}
+
+def scheduler_log(test_env):
+    with open(os.path.join(test_env["logs_dir"], "scheduler.log"), 'r') as f:
+        return f.read()
+
+def daemon_pid(test_env):
+    with open(os.path.join(test_env["logs_dir"], "daemon.pid"), 'r') as f:
+        return int(f.read().strip())
+
+def alive(pid):
+    try:
+        os.kill(pid, 0)
+        return True
+    except OSError:
+        return False
+
+def test_sigterm_leaves_processes_running(test_env):
+    """Test that stopping the daemon shuts it down cleanly without touching managed processes."""
+    test_env["run"](["run", "--name", "survivor", "--no-tui", "/bin/sleep", "100"])
+    pid = int(list(entries(test_env, "survivor").keys())[0])
+
+    res = test_env["run"](["daemon", "stop"])
+    assert "stopped" in res.stdout
+
+    log = scheduler_log(test_env)
+    assert "Shutting down" in log
+    assert "Scheduler daemon stopped." in log
+    assert alive(pid)
+    assert entries(test_env, "survivor")[str(pid)]["status"] == "running"
+
+def test_on_shutdown_stop(test_env):
+    """Test that on_shutdown = "stop" takes managed processes down with the daemon."""
+    use_config(test_env)
+    write_config(test_env, '[scheduler]\non_shutdown = "stop"\n\n[process]\nstop_signal = "SIGTERM"\n')
+    test_env["run"](["run", "--name", "one", "--no-tui", "/bin/sleep", "100"])
+    test_env["run"](["run", "--name", "two", "--no-tui", "/bin/sleep", "100"])
+    pids = [int(pid) for name in ["one", "two"] for pid in entries(test_env, name)]
+
+    test_env["run"](["daemon", "stop"])
+    for name in ["one", "two"]:
+        assert all(p["status"] == "killed (shutdown)" for p in entries(test_env, name).values())
+    time.sleep(0.5)
+    assert not any(alive(pid) for pid in pids)
+
+def test_sighup_reloads_config(test_env):
+    """Test that `daemon reload` applies config changes without restarting the daemon."""
+    use_config(test_env)
+    test_env["run"](["run", "--name", "chatty", "--no-tui", "python3", "-u", "-c",
+                     "import time\nwhile True:\n    print('x' * 100, flush=True)\n    time.sleep(0.01)"])
+    pid = daemon_pid(test_env)
+    log_file = list(entries(test_env, "chatty").values())[0]["log_file"]
+
+    write_config(test_env, '[scheduler]\ntick_ms = 200\n\n[logs]\nmax_size = "2K"\n')
+    res = test_env["run"](["daemon", "reload"])
+    assert f"PID {pid}" in res.stdout
+
+    for _ in range(20):
+        time.sleep(0.5)
+        if os.path.exists(log_file + ".1"):
+            break
+    assert os.path.exists(log_file + ".1")
+    assert daemon_pid(test_env) == pid
+    assert "Reloaded" in scheduler_log(test_env)
+
+    # A broken file is reported and the settings in use are kept
+    write_config(test_env, '[process]\nstop_signal = "SIGFOO"\n')
+    os.kill(pid, signal.SIGHUP)
+    time.sleep(1)
+    assert "Keeping the current config" in scheduler_log(test_env)
+    assert alive(pid)
+
+def test_daemon_wakes_on_schedule_change_and_child_exit(test_env):
+    """Test that the daemon reacts right away instead of waiting for its next tick."""
+    use_config(test_env)
+    write_config(test_env, '[scheduler]\ntick_ms = 60000\n')
+    test_env["run"](["daemon", "start"])
+    time.sleep(1)
+
+    test_env["run"](["schedule", "add", "--name", "eager", "--every", "1h", "/bin/sleep", "100"])
+    job = {}
+    for _ in range(10):
+        time.sleep(0.3)
+        job = entries(test_env, "S:eager")
+        if job:
+            break
+    assert job, "Scheduled job did not start before the next tick"
+
+    # The job is the daemon's child, so its exit arrives as SIGCHLD
+    pid = list(job.keys())[0]
+    os.kill(int(pid), signal.SIGKILL)
+    for _ in range(10):
+        time.sleep(0.3)
+        proc = entries(test_env, "S:eager")[pid]
+        if proc["status"] != "running":
+            break
+    assert proc["status"] == "completed"
+    assert proc["exit_code"] == 128 + signal.SIGKILL
@@ -0,0 +1,71 @@
+import pytest
+import os
+import time
+import json
+
+def read_journal(logs_dir):
+    path = os.path.join(logs_dir, "events.jsonl")
+    if not os.path.exists(path):
+        return []
+    with open(path, 'r') as f:
+        return [json.loads(l) for l in f if l.strip()]
+
+def wait_for_event(logs_dir, kind, name, timeout=6):
+    deadline = time.time() + timeout
+    while time.time() < deadline:
+        for e in read_journal(logs_dir):
+            if e["kind"] == kind and e["name"] == name:
+                return e
+        time.sleep(0.2)
+    return None
+
+def test_journal_records_lifecycle(test_env):
+    """Test that spawn, kill and exit transitions are journaled with their actor."""
+    test_env["run"](["run", "--name", "ev_sleep", "--no-tui", "/bin/sleep", "100"])
+    spawned = wait_for_event(test_env["logs_dir"], "spawned", "ev_sleep")
+    assert spawned is not None
+    assert spawned["actor"] == "cli"
+    assert "sleep" in spawned["detail"]
+
+    test_env["run"](["stop", spawned["pid"]])
+    killed = wait_for_event(test_env["logs_dir"], "killed", "ev_sleep")
+    assert killed is not None
+    assert killed["detail"] == "manual"
+
+    test_env["run"](["run", "--name", "ev_exit", "--no-tui", "/bin/sh", "-c", "exit 3"])
+    exited = wait_for_event(test_env["logs_dir"], "exited", "ev_exit")
+    assert exited is not None
+
+def test_events_command_filters_by_name(test_env):
+    """Test that `events --name` only prints the matching process."""
+    test_env["run"](["run", "--name", "ev_a", "--no-tui", "/bin/sh", "-c", "exit 0"])
+    test_env["run"](["run", "--name", "ev_b", "--no-tui", "/bin/sh", "-c", "exit 0"])
+    assert wait_for_event(test_env["logs_dir"], "spawned", "ev_b") is not None
+
+    result = test_env["run"](["events", "--name", "ev_a"])
+    assert result.returncode == 0
+    assert "spawned ev_a" in result.stdout
+    assert "ev_b" not in result.stdout
+
+def test_journal_is_compacted(test_env):
+    """Test that the daemon drops old events and caps the journal at the newest 10000."""
+    os.makedirs(test_env["logs_dir"], exist_ok=True)
+    now = time.time()
+    def event(ts, name):
+        return json.dumps({"ts": ts, "kind": "spawned", "actor": "cli", "pid": "1", "name": name, "detail": None})
+    with open(os.path.join(test_env["logs_dir"], "events.jsonl"), 'w') as f:
+        f.write(event(now - 90 * 86400, "ancient") + "\n")
+        for i in range(10050):
+            f.write(event(now - 60 + i * 0.001, f"recent_{i}") + "\n")
+
+    # Any command starts the daemon, which compacts right away
+    test_env["run"](["list"])
+    deadline = time.time() + 6
+    while time.time() < deadline and len(read_journal(test_env["logs_dir"])) > 10000:
+        time.sleep(0.2)
+
+    names = [e["name"] for e in read_journal(test_env["logs_dir"])]
+    assert len(names) <= 10000
+    assert "ancient" not in names
+    assert "recent_10049" in names
+    assert "recent_49" not in names
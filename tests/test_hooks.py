@@ -0,0 +1,96 @@
+import pytest
+import os
+import time
+import json
+import threading
+from http.server import BaseHTTPRequestHandler, HTTPServer
+
+def wait_for_file(path, timeout=6):
+    deadline = time.time() + timeout
+    while time.time() < deadline:
+        if os.path.exists(path) and os.path.getsize(path) > 0:
+            return True
+        time.sleep(0.2)
+    return False
+
+def test_on_exit_hook_env(test_env):
+    """Test that a per-process on_exit hook runs with event details in the environment."""
+    out = os.path.join(test_env["test_dir"], "exit.txt")
+    test_env["run"](["run", "--name", "exit_hook", "--no-tui",
+                     "--on-exit", f'echo "$KSAI_EVENT $KSAI_NAME" >> {out}',
+                     "/bin/sh", "-c", "exit 0"])
+
+    assert wait_for_file(out)
+    with open(out, 'r') as f:
+        assert "exit exit_hook" in f.read()
+
+def test_on_crash_hook_gets_exit_code(test_env):
+    """Test that a process started from the CLI reports its exit code to the crash hook."""
+    out = os.path.join(test_env["test_dir"], "crash.txt")
+    test_env["run"](["run", "--name", "crash_hook", "--no-tui",
+                     "--on-crash", f'echo "$KSAI_EVENT $KSAI_EXIT_CODE" >> {out}',
+                     "/bin/sh", "-c", "sleep 0.5; exit 3"])
+
+    assert wait_for_file(out)
+    with open(out, 'r') as f:
+        assert "crash 3" in f.read()
+
+    with open(test_env["state_file"], 'r') as f:
+        state = json.load(f)["processes"]
+    info = next(p for p in state.values() if p["display_name"] == "crash_hook")
+    assert info["exit_code"] == 3
+
+def test_on_timeout_hook_json_stdin(test_env):
+    """Test that the timeout hook receives the event as JSON on stdin."""
+    out = os.path.join(test_env["test_dir"], "timeout.json")
+    test_env["run"](["run", "--name", "timeout_hook", "--no-tui", "--timeout", "1s",
+                     "--on-timeout", f"cat > {out}",
+                     "/bin/sleep", "100"])
+
+    assert wait_for_file(out)
+    time.sleep(0.2)
+    with open(out, 'r') as f:
+        event = json.load(f)
+    assert event["event"] == "timeout"
+    assert event["name"] == "timeout_hook"
+    assert event["status"] == "killed (timeout)"
+
+def test_global_hooks_and_webhook(test_env):
+    """Test global hook management and webhook delivery."""
+    received = []
+
+    class Handler(BaseHTTPRequestHandler):
+        def do_POST(self):
+            length = int(self.headers["Content-Length"])
+            received.append(json.loads(self.rfile.read(length)))
+            self.send_response(200)
+            self.end_headers()
+
+        def log_message(self, *args):
+            pass
+
+    server = HTTPServer(("127.0.0.1", 0), Handler)
+    port = server.server_address[1]
+    threading.Thread(target=server.serve_forever, daemon=True).start()
+
+    try:
+        res = test_env["run"](["hooks", "set", "webhook", f"http://127.0.0.1:{port}/events"])
+        assert "set" in res.stdout
+        res = test_env["run"](["hooks", "show"])
+        assert f"http://127.0.0.1:{port}/events" in res.stdout
+
+        res = test_env["run"](["hooks", "set", "on_bogus", "true"])
+        assert "Unknown hook" in res.stdout
+
+        test_env["run"](["run", "--name", "webhook_test", "--no-tui", "/bin/sh", "-c", "exit 0"])
+
+        deadline = time.time() + 6
+        while time.time() < deadline and not received:
+            time.sleep(0.2)
+        assert any(e["event"] == "exit" and e["name"] == "webhook_test" for e in received)
+
+        test_env["run"](["hooks", "unset", "webhook"])
+        res = test_env["run"](["hooks", "show"])
+        assert "(none)" in res.stdout
+    finally:
+        server.shutdown()
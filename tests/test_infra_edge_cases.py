@@ -14,7 +14,7 @@ def test_zombie_handling(test_env):
     
     # Get PID
     with open(test_env["state_file"], 'r') as f:
-        state = json.load(f)
+        state = json.load(f)["processes"]
         pid = [k for k, v in state.items() if v["display_name"] == "zombie_candidate"][0]
         
     # Kill externally (SIGKILL)
@@ -31,7 +31,7 @@ def test_zombie_handling(test_env):
     
     # Verify status in state file
     with open(test_env["state_file"], 'r') as f:
-        state = json.load(f)
+        state = json.load(f)["processes"]
         if pid in state:
             status = state[pid]["status"].lower()
             assert "running" not in status
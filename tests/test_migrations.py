@@ -0,0 +1,70 @@
+import pytest
+import os
+import json
+
+LEGACY_STATE = {
+    "999999": {
+        "cmd_str": "/bin/true",
+        "log_file": "/tmp/nonexistent.log",
+        "status": "completed",
+        "start_time": 1700000000.0,
+        "timeout_sec": None,
+        "script_name": "legacy_script",
+        "working_dir": "/tmp",
+        "display_name": ""
+    }
+}
+
+LEGACY_JOBS = [
+    {"name": "legacy_job", "command": "/bin/true", "frequency": "1h"}
+]
+
+def write_legacy(test_env):
+    with open(test_env["state_file"], 'w') as f:
+        json.dump(LEGACY_STATE, f)
+    with open(test_env["schedule_file"], 'w') as f:
+        json.dump(LEGACY_JOBS, f)
+
+def test_migrate_check_reports_without_writing(test_env):
+    """Test that `migrate --check` lists pending migrations and leaves the files alone."""
+    write_legacy(test_env)
+
+    result = test_env["run"](["migrate", "--check"])
+    assert result.returncode == 0
+    assert "schema version 1 -> 2" in result.stdout
+    assert "set missing display_name to 'legacy_script'" in result.stdout
+
+    with open(test_env["state_file"], 'r') as f:
+        assert json.load(f) == LEGACY_STATE
+
+def test_migrate_applies_and_loads_legacy_files(test_env):
+    """Test that migrating wraps the files in a versioned envelope and keeps their data."""
+    write_legacy(test_env)
+
+    result = test_env["run"](["migrate"])
+    assert result.returncode == 0
+
+    with open(test_env["state_file"], 'r') as f:
+        state = json.load(f)
+    assert state["schema_version"] == 2
+    assert state["processes"]["999999"]["display_name"] == "legacy_script"
+
+    with open(test_env["schedule_file"], 'r') as f:
+        jobs = json.load(f)
+    assert jobs["schema_version"] == 2
+    assert jobs["jobs"][0]["enabled"] is True
+
+    result = test_env["run"](["migrate", "--check"])
+    assert "already at schema version 2" in result.stdout
+
+def test_newer_schema_is_left_alone(test_env):
+    """Test that a file from a newer version is reported and never overwritten."""
+    newer = {"schema_version": 99, "processes": {}}
+    with open(test_env["state_file"], 'w') as f:
+        json.dump(newer, f)
+
+    result = test_env["run"](["list"])
+    assert "newer" in result.stderr
+
+    with open(test_env["state_file"], 'r') as f:
+        assert json.load(f) == newer
@@ -0,0 +1,63 @@
+import pytest
+import os
+import json
+import time
+import subprocess
+
+def fake_entry(pid, **overrides):
+    entry = {
+        "cmd_str": "/bin/sleep 100",
+        "log_file": "/dev/null",
+        "status": "running",
+        "start_time": time.time(),
+        "timeout_sec": None,
+        "script_name": "sleep",
+        "working_dir": "/tmp",
+        "display_name": "impostor",
+    }
+    entry.update(overrides)
+    return entry
+
+def write_state(test_env, processes):
+    with open(test_env["state_file"], 'w') as f:
+        json.dump({"schema_version": 2, "processes": processes}, f)
+
+def test_launch_records_identity(test_env):
+    """Test that the kernel start time and boot ID are stored at launch."""
+    test_env["run"](["run", "--name", "ident", "--no-tui", "/bin/sleep", "100"])
+    with open(test_env["state_file"], 'r') as f:
+        state = json.load(f)["processes"]
+    pid, proc = [(k, v) for k, v in state.items() if v["display_name"] == "ident"][0]
+
+    with open(f"/proc/{pid}/stat", 'r') as f:
+        stat = f.read()
+    expected = int(stat[stat.rfind(')') + 2:].split()[19])
+    assert proc["start_ticks"] == expected
+    with open("/proc/sys/kernel/random/boot_id", 'r') as f:
+        assert proc["boot_id"] == f.read().strip()
+
+    test_env["run"](["stop", pid])
+
+def check_not_signalled(test_env, override):
+    stranger = subprocess.Popen(["/bin/sleep", "100"])
+    try:
+        write_state(test_env, {str(stranger.pid): fake_entry(stranger.pid, **override)})
+
+        test_env["run"](["stop", str(stranger.pid)])
+        time.sleep(0.5)
+        assert stranger.poll() is None, "unrelated process was killed"
+
+        with open(test_env["state_file"], 'r') as f:
+            state = json.load(f)["processes"]
+        assert state[str(stranger.pid)]["status"] == "lost (PID reused)"
+    finally:
+        stranger.kill()
+        stranger.wait()
+
+def test_reused_pid_is_never_signalled(test_env):
+    """Test that a PID now owned by a process with a different start time is not killed."""
+    check_not_signalled(test_env, {"start_ticks": 1})
+
+def test_pid_from_previous_boot_is_never_signalled(test_env):
+    """Test that a PID recorded under a different boot ID is not killed."""
+    check_not_signalled(test_env, {"boot_id": "00000000-0000-0000-0000-000000000000"})
@@ -0,0 +1,95 @@
+import pytest
+import os
+import json
+import subprocess
+from conftest import KSAI_PROC_BIN
+
+def xdg_env(test_env):
+    """Environment without the KSAI_PROC_* path overrides, rooted in the test dir."""
+    env = {k: v for k, v in test_env["env"].items() if not k.startswith("KSAI_PROC_")}
+    env["XDG_STATE_HOME"] = os.path.join(test_env["test_dir"], "state")
+    env["XDG_CONFIG_HOME"] = os.path.join(test_env["test_dir"], "config")
+    return env
+
+def run(env, cwd, args):
+    return subprocess.run([KSAI_PROC_BIN] + args, capture_output=True, text=True, env=env, cwd=cwd)
+
+def kill_tracked(state_file):
+    if not os.path.exists(state_file):
+        return
+    with open(state_file, 'r') as f:
+        pids = list(json.load(f)["processes"])
+    pid_file = os.path.join(os.path.dirname(state_file), "daemon.pid")
+    if os.path.exists(pid_file):
+        with open(pid_file, 'r') as f:
+            pids.append(f.read().strip())
+    for pid in pids:
+        try:
+            os.kill(int(pid), 9)
+        except:
+            pass
+
+def test_profiles_are_isolated(test_env):
+    """Test that processes launched in one profile are invisible to another."""
+    env = xdg_env(test_env)
+    state_home = os.path.join(env["XDG_STATE_HOME"], "ksai_proc")
+    default_state = os.path.join(state_home, "runningscripts.json")
+    work_state = os.path.join(state_home, "profiles", "work", "runningscripts.json")
+    try:
+        run(env, test_env["test_dir"], ["run", "--no-tui", "--name", "home_job", "/bin/sleep", "101"])
+        run(env, test_env["test_dir"], ["--profile", "work", "run", "--no-tui", "--name", "work_job", "/bin/sleep", "102"])
+
+        assert os.path.exists(default_state)
+        assert os.path.exists(work_state)
+
+        default_list = run(env, test_env["test_dir"], ["list"]).stdout
+        work_list = run(env, test_env["test_dir"], ["list", "--profile", "work"]).stdout
+        assert "home_job" in default_list and "work_job" not in default_list
+        assert "work_job" in work_list and "home_job" not in work_list
+
+        # Each profile gets its own scheduler daemon
+        pids = []
+        for state in [default_state, work_state]:
+            with open(os.path.join(os.path.dirname(state), "daemon.pid"), 'r') as f:
+                pids.append(f.read().strip())
+        assert pids[0] != pids[1]
+
+        profiles = run(env, test_env["test_dir"], ["profiles"]).stdout
+        assert "work" in profiles and "default" in profiles
+    finally:
+        kill_tracked(default_state)
+        kill_tracked(work_state)
+
+def test_config_file_overrides_state_dir(test_env):
+    """Test that a profile's state_dir from config.toml is used."""
+    env = xdg_env(test_env)
+    custom = os.path.join(test_env["test_dir"], "custom_state")
+    config_dir = os.path.join(env["XDG_CONFIG_HOME"], "ksai_proc")
+    os.makedirs(config_dir)
+    with open(os.path.join(config_dir, "config.toml"), 'w') as f:
+        f.write(f'[profiles.ci]\nstate_dir = "{custom}"\n')
+
+    state_file = os.path.join(custom, "runningscripts.json")
+    try:
+        run(env, test_env["test_dir"], ["--profile", "ci", "run", "--no-tui", "--name", "ci_job", "/bin/sleep", "103"])
+        assert os.path.exists(state_file)
+        assert os.path.isdir(os.path.join(custom, "logs"))
+    finally:
+        kill_tracked(state_file)
+
+def test_invalid_profile_names_are_rejected(test_env):
+    """Test that profile names which could escape the profiles directory are refused."""
+    env = xdg_env(test_env)
+    for name in ["../../x", "/tmp/x", "a b", ""]:
+        result = run(env, test_env["test_dir"], ["--profile", name, "list"])
+        assert result.returncode != 0
+        assert "invalid profile name" in result.stderr
+
+    env["KSAI_PROC_PROFILE"] = "a/b"
+    result = run(env, test_env["test_dir"], ["list"])
+    assert result.returncode != 0
+    assert "invalid profile name" in result.stderr
+
+    # Nothing was written anywhere, in or out of the state home
+    assert not os.path.exists(env["XDG_STATE_HOME"])
+    assert not os.path.exists(os.path.join(test_env["test_dir"], "x"))
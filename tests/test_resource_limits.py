@@ -0,0 +1,62 @@
+import pytest
+import os
+import time
+import json
+
+def read_log(test_env, name):
+    with open(test_env["state_file"], 'r') as f:
+        state = json.load(f)["processes"]
+        proc = [v for v in state.values() if v["display_name"] == name][0]
+    with open(proc["log_file"], 'r') as f:
+        return f.read()
+
+def test_rlimits_applied(test_env):
+    """Test that --nofile and --max-memory are applied via setrlimit."""
+    res = test_env["run"](["run", "--name", "limits_test", "--no-tui", "--nofile", "64", "--max-memory", "512M",
+                           "/bin/sh", "-c", "ulimit -n; ulimit -v"])
+    assert res.returncode == 0
+    time.sleep(1)
+
+    lines = read_log(test_env, "limits_test").split()
+    assert "64" in lines
+    assert "524288" in lines # ulimit -v reports KB
+
+def test_invalid_size(test_env):
+    """Test invalid size input."""
+    res = test_env["run"](["run", "--max-memory", "lots", "/bin/ls"])
+    assert res.returncode != 0
+    assert "invalid size format" in res.stderr.lower()
+
+def test_watchdog_kills_on_rss(test_env):
+    """Test that the daemon watchdog kills a process exceeding --watch-rss and records why."""
+    script = "x = ' ' * 50_000_000; import time; time.sleep(100)"
+    test_env["run"](["run", "--name", "hog", "--no-tui", "--watch-rss", "10M", "python3", "-c", script])
+
+    time.sleep(4)
+
+    with open(test_env["state_file"], 'r') as f:
+        state = json.load(f)["processes"]
+        proc = [v for v in state.values() if v["display_name"] == "hog"][0]
+        assert proc["status"].startswith("killed (watchdog: memory")
+
+    assert "WATCHDOG" in read_log(test_env, "hog")
+
+def test_watchdog_alert_only(test_env):
+    """Test that --watch-action alert fires on_unhealthy once and leaves the process running."""
+    out = os.path.join(test_env["test_dir"], "alerts.txt")
+    script = "x = ' ' * 50_000_000; import time; time.sleep(100)"
+    test_env["run"](["run", "--name", "hog_alert", "--no-tui", "--watch-rss", "10M", "--watch-action", "alert",
+                     "--on-unhealthy", f'echo "$KSAI_REASON" >> {out}', "python3", "-c", script])
+
+    time.sleep(5)
+
+    with open(test_env["state_file"], 'r') as f:
+        state = json.load(f)["processes"]
+        proc = [v for v in state.values() if v["display_name"] == "hog_alert"][0]
+        assert proc["status"] == "running"
+
+    with open(out, 'r') as f:
+        alerts = f.read().splitlines()
+    assert len(alerts) == 1
+    assert alerts[0].startswith("memory")
+    assert "WATCHDOG ALERT" in read_log(test_env, "hog_alert")
@@ -54,7 +54,7 @@ def check_process_running(name):
     
     try:
         with open(STATE_FILE, 'r') as f:
-            state = json.load(f)
+            state = json.load(f)["processes"]
             for pid, proc in state.items():
                 if name in proc.get('display_name', '') or name in proc.get('cmd_str', ''):
                     if proc['status'] == 'running':
@@ -131,7 +131,7 @@ def test_revival():
     # 2. Kill the process manually (simulating crash)
     # We need the PID.
     with open(STATE_FILE, 'r') as f:
-        state = json.load(f)
+        state = json.load(f)["processes"]
         pid_to_kill = None
         for pid, proc in state.items():
             if "S:job_rev" in proc.get('display_name', ''):
@@ -159,7 +159,7 @@ def test_revival():
     if check_process_running("S:job_rev"):
         # Verify it's a NEW pid
         with open(STATE_FILE, 'r') as f:
-            state = json.load(f)
+            state = json.load(f)["processes"]
             new_pid = None
             for pid, proc in state.items():
                 if "S:job_rev" in proc.get('display_name', ''):
@@ -14,7 +14,7 @@ def test_schedule_immediate(test_env):
     
     # Check if running
     with open(test_env["state_file"], 'r') as f:
-        state = json.load(f)
+        state = json.load(f)["processes"]
         found = any("S:imm_test" in p["display_name"] for p in state.values())
         assert found
 
@@ -28,7 +28,7 @@ def test_schedule_future(test_env):
     time.sleep(2)
     # Should NOT be running
     with open(test_env["state_file"], 'r') as f:
-        state = json.load(f)
+        state = json.load(f)["processes"]
         found = any("S:future_test" in p["display_name"] for p in state.values())
         assert not found
         
@@ -39,7 +39,7 @@ def test_schedule_future(test_env):
     
     # Should be running
     with open(test_env["state_file"], 'r') as f:
-        state = json.load(f)
+        state = json.load(f)["processes"]
         found = any("S:future_test" in p["display_name"] and p["status"] == "running" for p in state.values())
         assert found
 
@@ -51,7 +51,7 @@ def test_schedule_revival(test_env):
     
     # Kill it
     with open(test_env["state_file"], 'r') as f:
-        state = json.load(f)
+        state = json.load(f)["processes"]
         pid = [k for k, v in state.items() if "S:crash_test" in v["display_name"]][0]
     
     os.kill(int(pid), 9)
@@ -66,7 +66,7 @@ def test_schedule_revival(test_env):
     
     # Verify new PID
     with open(test_env["state_file"], 'r') as f:
-        state = json.load(f)
+        state = json.load(f)["processes"]
         new_pid = [k for k, v in state.items() if "S:crash_test" in v["display_name"] and v["status"] == "running"][0]
         assert new_pid != pid
 
@@ -88,3 +88,32 @@ def test_schedule_management(test_env):
     test_env["run"](["schedule", "remove", "mgmt_test"])
     res = test_env["run"](["schedule", "list"])
     assert "mgmt_test" not in res.stdout
+
+def test_schedule_run_now_and_reenable(test_env):
+    """Test that `schedule run` starts a job right away, even a disabled one, and `schedule start` re-enables it."""
+    future_time = (datetime.now() + timedelta(hours=1)).strftime("%Y-%m-%d %H:%M:%S")
+    test_env["run"](["schedule", "add", "--name", "manual", "--every", "1h", "--start-at", future_time, "/bin/sleep", "100"])
+    test_env["run"](["schedule", "stop", "manual"])
+
+    res = test_env["run"](["schedule", "run", "manual"])
+    assert "will run now" in res.stdout
+    found = False
+    for _ in range(10):
+        time.sleep(0.5)
+        with open(test_env["state_file"], 'r') as f:
+            state = json.load(f)["processes"]
+        if any(p["display_name"] == "S:manual" and p["status"] == "running" for p in state.values()):
+            found = True
+            break
+    assert found, "Triggered job did not start"
+
+    with open(test_env["schedule_file"], 'r') as f:
+        job = json.load(f)["jobs"][0]
+    assert job["run_now"] is False
+    assert job["enabled"] is False
+
+    assert "enabled" in test_env["run"](["schedule", "start", "manual"]).stdout
+    with open(test_env["schedule_file"], 'r') as f:
+        assert json.load(f)["jobs"][0]["enabled"] is True
+
+    assert "not found" in test_env["run"](["schedule", "run", "missing"]).stdout
@@ -4,20 +4,17 @@ import time
 import json
 import signal
 
+def daemon_pid(test_env):
+    with open(os.path.join(test_env["logs_dir"], "daemon.pid"), 'r') as f:
+        return f.read().strip()
+
 def test_scheduler_revival_on_cli(test_env):
     """Test that the scheduler daemon is revived when a CLI command is run."""
     # 1. Start scheduler implicitly
     test_env["run"](["schedule", "list"])
     
     # 2. Get scheduler PID
-    with open(test_env["state_file"], 'r') as f:
-        state = json.load(f)
-        scheduler_pid = None
-        for pid, proc in state.items():
-            if "ksai_scheduler_daemon" in proc["display_name"]:
-                scheduler_pid = pid
-                break
-    
+    scheduler_pid = daemon_pid(test_env)
     assert scheduler_pid, "Scheduler should be running"
     
     # 3. Schedule a job to verify it continues working later
@@ -32,25 +29,19 @@ def test_scheduler_revival_on_cli(test_env):
     # 5. Run a CLI command (e.g., list)
     test_env["run"](["list"])
     
-    # 6. Give the revived scheduler a moment to register itself in the state file
+    # 6. Give the revived scheduler a moment to take the pidfile
     time.sleep(2)
     
     # 7. Verify scheduler revived with NEW PID
-    with open(test_env["state_file"], 'r') as f:
-        state = json.load(f)
-        new_scheduler_pid = None
-        for pid, proc in state.items():
-            if "ksai_scheduler_daemon" in proc["display_name"] and proc["status"] == "running":
-                new_scheduler_pid = pid
-                break
-                
+    new_scheduler_pid = daemon_pid(test_env)
     assert new_scheduler_pid, "Scheduler should have revived"
+    os.kill(int(new_scheduler_pid), 0)
     assert new_scheduler_pid != scheduler_pid, "Scheduler should have a new PID"
 
-    # 7. Check if old PID entry is gone/marked killed?
-    # Actually, `ksai_proc list` (triggered above) calls `ensure_scheduler_running`
-    # AND `reap_processes`. So `reap_processes` should have marked the old scheduler as dead/completed.
-    # The new scheduler instance is a separate process.
+    # 7. The daemon is tracked by its pidfile only, never as a process in the state file
+    with open(test_env["state_file"], 'r') as f:
+        state = json.load(f)["processes"]
+    assert not any("ksai_scheduler_daemon" in p["display_name"] for p in state.values())
     
     # Optional: Verify scheduled job is still being monitored.
     # We can check if the new scheduler picked up the job state.
@@ -0,0 +1,103 @@
+import pytest
+import os
+import time
+import sqlite3
+import json
+
+def use_sqlite(test_env):
+    test_env["env"]["KSAI_PROC_STATE_BACKEND"] = "sqlite"
+    return os.path.join(test_env["logs_dir"], "ksai_proc.db")
+
+def wait_for_pid(test_env, name, timeout=6):
+    deadline = time.time() + timeout
+    while time.time() < deadline:
+        result = test_env["run"](["list"])
+        for line in result.stdout.splitlines():
+            if name in line:
+                return line.split()[0]
+        time.sleep(0.2)
+    return None
+
+def test_sqlite_backend_tracks_processes(test_env):
+    """Test that the SQLite backend stores processes and jobs in the database."""
+    db = use_sqlite(test_env)
+    test_env["run"](["run", "--name", "sql_sleep", "--no-tui", "/bin/sleep", "100"])
+    pid = wait_for_pid(test_env, "sql_sleep")
+    assert pid is not None
+    assert os.path.exists(db)
+    assert not os.path.exists(test_env["state_file"])
+
+    conn = sqlite3.connect(db)
+    row = conn.execute("SELECT status FROM processes WHERE display_name = 'sql_sleep'").fetchone()
+    conn.close()
+    assert row == ("running",)
+
+    test_env["run"](["schedule", "add", "--name", "sql_job", "--every", "1h", "/bin/true"])
+    result = test_env["run"](["schedule", "list"])
+    assert "sql_job" in result.stdout
+
+    test_env["run"](["stop", pid])
+
+def test_sqlite_run_history_survives_remove(test_env):
+    """Test that run history is kept after the process entry is removed."""
+    use_sqlite(test_env)
+    test_env["run"](["run", "--name", "sql_hist", "--no-tui", "/bin/sleep", "100"])
+    pid = wait_for_pid(test_env, "sql_hist")
+    assert pid is not None
+
+    test_env["run"](["stop", pid])
+    test_env["run"](["remove", pid])
+    result = test_env["run"](["list"])
+    assert "sql_hist" not in result.stdout
+
+    result = test_env["run"](["runs", "--name", "sql_hist"])
+    assert pid in result.stdout
+    assert "killed (manual)" in result.stdout
+
+def test_sqlite_open_failure_is_fatal(test_env):
+    """Test that an unusable database is an error rather than a silent switch to the JSON file."""
+    db = use_sqlite(test_env)
+    os.makedirs(db) # a directory can't be opened as a database
+
+    res = test_env["run"](["run", "--name", "no_db", "--no-tui", "/bin/sleep", "10"])
+    assert res.returncode != 0
+    assert "could not open the SQLite state" in res.stderr
+    assert not os.path.exists(test_env["state_file"])
+
+def test_sqlite_rows_are_migrated(test_env):
+    """Test that a database from before row versions is upgraded and old rows go through the migrations."""
+    db = use_sqlite(test_env)
+    def row(name, **fields):
+        return json.dumps({"cmd_str": "/bin/true", "log_file": "/dev/null", "status": "completed",
+                           "start_time": time.time(), "script_name": name, **fields})
+
+    # The layout of SQLite schema version 1
+    conn = sqlite3.connect(db)
+    conn.executescript("""
+        CREATE TABLE processes (pid TEXT PRIMARY KEY, display_name TEXT NOT NULL, status TEXT NOT NULL,
+                                start_time REAL NOT NULL, data TEXT NOT NULL);
+        CREATE TABLE jobs (position INTEGER NOT NULL, name TEXT NOT NULL, data TEXT NOT NULL);
+        CREATE TABLE runs (pid TEXT NOT NULL, display_name TEXT NOT NULL, cmd TEXT NOT NULL,
+                           start_time REAL NOT NULL, end_time REAL, status TEXT NOT NULL,
+                           exit_code INTEGER, PRIMARY KEY (pid, start_time));
+        PRAGMA user_version = 1;
+    """)
+    conn.execute("INSERT INTO processes VALUES ('999991', 'old_row', 'completed', 0, ?)",
+                 (row("old_row", display_name="old_row"),))
+    conn.commit()
+    conn.close()
+
+    result = test_env["run"](["list"])
+    assert "old_row" in result.stdout
+
+    conn = sqlite3.connect(db)
+    assert conn.execute("PRAGMA user_version").fetchone() == (2,)
+    # A row in JSON schema version 1, which had no display name yet
+    conn.execute("INSERT INTO processes (pid, display_name, status, start_time, schema_version, data) "
+                 "VALUES ('999992', '', 'completed', 0, 1, ?)", (row("legacy_script"),))
+    conn.commit()
+    conn.close()
+
+    result = test_env["run"](["list"])
+    assert "old_row" in result.stdout
+    assert "legacy_script" in result.stdout
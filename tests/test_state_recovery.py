@@ -0,0 +1,76 @@
+import pytest
+import os
+import time
+import json
+import subprocess
+
+def test_corrupt_state_restored_from_backup(test_env):
+    """Test that a corrupt state file is detected loudly and restored from the backup."""
+    test_env["run"](["run", "--name", "survivor", "--no-tui", "/bin/sleep", "100"])
+    # Wait for the daemon to rewrite the state at least once so a backup exists
+    backup = test_env["state_file"] + ".bak"
+    deadline = time.time() + 6
+    while time.time() < deadline:
+        if os.path.exists(backup) and "survivor" in open(backup).read():
+            break
+        time.sleep(0.2)
+    assert os.path.exists(backup)
+
+    with open(test_env["state_file"], 'w') as f:
+        f.write('{"12345": {"cmd_str": "trunc')
+
+    result = test_env["run"](["list"])
+    # The daemon wakes on the change and may restore the file before the CLI reads it
+    with open(os.path.join(test_env["logs_dir"], "scheduler.log"), 'r') as f:
+        assert "corrupt" in result.stderr + f.read()
+    assert "survivor" in result.stdout
+
+    with open(test_env["state_file"], 'r') as f:
+        state = json.load(f)["processes"]
+    assert any(p["display_name"] == "survivor" for p in state.values())
+    assert any(n.startswith("runningscripts.json.corrupt-") for n in os.listdir(test_env["logs_dir"]))
+
+def test_doctor_detects_and_repairs(test_env):
+    """Test that doctor reports a corrupt file and --repair fixes it."""
+    with open(test_env["state_file"], 'w') as f:
+        f.write("not json at all")
+
+    result = test_env["run"](["doctor"])
+    assert result.returncode != 0
+    assert "[corrupt]" in result.stdout
+
+    result = test_env["run"](["doctor", "--repair"])
+    assert "[repair]" in result.stdout
+
+    result = test_env["run"](["doctor"])
+    assert result.returncode == 0, result.stdout
+    assert "No problems found" in result.stdout
+
+def test_interrupted_stop_is_recovered(test_env):
+    """Test that a process left "stopping" by a stop that never finished is resolved."""
+    survivor = subprocess.Popen(["/bin/sleep", "100"])
+    gone = subprocess.Popen(["/bin/true"])
+    gone.wait()
+    try:
+        def entry(pid, name, since):
+            return {"cmd_str": "/bin/sleep 100", "log_file": "/dev/null", "status": "stopping",
+                    "start_time": time.time(), "display_name": name, "stopping_since": since}
+        with open(test_env["state_file"], 'w') as f:
+            json.dump({"schema_version": 2, "processes": {
+                str(survivor.pid): entry(survivor.pid, "survivor", time.time() - 3600),
+                str(gone.pid): entry(gone.pid, "gone", time.time() - 3600),
+                "999999": entry(999999, "in_progress", time.time()),
+            }}, f)
+
+        test_env["run"](["list"])
+
+        with open(test_env["state_file"], 'r') as f:
+            state = json.load(f)["processes"]
+        statuses = {p["display_name"]: p["status"] for p in state.values()}
+        assert statuses["survivor"] == "running"
+        assert statuses["gone"] == "killed (stop interrupted)"
+        # A stop that may still be under way is left to finish
+        assert statuses["in_progress"] == "stopping"
+    finally:
+        survivor.kill()
+        survivor.wait()
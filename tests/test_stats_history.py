@@ -0,0 +1,52 @@
+import pytest
+import os
+import time
+import json
+
+def test_stats_recorded_by_daemon(test_env):
+    """Test that the scheduler daemon records CPU/memory samples for running processes."""
+    test_env["run"](["run", "--name", "stats_test", "--no-tui", "/bin/sleep", "100"])
+
+    # The daemon samples every 5s
+    time.sleep(7)
+
+    stats_dir = os.path.join(test_env["logs_dir"], "stats")
+    files = [f for f in os.listdir(stats_dir) if f.startswith("stats_test-")]
+    assert len(files) == 1
+    history_file = os.path.join(stats_dir, files[0])
+    with open(history_file, 'r') as f:
+        samples = [json.loads(l) for l in f if l.strip()]
+    assert len(samples) >= 1
+    assert "cpu" in samples[0] and "mem" in samples[0]
+
+    # Lookup by name and by PID
+    res = test_env["run"](["stats", "stats_test", "--since", "1h"])
+    assert "Stats for 'stats_test'" in res.stdout
+
+    with open(test_env["state_file"], 'r') as f:
+        state = json.load(f)["processes"]
+        pid = [k for k, v in state.items() if v["display_name"] == "stats_test"][0]
+    res = test_env["run"](["stats", pid])
+    assert "Stats for 'stats_test'" in res.stdout
+
+def test_stats_invalid_input(test_env):
+    """Test stats for unknown targets and bad durations."""
+    res = test_env["run"](["stats", "nonexistent"])
+    assert "No statistics recorded" in res.stdout
+
+    res = test_env["run"](["stats", "nonexistent", "--since", "bogus"])
+    assert "Invalid duration" in res.stdout
+
+def test_stale_history_is_swept(test_env):
+    """Test that the daemon prunes the histories of processes that no longer run."""
+    stats_dir = os.path.join(test_env["logs_dir"], "stats")
+    os.makedirs(stats_dir, exist_ok=True)
+    stale = os.path.join(stats_dir, "gone-00000000.jsonl")
+    with open(stale, 'w') as f:
+        f.write(json.dumps({"ts": int(time.time()) - 8 * 86400, "cpu": 1.0, "mem": 1024}) + "\n")
+
+    test_env["run"](["run", "--name", "sweep_test", "--no-tui", "/bin/sleep", "100"])
+    time.sleep(7)
+
+    assert not os.path.exists(stale)
+    assert any(f.startswith("sweep_test-") for f in os.listdir(stats_dir))